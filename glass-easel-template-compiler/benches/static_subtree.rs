@@ -0,0 +1,144 @@
+//! Compare the generated code size and the update cost of templates with static subtrees.
+//!
+//! Run with `cargo bench --bench static_subtree` .
+//! The update cost is measured in node.js with a minimal mock of the runtime callbacks,
+//! i.e. the `DefineElement` `DefineTextNode` and other functions called by the generated code,
+//! so it shows the work done by the generated code itself.
+//! It is skipped if `node` is not found.
+//!
+//! The numbers without static subtree hoisting, i.e. built from the commit before it,
+//! and with it (ns/update is the median of three interleaved runs on the same machine):
+//!
+//! | template       | bytes       | calls/update | ns/update     |
+//! |----------------|-------------|--------------|---------------|
+//! | icon-list      | 1446 → 1422 | 210 → 103    | 13345 → 7151  |
+//! | static-page    | 2254 → 2174 | 40 → 16      | 5812 → 3768   |
+//! | repeated-cards | 1719 → 1479 | 29 → 17      | 4430 → 3350   |
+//! | dynamic-form   | 1337 → 1337 | 21 → 21      | 5921 → 5763   |
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use glass_easel_template_compiler::TmplGroup;
+
+struct Case {
+    name: &'static str,
+    src: &'static str,
+    data: &'static str,
+    update_path_tree: &'static str,
+}
+
+const CASES: [Case; 4] = [
+    Case {
+        name: "icon-list",
+        src: r#"<view class="page"><view class="header"><view class="logo"><view class="logo-a" /><view class="logo-b" /></view><text class="title">Contacts</text></view><view wx:for="{{ list }}" class="item"><view class="icon"><view class="icon-circle"><view class="icon-dot" /></view><view class="icon-bar" /></view><text class="name">{{ item.name }}</text><view class="arrow"><view class="arrow-line" /><view class="arrow-line" /></view></view><view class="footer"><text>Powered by glass-easel</text></view></view>"#,
+        data: r#"{"list":[{"name":"a"},{"name":"b"},{"name":"c"},{"name":"d"},{"name":"e"},{"name":"f"},{"name":"g"},{"name":"h"},{"name":"i"},{"name":"j"},{"name":"k"},{"name":"l"},{"name":"m"},{"name":"n"},{"name":"o"},{"name":"p"},{"name":"q"},{"name":"r"},{"name":"s"},{"name":"t"}]}"#,
+        update_path_tree: r#"{"list":true}"#,
+    },
+    Case {
+        name: "static-page",
+        src: r#"<view class="page"><view class="nav"><view class="nav-item"><text>Home</text></view><view class="nav-item"><text>Docs</text></view><view class="nav-item"><text>About</text></view></view><view class="article"><text class="h1">Getting started</text><view class="p"><text>Templates are compiled into JavaScript functions.</text></view><view class="p"><text>Static markup is created once and never updated.</text></view><view class="note"><view class="note-icon" /><text>Hello, {{ user }}!</text></view><view class="p"><text>Read the guide for more details.</text></view></view><view class="footer"><view class="footer-col"><text>Links</text><view class="link"><text>GitHub</text></view><view class="link"><text>Issues</text></view></view><view class="footer-col"><text>Visits: {{ visits }}</text></view></view></view>"#,
+        data: r#"{"user":"glass-easel","visits":1}"#,
+        update_path_tree: r#"{"visits":true}"#,
+    },
+    Case {
+        name: "repeated-cards",
+        src: r#"<view class="list"><view class="card"><view class="badge"><view class="badge-dot" /><text>New</text></view><text>{{ a }}</text></view><view class="card"><view class="badge"><view class="badge-dot" /><text>New</text></view><text>{{ b }}</text></view><view class="card"><view class="badge"><view class="badge-dot" /><text>New</text></view><text>{{ c }}</text></view><view class="card"><view class="badge"><view class="badge-dot" /><text>New</text></view><text>{{ d }}</text></view></view>"#,
+        data: r#"{"a":"1","b":"2","c":"3","d":"4"}"#,
+        update_path_tree: r#"{"a":true}"#,
+    },
+    Case {
+        name: "dynamic-form",
+        src: r#"<view class="form"><view class="row" wx:for="{{ fields }}"><text class="label">{{ item.label }}</text><input value="{{ item.value }}" placeholder="{{ item.placeholder }}" bind:input="onInput" data:index="{{ index }}" /><text wx:if="{{ item.error }}" class="error">{{ item.error }}</text></view><button disabled="{{ !valid }}" bind:tap="submit">{{ submitText }}</button></view>"#,
+        data: r#"{"fields":[{"label":"a","value":"1"},{"label":"b","value":"2","error":"x"},{"label":"c","value":"3"},{"label":"d","value":"4"}],"valid":true,"submitText":"OK"}"#,
+        update_path_tree: r#"{"fields":true,"valid":true}"#,
+    },
+];
+
+const UPDATE_ROUNDS: usize = 20000;
+
+const NODE_HARNESS: &str = r#"
+const [code, name, data, updatePathTree, rounds] = JSON.parse(require('fs').readFileSync(0, 'utf8'))
+const procGen = eval(code)[name]('')
+const wrapper = new Proxy({}, { get: () => () => {} })
+let calls = 0
+const defs = (c) => {
+  const t = () => { calls += 1 }
+  const e = (tag, generics, init, children) => {
+    calls += 1
+    init({}, c)
+    children(c, t, e, b, f, s, j)
+  }
+  const b = (key, children) => children(c, t, e, b, f, s, j)
+  const f = (list, key, tree, lvalue, itemCb) => {
+    const l = list || []
+    for (let i = 0; i < l.length; i += 1) {
+      itemCb(c, l[i], i, c || tree, c || tree, null, t, e, b, f, s, j)
+    }
+  }
+  const s = () => { calls += 1 }
+  const j = (children) => children(c, t, e, b, f, s, j)
+  return [t, e, b, f, s, j]
+}
+const created = procGen(wrapper, true, data)
+created.C(true, ...defs(true))
+const update = () => {
+  const updated = procGen(wrapper, false, data, JSON.parse(updatePathTree), created.B)
+  updated.C(false, ...defs(false))
+}
+for (let i = 0; i < 1000; i += 1) update()
+calls = 0
+update()
+const callsPerUpdate = calls
+const start = process.hrtime.bigint()
+for (let i = 0; i < rounds; i += 1) update()
+const ns = Number(process.hrtime.bigint() - start) / rounds
+console.log(JSON.stringify([callsPerUpdate, ns]))
+"#;
+
+fn run_update(code: &str, case: &Case) -> Option<(u64, f64)> {
+    let input = format!(
+        "[{},{},{},{},{}]",
+        serde_json::to_string(code).unwrap(),
+        serde_json::to_string(case.name).unwrap(),
+        case.data,
+        serde_json::to_string(case.update_path_tree).unwrap(),
+        UPDATE_ROUNDS,
+    );
+    let mut child = Command::new("node")
+        .arg("-e")
+        .arg(NODE_HARNESS)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+fn main() {
+    println!(
+        "{:<16}{:>12}{:>16}{:>16}",
+        "template", "bytes", "calls/update", "ns/update"
+    );
+    for case in CASES.iter() {
+        let mut group = TmplGroup::new();
+        group.add_tmpl(case.name, case.src);
+        let bytes = group.get_tmpl_gen_object(case.name).unwrap().len();
+        let code = group.get_tmpl_gen_object_groups().unwrap();
+        let (calls, ns) = match run_update(&code, case) {
+            Some((calls, ns)) => (calls.to_string(), format!("{:.0}", ns)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        println!("{:<16}{:>12}{:>16}{:>16}", case.name, bytes, calls, ns);
+    }
+}
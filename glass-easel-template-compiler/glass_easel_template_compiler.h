@@ -74,6 +74,10 @@ StrRef tmpl_group_export_globals(const TmplGroup *self);
 
 void tmpl_group_free(TmplGroup self);
 
+StrRefArray tmpl_group_get_dependents(const TmplGroup *self,
+                                      const uint8_t *path_buf,
+                                      size_t path_len);
+
 StrRefArray tmpl_group_get_direct_dependencies(const TmplGroup *self,
                                                const uint8_t *path_buf,
                                                size_t path_len);
@@ -102,12 +106,26 @@ StrRefArray tmpl_group_get_script_dependencies(const TmplGroup *self,
                                                const uint8_t *path_buf,
                                                size_t path_len);
 
+StrRefArray tmpl_group_get_script_dependents(const TmplGroup *self,
+                                             const uint8_t *path_buf,
+                                             size_t path_len);
+
 StrRef tmpl_group_get_tmpl_gen_object(const TmplGroup *self,
                                       const uint8_t *path_buf,
                                       size_t path_len);
 
 StrRef tmpl_group_get_tmpl_gen_object_groups(const TmplGroup *self);
 
+StrRefArray tmpl_group_get_topological_order(const TmplGroup *self);
+
+StrRefArray tmpl_group_get_transitive_dependencies(const TmplGroup *self,
+                                                   const uint8_t *path_buf,
+                                                   size_t path_len);
+
+StrRefArray tmpl_group_get_transitive_script_dependencies(const TmplGroup *self,
+                                                          const uint8_t *path_buf,
+                                                          size_t path_len);
+
 StrRef tmpl_group_get_wx_gen_object_groups(const TmplGroup *self);
 
 TmplGroup tmpl_group_new();
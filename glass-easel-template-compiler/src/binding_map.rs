@@ -1,11 +1,11 @@
-use std::{collections::HashMap, fmt::Write};
+use std::{collections::BTreeMap, fmt::Write};
 
 use crate::{escape::gen_lit_str, proc_gen::JsFunctionScopeWriter, TmplError};
 
 #[derive(Debug, Clone)]
 pub(crate) struct BindingMapCollector {
     overall_disabled: bool,
-    fields: HashMap<String, BindingMapField>,
+    fields: BTreeMap<String, BindingMapField>,
 }
 
 #[derive(Debug, Clone)]
@@ -18,7 +18,7 @@ impl BindingMapCollector {
     pub(crate) fn new() -> Self {
         Self {
             overall_disabled: false,
-            fields: HashMap::new(),
+            fields: BTreeMap::new(),
         }
     }
 
@@ -54,6 +54,17 @@ impl BindingMapCollector {
         })
     }
 
+    pub(crate) fn is_overall_disabled(&self) -> bool {
+        self.overall_disabled
+    }
+
+    pub(crate) fn list_disabled_fields(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().filter_map(|(key, field)| match field {
+            BindingMapField::Mapped(_) => None,
+            BindingMapField::Disabled => Some(key.as_str()),
+        })
+    }
+
     pub(crate) fn list_fields(&self) -> impl Iterator<Item = (&str, usize)> {
         let overall_disabled = self.overall_disabled;
         self.fields.iter().filter_map(move |(key, field)| {
@@ -233,6 +233,74 @@ impl TmplGroup {
             .into()
     }
 
+    #[no_mangle]
+    pub unsafe extern "C" fn tmpl_group_get_transitive_dependencies(
+        &self,
+        path_buf: &u8,
+        path_len: usize,
+    ) -> StrRefArray {
+        let path = String::from_utf8_lossy(slice::from_raw_parts(path_buf, path_len)).to_string();
+        self.inner()
+            .transitive_dependencies(&path)
+            .map(|x| x.into_iter().map(|x| x.into()).collect::<Box<[StrRef]>>())
+            .unwrap_or_default()
+            .into()
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn tmpl_group_get_transitive_script_dependencies(
+        &self,
+        path_buf: &u8,
+        path_len: usize,
+    ) -> StrRefArray {
+        let path = String::from_utf8_lossy(slice::from_raw_parts(path_buf, path_len)).to_string();
+        self.inner()
+            .transitive_script_dependencies(&path)
+            .map(|x| x.into_iter().map(|x| x.into()).collect::<Box<[StrRef]>>())
+            .unwrap_or_default()
+            .into()
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn tmpl_group_get_dependents(
+        &self,
+        path_buf: &u8,
+        path_len: usize,
+    ) -> StrRefArray {
+        let path = String::from_utf8_lossy(slice::from_raw_parts(path_buf, path_len));
+        self.inner()
+            .dependents(&path)
+            .into_iter()
+            .map(|x| x.into())
+            .collect::<Box<[StrRef]>>()
+            .into()
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn tmpl_group_get_script_dependents(
+        &self,
+        path_buf: &u8,
+        path_len: usize,
+    ) -> StrRefArray {
+        let path = String::from_utf8_lossy(slice::from_raw_parts(path_buf, path_len));
+        self.inner()
+            .script_dependents(&path)
+            .into_iter()
+            .map(|x| x.into())
+            .collect::<Box<[StrRef]>>()
+            .into()
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn tmpl_group_get_topological_order(&self) -> StrRefArray {
+        self.inner()
+            .topological_order()
+            .into_iter()
+            .map(|x| x.into())
+            .collect::<Box<[StrRef]>>()
+            .into()
+    }
+
     #[no_mangle]
     pub unsafe extern "C" fn tmpl_group_get_inline_script_module_names(
         &self,
@@ -0,0 +1,210 @@
+//! Code completion queries.
+//!
+//! The source is parsed again with a probe at the position,
+//! so the parser tells the syntax context even if the incomplete part being typed is dropped.
+//! The visible scopes are collected from the recovered template tree.
+
+use std::ops::Range;
+
+use crate::{
+    group::TmplGroup,
+    parse::{
+        parse_with_probe,
+        tag::{Element, ElementKind, TagLocation, TemplateDefinition},
+        visit::{self, Visit},
+        visit_scope::{self, Scope, ScopeVisit},
+        ParseContext, Position, Template,
+    },
+};
+
+/// The attribute prefixes recognized by the parser.
+const ATTRIBUTE_PREFIXES: [&str; 18] = [
+    "wx:",
+    "bind:",
+    "catch:",
+    "mut-bind:",
+    "capture-bind:",
+    "capture-catch:",
+    "capture-mut-bind:",
+    "model:",
+    "change:",
+    "worklet:",
+    "data:",
+    "mark:",
+    "generic:",
+    "extra-attr:",
+    "slot:",
+    "let:",
+    "class:",
+    "style:",
+];
+
+/// A completion candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+}
+
+/// The kind of a completion candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompletionKind {
+    /// A `<wxs>` module.
+    Module,
+    /// A scope variable, i.e. a `wx:for-item` , a `wx:for-index` , a `slot:` value or a `let:` variable.
+    Variable,
+    /// A sub-template name.
+    Template,
+    /// An attribute name prefix, e.g. `bind:` .
+    AttributePrefix,
+    /// A template path in the group.
+    Path,
+}
+
+const END: Position = Position {
+    line: u32::MAX,
+    utf16_col: u32::MAX,
+};
+
+/// Get the range of an element, in which its scopes are visible.
+///
+/// An element without the end tag lasts until its parent ends.
+fn element_range(tag_location: &TagLocation, parent_end: Position) -> Range<Position> {
+    let start = tag_location.start.0.start;
+    let end = match &tag_location.end {
+        Some((_, end)) => end.end,
+        None if tag_location.close != tag_location.start.1 => tag_location.start.1.end,
+        None => parent_end,
+    };
+    start..end
+}
+
+struct ScopeCollector {
+    pos: Position,
+    /// The ends of the elements around the position.
+    ends: Vec<Position>,
+    list: Vec<CompletionItem>,
+}
+
+impl ScopeCollector {
+    fn contains(&self, range: &Range<Position>) -> bool {
+        range.start < self.pos && self.pos <= range.end
+    }
+}
+
+impl<'a> Visit<'a> for ScopeCollector {
+    fn visit_template(&mut self, x: &'a Template) {
+        visit_scope::walk_template(self, x);
+    }
+
+    fn visit_template_definition(&mut self, x: &'a TemplateDefinition) {
+        let range = element_range(&x.tag_location, END);
+        if self.contains(&range) {
+            self.ends.push(range.end);
+            visit::walk_template_definition(self, x);
+            self.ends.pop();
+        }
+    }
+
+    fn visit_element(&mut self, x: &'a Element) {
+        // the branches are checked separately
+        if let ElementKind::If { .. } = &x.kind {
+            return visit_scope::walk_element(self, x);
+        }
+        let parent_end = self.ends.last().cloned().unwrap_or(END);
+        let range = element_range(&x.tag_location, parent_end);
+        if self.contains(&range) {
+            self.ends.push(range.end);
+            visit_scope::walk_element(self, x);
+            self.ends.pop();
+        }
+    }
+}
+
+impl<'a> ScopeVisit<'a> for ScopeCollector {
+    fn declare_scope(&mut self, scope: Scope<'a>) {
+        let kind = match scope {
+            Scope::Script(_) => CompletionKind::Module,
+            _ => CompletionKind::Variable,
+        };
+        self.list.push(CompletionItem {
+            label: scope.name().to_string(),
+            kind,
+        });
+    }
+
+    // only the elements around the position are visited, so all their scopes are visible
+    fn end_scopes(&mut self, _count: usize) {}
+}
+
+/// List the scopes visible at the position, the inner ones first.
+fn visible_scopes(tree: &Template, pos: Position) -> Vec<CompletionItem> {
+    let mut c = ScopeCollector {
+        pos,
+        ends: vec![],
+        list: vec![],
+    };
+    c.visit_template(tree);
+    // inner scopes shadow the outer ones
+    let mut ret: Vec<CompletionItem> = vec![];
+    for item in c.list.into_iter().rev() {
+        if ret.iter().all(|x| x.label != item.label) {
+            ret.push(item);
+        }
+    }
+    ret
+}
+
+/// List the sub-templates which can be used in a template file.
+fn visible_sub_templates(group: &TmplGroup, tree: &Template) -> Vec<CompletionItem> {
+    let mut ret: Vec<CompletionItem> = vec![];
+    let mut add = |t: &Template| {
+        for def in t.globals.sub_templates.iter() {
+            if ret.iter().all(|x| x.label != def.name.name.as_str()) {
+                ret.push(CompletionItem {
+                    label: def.name.name.to_string(),
+                    kind: CompletionKind::Template,
+                });
+            }
+        }
+    };
+    for t in group.sub_template_sources(tree) {
+        add(t);
+    }
+    ret
+}
+
+/// List the completion candidates at the position of a template source.
+pub(crate) fn completions(
+    group: &TmplGroup,
+    path: &str,
+    src: &str,
+    pos: Position,
+) -> Vec<CompletionItem> {
+    let (tree, context) = parse_with_probe(path, src, pos);
+    match context {
+        None => vec![],
+        Some(ParseContext::Expression) => visible_scopes(&tree, pos),
+        Some(ParseContext::AttributeName) => ATTRIBUTE_PREFIXES
+            .iter()
+            .map(|x| CompletionItem {
+                label: x.to_string(),
+                kind: CompletionKind::AttributePrefix,
+            })
+            .collect(),
+        Some(ParseContext::AttributeValue { tag, attr }) => match (tag.as_str(), attr.as_str()) {
+            ("template", "is") => visible_sub_templates(group, &tree),
+            ("import", "src") | ("include", "src") => group
+                .list_template_trees()
+                .filter(|(p, _)| *p != path)
+                .map(|(p, _)| CompletionItem {
+                    label: crate::path::relative(path, p),
+                    kind: CompletionKind::Path,
+                })
+                .collect(),
+            _ => vec![],
+        },
+    }
+}
@@ -0,0 +1,411 @@
+//! Report the data paths read by the bindings of a template.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::{
+    binding_map::BindingMapCollector,
+    parse::{
+        expr::Expression,
+        tag::{
+            Attribute, ClassAttribute, CommonElementAttributes, Element, ElementKind, Node,
+            StyleAttribute, TemplateDefinition, Value,
+        },
+        visit::{self, Visit},
+        visit_scope::{self, Scope, ScopeVisit},
+        Position, Template, TemplateStructure,
+    },
+    proc_gen::expr::DataPathRoot,
+    TmplError,
+};
+
+/// The data dependencies of a template file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataDependencyReport {
+    /// The template path.
+    pub path: String,
+    /// The data paths read by the file content, sorted and joined with `.` .
+    ///
+    /// Paths read inside sub-templates are not included, since they are relative to the `data` of `<template is>` .
+    /// The templates in `includes` read the same data, so they should be checked as well.
+    pub data_paths: Vec<String>,
+    /// The absolute paths of the `<include>` ed templates.
+    pub includes: Vec<String>,
+    /// Whether the binding map is disabled for the whole file, which happens when there is an `<include>` .
+    pub binding_map_disabled: bool,
+    /// The data fields whose changes always cause full updates,
+    /// i.e. the ones read by `wx:for` `wx:if` `let:` or any value inside their subtrees.
+    pub full_update_fields: Vec<String>,
+    /// All dynamic values in the file.
+    pub bindings: Vec<DataBinding>,
+}
+
+/// A dynamic value in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataBinding {
+    /// The `<template name>` containing it, or `None` for the file content.
+    pub sub_template: Option<String>,
+    /// What the value is for, e.g. `text` `wx:if` `class` `attr:hidden` `bind:tap` .
+    pub name: String,
+    pub location: Range<Position>,
+    /// The data paths read by the value, including the ones read through `wx:for` items and `let:` variables, sorted.
+    pub data_paths: Vec<String>,
+    /// Whether changes of the data fields can be applied by binding map updaters without a full update.
+    pub binding_map: bool,
+}
+
+impl fmt::Display for DataDependencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: reads {}", self.path, self.data_paths.join(", "))?;
+        if self.binding_map_disabled {
+            writeln!(f, "  binding map disabled")?;
+        } else if !self.full_update_fields.is_empty() {
+            writeln!(
+                f,
+                "  full updates on {}",
+                self.full_update_fields.join(", ")
+            )?;
+        }
+        for b in self.bindings.iter() {
+            write!(
+                f,
+                "  {}:{} {}",
+                b.location.start.line + 1,
+                b.location.start.utf16_col + 1,
+                b.name
+            )?;
+            if let Some(t) = b.sub_template.as_ref() {
+                write!(f, " in template {:?}", t)?;
+            }
+            writeln!(
+                f,
+                " [{}] {}",
+                b.data_paths.join(", "),
+                if b.binding_map {
+                    "binding map"
+                } else {
+                    "full update"
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The data read through a scope.
+enum ScopeSource {
+    /// Script modules and slot values do not read data.
+    None,
+    /// The scope is the value of a data path, e.g. `let:a="{{ b.c }}"` .
+    Alias(Vec<String>),
+    /// The scope is computed from some data paths.
+    Derived(Vec<Vec<String>>),
+}
+
+struct Collector<'a> {
+    bmc: &'a BindingMapCollector,
+    sub_template: Option<&'a str>,
+    scopes: Vec<ScopeSource>,
+    /// The names of the values in the elements being visited, see `value_names` .
+    names: Vec<Vec<(Range<Position>, String)>>,
+    bindings: Vec<DataBinding>,
+    /// The first error found when resolving the paths.
+    error: Option<TmplError>,
+}
+
+/// Name the values of an element by what they are for.
+fn value_names(elem: &Element) -> Vec<(Range<Position>, String)> {
+    fn attrs(ret: &mut Vec<(Range<Position>, String)>, prefix: &str, list: &[Attribute]) {
+        for attr in list {
+            if let Some(value) = attr.value.as_ref() {
+                ret.push((value.location(), format!("{}{}", prefix, attr.name.name)));
+            }
+        }
+    }
+
+    fn common(ret: &mut Vec<(Range<Position>, String)>, common: &CommonElementAttributes) {
+        if let Some((_, value)) = common.id.as_ref() {
+            ret.push((value.location(), "id".to_string()));
+        }
+        if let Some((_, value)) = common.slot.as_ref() {
+            ret.push((value.location(), "slot".to_string()));
+        }
+        for ev in common.event_bindings.iter() {
+            if let Some(value) = ev.value.as_ref() {
+                ret.push((value.location(), format!("bind:{}", ev.name.name)));
+            }
+        }
+        attrs(ret, "data:", &common.data);
+        attrs(ret, "mark:", &common.marks);
+    }
+
+    let mut ret = vec![];
+    match &elem.kind {
+        ElementKind::Normal {
+            attributes,
+            class,
+            style,
+            change_attributes,
+            let_vars,
+            common: c,
+            ..
+        } => {
+            attrs(&mut ret, "let:", let_vars);
+            for attr in attributes {
+                if let Some(value) = attr.value.as_ref() {
+                    ret.push((value.location(), format!("attr:{}", attr.name.name)));
+                }
+            }
+            match class {
+                ClassAttribute::None => {}
+                ClassAttribute::String(_, value) => ret.push((value.location(), "class".into())),
+                ClassAttribute::Multiple(list) => {
+                    for (_, name, value) in list {
+                        if let Some(value) = value {
+                            ret.push((value.location(), format!("class:{}", name.name)));
+                        }
+                    }
+                }
+            }
+            match style {
+                StyleAttribute::None => {}
+                StyleAttribute::String(_, value) => ret.push((value.location(), "style".into())),
+                StyleAttribute::Multiple(list) => {
+                    for (_, name, value) in list {
+                        ret.push((value.location(), format!("style:{}", name.name)));
+                    }
+                }
+            }
+            attrs(&mut ret, "change:", change_attributes);
+            common(&mut ret, c);
+        }
+        ElementKind::Pure { let_vars, slot, .. } => {
+            attrs(&mut ret, "let:", let_vars);
+            if let Some((_, value)) = slot {
+                ret.push((value.location(), "slot".into()));
+            }
+        }
+        ElementKind::For { list, .. } => ret.push((list.1.location(), "wx:for".into())),
+        ElementKind::If { branches, .. } => {
+            for (_, cond, _) in branches {
+                ret.push((cond.location(), "wx:if".into()));
+            }
+        }
+        ElementKind::TemplateRef { target, data } => {
+            ret.push((target.1.location(), "is".into()));
+            ret.push((data.1.location(), "data".into()));
+        }
+        ElementKind::Include { .. } => {}
+        ElementKind::Slot {
+            name,
+            values,
+            common: c,
+        } => {
+            ret.push((name.1.location(), "name".into()));
+            attrs(&mut ret, "slot-value:", values);
+            common(&mut ret, c);
+        }
+    }
+    ret
+}
+
+impl<'a> Collector<'a> {
+    /// Resolve the paths read by the expression, and whether the expression is exactly the only path.
+    fn resolve_with_alias(&mut self, expression: &Expression) -> (Vec<Vec<String>>, bool) {
+        let mut ret: Vec<Vec<String>> = vec![];
+        let mut is_alias = matches!(
+            expression,
+            Expression::DataField { .. }
+                | Expression::ScopeRef { .. }
+                | Expression::StaticMember { .. }
+                | Expression::OptionalStaticMember { .. }
+        );
+        // the same paths are checked against the update path tree in the generated code
+        let paths = match expression.data_paths(self.scopes.len()) {
+            Ok(x) => x,
+            Err(err) => {
+                // a missing path would let callers drop data that is actually read
+                self.error.get_or_insert(err);
+                vec![]
+            }
+        };
+        for p in paths {
+            let fields = p.fields.iter().map(|x| x.to_string());
+            let list = match p.root {
+                DataPathRoot::Data(name) => {
+                    let mut path = vec![name.to_string()];
+                    path.extend(fields);
+                    vec![path]
+                }
+                DataPathRoot::Scope(index) => match &self.scopes[index] {
+                    ScopeSource::None => vec![],
+                    ScopeSource::Alias(path) => {
+                        let mut path = path.clone();
+                        path.extend(fields);
+                        vec![path]
+                    }
+                    ScopeSource::Derived(list) => {
+                        is_alias = false;
+                        list.clone()
+                    }
+                },
+            };
+            for path in list {
+                if !ret.contains(&path) {
+                    ret.push(path);
+                }
+            }
+        }
+        (ret, is_alias)
+    }
+
+    fn resolve(&mut self, expression: &Expression) -> Vec<Vec<String>> {
+        self.resolve_with_alias(expression).0
+    }
+
+    fn scope_source(&mut self, value: Option<&Value>) -> ScopeSource {
+        let Some(Value::Dynamic { expression, .. }) = value else {
+            return ScopeSource::None;
+        };
+        let (mut list, is_alias) = self.resolve_with_alias(expression);
+        if is_alias && list.len() == 1 {
+            ScopeSource::Alias(list.pop().unwrap())
+        } else {
+            ScopeSource::Derived(list)
+        }
+    }
+
+    fn value(&mut self, name: impl fmt::Display, value: &Value) {
+        let Value::Dynamic {
+            expression,
+            binding_map_keys,
+            ..
+        } = value
+        else {
+            return;
+        };
+        let mut data_paths: Vec<String> = self
+            .resolve(expression)
+            .into_iter()
+            .map(|x| x.join("."))
+            .collect();
+        data_paths.sort();
+        self.bindings.push(DataBinding {
+            sub_template: self.sub_template.map(|x| x.to_string()),
+            name: name.to_string(),
+            location: value.location(),
+            data_paths,
+            binding_map: binding_map_keys
+                .as_ref()
+                .map(|x| !x.is_empty(self.bmc))
+                .unwrap_or(false),
+        });
+    }
+
+    fn for_source(&mut self, list: &Value) -> ScopeSource {
+        // an item is read through a dynamic index, so only the list path is known
+        match self.scope_source(Some(list)) {
+            ScopeSource::Alias(path) => ScopeSource::Derived(vec![path]),
+            x => x,
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Collector<'a> {
+    fn visit_template(&mut self, x: &'a Template) {
+        visit_scope::walk_template(self, x);
+    }
+
+    fn visit_template_definition(&mut self, x: &'a TemplateDefinition) {
+        self.sub_template = Some(&x.name.name);
+        visit::walk_template_definition(self, x);
+        self.sub_template = None;
+    }
+
+    fn visit_node(&mut self, x: &'a Node) {
+        match x {
+            Node::Text(value) => self.value("text", value),
+            _ => visit::walk_node(self, x),
+        }
+    }
+
+    fn visit_element(&mut self, x: &'a Element) {
+        self.names.push(value_names(x));
+        visit_scope::walk_element(self, x);
+        self.names.pop();
+    }
+
+    fn visit_value(&mut self, x: &'a Value) {
+        let location = x.location();
+        let name = self
+            .names
+            .last()
+            .and_then(|list| list.iter().find(|(loc, _)| *loc == location));
+        if let Some((_, name)) = name {
+            self.value(name.clone(), x);
+        }
+    }
+}
+
+impl<'a> ScopeVisit<'a> for Collector<'a> {
+    fn declare_scope(&mut self, scope: Scope<'a>) {
+        let source = match scope {
+            Scope::Script(_) | Scope::SlotValue(_) => ScopeSource::None,
+            Scope::LetVar(attr) => self.scope_source(attr.value.as_ref()),
+            Scope::ForItem { list, .. } => self.for_source(&list.1),
+            Scope::ForIndex { list, .. } => match self.for_source(&list.1) {
+                ScopeSource::Derived(list) => ScopeSource::Derived(list),
+                _ => ScopeSource::None,
+            },
+        };
+        self.scopes.push(source);
+    }
+
+    fn end_scopes(&mut self, count: usize) {
+        self.scopes.truncate(self.scopes.len() - count);
+    }
+}
+
+/// Collect the data dependencies of a template.
+///
+/// Fails if the data paths of any expression cannot be resolved.
+pub(crate) fn analyze(tmpl: &Template) -> Result<DataDependencyReport, TmplError> {
+    let bmc = &tmpl.globals.binding_map_collector;
+    let mut c = Collector {
+        bmc,
+        sub_template: None,
+        scopes: vec![],
+        names: vec![],
+        bindings: vec![],
+        error: None,
+    };
+    c.visit_template(tmpl);
+    if let Some(err) = c.error {
+        return Err(err);
+    }
+    // the sub-templates are visited before the file content
+    c.bindings.sort_by_key(|x| x.sub_template.is_some());
+    let mut data_paths: Vec<String> = c
+        .bindings
+        .iter()
+        .filter(|x| x.sub_template.is_none())
+        .flat_map(|x| x.data_paths.iter().cloned())
+        .collect();
+    data_paths.sort();
+    data_paths.dedup();
+    Ok(DataDependencyReport {
+        path: tmpl.path.clone(),
+        data_paths,
+        includes: tmpl
+            .globals
+            .includes
+            .iter()
+            .map(|x| crate::path::resolve(&tmpl.path, &x.src.name))
+            .collect(),
+        binding_map_disabled: bmc.is_overall_disabled(),
+        full_update_fields: bmc.list_disabled_fields().map(|x| x.to_string()).collect(),
+        bindings: c.bindings,
+    })
+}
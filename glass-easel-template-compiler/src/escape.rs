@@ -52,6 +52,8 @@ pub(crate) fn gen_lit_str_with_quotes(s: &str, use_single_quote: bool) -> String
                 w.push_str(&format!("\\x{:02X}", ch as u8));
             }
             x if x == quote_ch => w.push_str(&format!("\\{}", x)),
+            // the source map markers
+            '\u{FDD0}' | '\u{FDD1}' => w.push_str(&format!("\\u{:04X}", ch as u32)),
             x => w.push(x),
         }
     }
@@ -71,6 +73,27 @@ pub(crate) fn gen_lit_str_with_quotes(s: &str, use_single_quote: bool) -> String
     ret
 }
 
+pub(crate) fn gen_lit_template_str_part(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => ret.push_str("\\\\"),
+            '`' => ret.push_str("\\`"),
+            '$' if chars.peek() == Some(&'{') => ret.push_str("\\$"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            x if x as u32 <= 31u32 => {
+                ret.push_str(&format!("\\x{:02X}", ch as u8));
+            }
+            '\u{FDD0}' | '\u{FDD1}' => ret.push_str(&format!("\\u{:04X}", ch as u32)),
+            x => ret.push(x),
+        }
+    }
+    ret
+}
+
 pub(crate) fn dash_to_camel(s: &str) -> CompactString {
     let mut camel_name = CompactString::new("");
     let mut next_upper = false;
@@ -114,6 +137,10 @@ mod tests {
         assert_eq!(gen_lit_str_with_quotes("'", false), r#""'""#);
         assert_eq!(gen_lit_str_with_quotes("\"", false), r#""\"""#);
         assert_eq!(gen_lit_str_with_quotes("\\n\n", false), r#""\\n\n""#);
+        assert_eq!(
+            gen_lit_str_with_quotes("\u{FDD0}\u{FDD1}", false),
+            r#""\uFDD0\uFDD1""#
+        );
     }
 
     #[test]
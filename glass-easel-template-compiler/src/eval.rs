@@ -0,0 +1,974 @@
+//! Evaluate template expressions without a JavaScript engine
+//!
+//! The evaluation follows the JavaScript semantics of the generated code.
+//! Data is converted from JSON with `JsValue::from` , and results can be converted back with `JsValue::to_json` .
+//!
+//! There are some differences from a real JavaScript engine:
+//!
+//! - reading a member of `null` or `undefined` gives `undefined` instead of throwing, like the generated code does;
+//! - functions are not representable, so calls are handled by the call handler of the `Evaluator` ;
+//! - `instanceof` is not supported and results in an `EvalError` .
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Range;
+
+use indexmap::IndexMap;
+
+use crate::parse::{
+    expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
+    Position,
+};
+
+/// A JavaScript value.
+///
+/// Functions are not representable, so function calls are delegated to the host.
+/// Objects and arrays are compared structurally since there is no object identity.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsValue {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsValue>),
+    Object(JsObject),
+}
+
+/// A JavaScript object.
+///
+/// The keys are in the JavaScript property order,
+/// i.e. the integer-like keys in ascending order first, then the other keys in insertion order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsObject(IndexMap<String, JsValue>);
+
+fn array_index(key: &str) -> Option<u32> {
+    let n: u32 = key.parse().ok()?;
+    (n != u32::MAX && n.to_string() == key).then_some(n)
+}
+
+impl JsObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsValue> {
+        self.0.get(key)
+    }
+
+    /// Set a property, which keeps its position if it already exists.
+    pub fn insert(&mut self, key: String, value: JsValue) {
+        if let Some(v) = self.0.get_mut(&key) {
+            *v = value;
+            return;
+        }
+        match array_index(&key) {
+            Some(n) => {
+                let index = self
+                    .0
+                    .as_slice()
+                    .partition_point(|k, _| array_index(k).map(|x| x < n).unwrap_or(false));
+                self.0.shift_insert(index, key, value);
+            }
+            None => {
+                self.0.insert(key, value);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<JsValue> {
+        self.0.shift_remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &JsValue)> {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for JsObject {
+    type Item = (String, JsValue);
+    type IntoIter = indexmap::map::IntoIter<String, JsValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl Extend<(String, JsValue)> for JsObject {
+    fn extend<T: IntoIterator<Item = (String, JsValue)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl FromIterator<(String, JsValue)> for JsObject {
+    fn from_iter<T: IntoIterator<Item = (String, JsValue)>>(iter: T) -> Self {
+        let mut ret = Self::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl From<&serde_json::Value> for JsValue {
+    fn from(v: &serde_json::Value) -> Self {
+        match v {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(x) => Self::Bool(*x),
+            serde_json::Value::Number(x) => Self::Number(x.as_f64().unwrap_or(f64::NAN)),
+            serde_json::Value::String(x) => Self::String(x.clone()),
+            serde_json::Value::Array(x) => Self::Array(x.iter().map(Self::from).collect()),
+            serde_json::Value::Object(x) => {
+                Self::Object(x.iter().map(|(k, v)| (k.clone(), Self::from(v))).collect())
+            }
+        }
+    }
+}
+
+impl JsValue {
+    /// Convert to JSON like `JSON.stringify` does.
+    ///
+    /// `undefined` and non-finite numbers become `null` , and `undefined` fields are removed.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::Undefined | Self::Null => serde_json::Value::Null,
+            Self::Bool(x) => serde_json::Value::Bool(*x),
+            Self::Number(x) => {
+                if x.fract() == 0. && x.abs() < 9007199254740992. {
+                    serde_json::Value::from(*x as i64)
+                } else {
+                    serde_json::Number::from_f64(*x)
+                        .map(serde_json::Value::Number)
+                        .unwrap_or(serde_json::Value::Null)
+                }
+            }
+            Self::String(x) => serde_json::Value::String(x.clone()),
+            Self::Array(x) => serde_json::Value::Array(x.iter().map(|x| x.to_json()).collect()),
+            Self::Object(x) => serde_json::Value::Object(
+                x.iter()
+                    .filter(|(_, v)| **v != Self::Undefined)
+                    .map(|(k, v)| (k.clone(), v.to_json()))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn type_of(&self) -> &'static str {
+        match self {
+            Self::Undefined => "undefined",
+            Self::Null | Self::Array(_) | Self::Object(_) => "object",
+            Self::Bool(_) => "boolean",
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+        }
+    }
+
+    pub fn to_boolean(&self) -> bool {
+        match self {
+            Self::Undefined | Self::Null => false,
+            Self::Bool(x) => *x,
+            Self::Number(x) => !(x.is_nan() || *x == 0.),
+            Self::String(x) => !x.is_empty(),
+            Self::Array(_) | Self::Object(_) => true,
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        match self {
+            Self::Undefined => f64::NAN,
+            Self::Null => 0.,
+            Self::Bool(x) => {
+                if *x {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            Self::Number(x) => *x,
+            Self::String(x) => string_to_number(x),
+            Self::Array(_) | Self::Object(_) => string_to_number(&self.to_js_string()),
+        }
+    }
+
+    /// Convert to string like `String(value)` .
+    pub fn to_js_string(&self) -> Cow<'_, str> {
+        match self {
+            Self::Undefined => Cow::Borrowed("undefined"),
+            Self::Null => Cow::Borrowed("null"),
+            Self::Bool(x) => Cow::Borrowed(if *x { "true" } else { "false" }),
+            Self::Number(x) => Cow::Owned(number_to_string(*x)),
+            Self::String(x) => Cow::Borrowed(x),
+            Self::Array(x) => {
+                let items: Vec<_> = x
+                    .iter()
+                    .map(|x| match x {
+                        Self::Undefined | Self::Null => Cow::Borrowed(""),
+                        x => x.to_js_string(),
+                    })
+                    .collect();
+                Cow::Owned(items.join(","))
+            }
+            Self::Object(_) => Cow::Borrowed("[object Object]"),
+        }
+    }
+
+    /// Convert to string, but `undefined` and `null` become an empty string.
+    pub fn to_string_without_undefined(&self) -> Cow<'_, str> {
+        match self {
+            Self::Undefined | Self::Null => Cow::Borrowed(""),
+            x => x.to_js_string(),
+        }
+    }
+
+    fn to_primitive(&self) -> Cow<'_, Self> {
+        match self {
+            Self::Array(_) | Self::Object(_) => {
+                Cow::Owned(Self::String(self.to_js_string().into_owned()))
+            }
+            x => Cow::Borrowed(x),
+        }
+    }
+
+    fn to_property_key(&self) -> Cow<'_, str> {
+        self.to_js_string()
+    }
+
+    pub fn strict_equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (a, b) => a == b,
+        }
+    }
+
+    pub fn loose_equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Undefined | Self::Null, Self::Undefined | Self::Null) => true,
+            (Self::Undefined | Self::Null, _) | (_, Self::Undefined | Self::Null) => false,
+            (Self::Number(a), Self::String(_)) => *a == other.to_number(),
+            (Self::String(_), Self::Number(b)) => self.to_number() == *b,
+            (Self::Bool(_), _) => Self::Number(self.to_number()).loose_equals(other),
+            (_, Self::Bool(_)) => self.loose_equals(&Self::Number(other.to_number())),
+            (Self::Array(_) | Self::Object(_), Self::Number(_) | Self::String(_)) => {
+                self.to_primitive().loose_equals(other)
+            }
+            (Self::Number(_) | Self::String(_), Self::Array(_) | Self::Object(_)) => {
+                self.loose_equals(&other.to_primitive())
+            }
+            (a, b) => a.strict_equals(b),
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        let a = self.to_primitive();
+        let b = other.to_primitive();
+        match (&*a, &*b) {
+            (Self::String(a), Self::String(b)) => Some(a.encode_utf16().cmp(b.encode_utf16())),
+            (a, b) => a.to_number().partial_cmp(&b.to_number()),
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let a = self.to_primitive();
+        let b = other.to_primitive();
+        match (&*a, &*b) {
+            (Self::String(_), _) | (_, Self::String(_)) => {
+                Self::String(format!("{}{}", a.to_js_string(), b.to_js_string()))
+            }
+            (a, b) => Self::Number(a.to_number() + b.to_number()),
+        }
+    }
+
+    /// Get a member value, or `undefined` if it does not exist.
+    ///
+    /// Like the generated code, reading members of `null` or `undefined` gives `undefined` .
+    fn member<'a>(this: Cow<'a, Self>, key: &str) -> Cow<'a, Self> {
+        let index = || {
+            if key == "0" || (!key.starts_with('0') && key.bytes().all(|x| x.is_ascii_digit())) {
+                key.parse::<usize>().ok()
+            } else {
+                None
+            }
+        };
+        match this {
+            Cow::Borrowed(Self::Array(arr)) => match index().and_then(|i| arr.get(i)) {
+                Some(x) => Cow::Borrowed(x),
+                None if key == "length" => Cow::Owned(Self::Number(arr.len() as f64)),
+                None => Cow::Owned(Self::Undefined),
+            },
+            Cow::Owned(Self::Array(mut arr)) => match index() {
+                Some(i) if i < arr.len() => Cow::Owned(arr.swap_remove(i)),
+                _ if key == "length" => Cow::Owned(Self::Number(arr.len() as f64)),
+                _ => Cow::Owned(Self::Undefined),
+            },
+            Cow::Borrowed(Self::Object(map)) => match map.get(key) {
+                Some(x) => Cow::Borrowed(x),
+                None => Cow::Owned(Self::Undefined),
+            },
+            Cow::Owned(Self::Object(mut map)) => {
+                Cow::Owned(map.remove(key).unwrap_or(Self::Undefined))
+            }
+            this => {
+                let ret = match &*this {
+                    Self::String(s) => {
+                        if key == "length" {
+                            Self::Number(s.encode_utf16().count() as f64)
+                        } else if let Some(c) = index().and_then(|i| s.encode_utf16().nth(i)) {
+                            Self::String(String::from_utf16_lossy(&[c]))
+                        } else {
+                            Self::Undefined
+                        }
+                    }
+                    _ => Self::Undefined,
+                };
+                Cow::Owned(ret)
+            }
+        }
+    }
+}
+
+fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0. { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if n == 0. {
+        return "0".to_string();
+    }
+    let abs = n.abs();
+    if (1e-6..1e21).contains(&abs) {
+        return format!("{}", n);
+    }
+    let s = format!("{:e}", n);
+    match s.split_once('e') {
+        Some((m, e)) if !e.starts_with('-') => format!("{}e+{}", m, e),
+        _ => s,
+    }
+}
+
+fn string_to_number(s: &str) -> f64 {
+    let s = s.trim_matches(|c: char| c.is_whitespace() || c == '\u{FEFF}');
+    if s.is_empty() {
+        return 0.;
+    }
+    for (prefix, radix) in [
+        ("0x", 16),
+        ("0X", 16),
+        ("0o", 8),
+        ("0O", 8),
+        ("0b", 2),
+        ("0B", 2),
+    ] {
+        if let Some(digits) = s.strip_prefix(prefix) {
+            return match u64::from_str_radix(digits, radix) {
+                Ok(x) => x as f64,
+                Err(_) => f64::NAN,
+            };
+        }
+    }
+    if s.strip_prefix(['+', '-']).unwrap_or(s) == "Infinity" {
+        return if s.starts_with('-') {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        };
+    }
+    if !s
+        .bytes()
+        .all(|x| x.is_ascii_digit() || b".eE+-".contains(&x))
+    {
+        return f64::NAN;
+    }
+    s.parse().unwrap_or(f64::NAN)
+}
+
+fn to_int32(n: f64) -> i32 {
+    to_uint32(n) as i32
+}
+
+fn to_uint32(n: f64) -> u32 {
+    if !n.is_finite() {
+        return 0;
+    }
+    n.trunc().rem_euclid(4294967296.) as u32
+}
+
+/// An error occurred during evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    pub message: String,
+    /// The location of the expression that caused the error.
+    pub location: Range<Position>,
+}
+
+impl EvalError {
+    pub fn new(message: impl Into<String>, location: Range<Position>) -> Self {
+        Self {
+            message: message.into(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (at {}:{})",
+            self.message,
+            self.location.start.line + 1,
+            self.location.start.utf16_col + 1
+        )
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// The function call handler.
+///
+/// It receives the callee expression and the evaluated arguments.
+/// The callee is not evaluated since functions are not representable.
+pub type CallHandler<'h> = dyn FnMut(&Expression, Vec<JsValue>) -> Result<JsValue, EvalError> + 'h;
+
+/// The evaluation environment, i.e. the data, the scopes and the call handler.
+pub struct Evaluator<'a, 'h> {
+    data: &'a JsValue,
+    scopes: &'a [JsValue],
+    call: Option<Box<CallHandler<'h>>>,
+}
+
+impl<'a, 'h> Evaluator<'a, 'h> {
+    /// Create an evaluator.
+    ///
+    /// `scopes` is the scope stack, and its items should match the `Expression::ScopeRef` indices,
+    /// i.e. the script modules first, then the scopes introduced by ancestors from outer to inner.
+    pub fn new(data: &'a JsValue, scopes: &'a [JsValue]) -> Self {
+        Self {
+            data,
+            scopes,
+            call: None,
+        }
+    }
+
+    /// Set the function call handler.
+    ///
+    /// Without a handler, all calls evaluate to `undefined` , like calling a non-function in the generated code.
+    pub fn set_call_handler(
+        &mut self,
+        f: impl FnMut(&Expression, Vec<JsValue>) -> Result<JsValue, EvalError> + 'h,
+    ) {
+        self.call = Some(Box::new(f));
+    }
+
+    /// Evaluate an expression.
+    ///
+    /// The result borrows from the data or the scopes when possible.
+    pub fn eval(&mut self, expr: &Expression) -> Result<Cow<'a, JsValue>, EvalError> {
+        let owned = |x| Ok(Cow::Owned(x));
+        let num = |x| Ok(Cow::Owned(JsValue::Number(x)));
+        let bool = |x| Ok(Cow::Owned(JsValue::Bool(x)));
+        match expr {
+            Expression::ScopeRef { index, location } => match self.scopes.get(*index) {
+                Some(x) => Ok(Cow::Borrowed(x)),
+                None => Err(EvalError::new("scope not found", location.clone())),
+            },
+            Expression::DataField { name, .. } => {
+                Ok(JsValue::member(Cow::Borrowed(self.data), name))
+            }
+            Expression::ToStringWithoutUndefined { value, .. } => {
+                let v = self.eval(value)?;
+                owned(JsValue::String(
+                    v.to_string_without_undefined().into_owned(),
+                ))
+            }
+
+            Expression::LitUndefined { .. } => owned(JsValue::Undefined),
+            Expression::LitNull { .. } => owned(JsValue::Null),
+            Expression::LitStr { value, .. } => owned(JsValue::String(value.to_string())),
+            Expression::LitInt { value, .. } => num(*value as f64),
+            Expression::LitFloat { value, .. } => num(*value),
+            Expression::LitBool { value, .. } => bool(*value),
+            Expression::LitObj { fields, .. } => {
+                let mut map = JsObject::new();
+                for field in fields {
+                    match field {
+                        ObjectFieldKind::Named { name, value, .. } => {
+                            let v = self.eval(value)?.into_owned();
+                            map.insert(name.to_string(), v);
+                        }
+                        ObjectFieldKind::Spread { value, .. } => {
+                            match self.eval(value)?.into_owned() {
+                                JsValue::Object(x) => map.extend(x),
+                                JsValue::Array(x) => map.extend(
+                                    x.into_iter().enumerate().map(|(i, v)| (i.to_string(), v)),
+                                ),
+                                JsValue::String(x) => {
+                                    map.extend(x.encode_utf16().enumerate().map(|(i, c)| {
+                                        (
+                                            i.to_string(),
+                                            JsValue::String(String::from_utf16_lossy(&[c])),
+                                        )
+                                    }))
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                owned(JsValue::Object(map))
+            }
+            Expression::LitArr { fields, .. } => {
+                let mut arr = vec![];
+                for field in fields {
+                    match field {
+                        ArrayFieldKind::Normal { value } => {
+                            arr.push(self.eval(value)?.into_owned());
+                        }
+                        ArrayFieldKind::Spread { value, location } => {
+                            match self.eval(value)?.into_owned() {
+                                JsValue::Array(x) => arr.extend(x),
+                                JsValue::String(x) => {
+                                    arr.extend(x.chars().map(|c| JsValue::String(c.to_string())))
+                                }
+                                x => {
+                                    return Err(EvalError::new(
+                                        format!("{} is not iterable", x.type_of()),
+                                        location.clone(),
+                                    ))
+                                }
+                            }
+                        }
+                        ArrayFieldKind::EmptySlot => {
+                            arr.push(JsValue::Undefined);
+                        }
+                    }
+                }
+                owned(JsValue::Array(arr))
+            }
+            Expression::LitTemplateStr { parts, .. } => {
+                let mut s = String::new();
+                for part in parts {
+                    match part {
+                        TemplateStrPart::Static { value, .. } => s.push_str(value),
+                        TemplateStrPart::Dynamic { value, .. } => {
+                            s.push_str(&self.eval(value)?.to_js_string())
+                        }
+                    }
+                }
+                owned(JsValue::String(s))
+            }
+
+            Expression::StaticMember {
+                obj, field_name, ..
+            }
+            | Expression::OptionalStaticMember {
+                obj, field_name, ..
+            } => {
+                let obj = self.eval(obj)?;
+                Ok(JsValue::member(obj, field_name))
+            }
+            Expression::DynamicMember {
+                obj, field_name, ..
+            }
+            | Expression::OptionalDynamicMember {
+                obj, field_name, ..
+            } => {
+                let obj = self.eval(obj)?;
+                let key = self.eval(field_name)?;
+                Ok(JsValue::member(obj, &key.to_property_key()))
+            }
+            Expression::FuncCall { func, args, .. }
+            | Expression::OptionalFuncCall { func, args, .. } => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg)?.into_owned());
+                }
+                match self.call.as_mut() {
+                    Some(call) => owned(call(func, values)?),
+                    None => owned(JsValue::Undefined),
+                }
+            }
+
+            Expression::Reverse { value, .. } => bool(!self.eval(value)?.to_boolean()),
+            Expression::BitReverse { value, .. } => {
+                num(!to_int32(self.eval(value)?.to_number()) as f64)
+            }
+            Expression::Positive { value, .. } => num(self.eval(value)?.to_number()),
+            Expression::Negative { value, .. } => num(-self.eval(value)?.to_number()),
+            Expression::TypeOf { value, .. } => {
+                owned(JsValue::String(self.eval(value)?.type_of().to_string()))
+            }
+            Expression::Void { value, .. } => {
+                self.eval(value)?;
+                owned(JsValue::Undefined)
+            }
+
+            Expression::Multiply { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(a * b)
+            }
+            Expression::Divide { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(a / b)
+            }
+            Expression::Remainer { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(a % b)
+            }
+            Expression::Plus { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                owned(a.add(&b))
+            }
+            Expression::Minus { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(a - b)
+            }
+
+            Expression::LeftShift { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(to_int32(a).wrapping_shl(to_uint32(b) & 31) as f64)
+            }
+            Expression::RightShift { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(to_int32(a).wrapping_shr(to_uint32(b) & 31) as f64)
+            }
+            Expression::UnsignedRightShift { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num(to_uint32(a).wrapping_shr(to_uint32(b) & 31) as f64)
+            }
+
+            Expression::Lt { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(a.compare(&b) == Some(Ordering::Less))
+            }
+            Expression::Gt { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(a.compare(&b) == Some(Ordering::Greater))
+            }
+            Expression::Lte { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(matches!(
+                    a.compare(&b),
+                    Some(Ordering::Less | Ordering::Equal)
+                ))
+            }
+            Expression::Gte { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(matches!(
+                    a.compare(&b),
+                    Some(Ordering::Greater | Ordering::Equal)
+                ))
+            }
+            Expression::InstanceOf { location, .. } => Err(EvalError::new(
+                "`instanceof` requires a constructor, which is not supported",
+                location.clone(),
+            )),
+
+            Expression::Eq { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(a.loose_equals(&b))
+            }
+            Expression::Ne { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(!a.loose_equals(&b))
+            }
+            Expression::EqFull { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(a.strict_equals(&b))
+            }
+            Expression::NeFull { left, right, .. } => {
+                let a = self.eval(left)?;
+                let b = self.eval(right)?;
+                bool(!a.strict_equals(&b))
+            }
+
+            Expression::BitAnd { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num((to_int32(a) & to_int32(b)) as f64)
+            }
+            Expression::BitXor { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num((to_int32(a) ^ to_int32(b)) as f64)
+            }
+            Expression::BitOr { left, right, .. } => {
+                let (a, b) = self.eval_numbers(left, right)?;
+                num((to_int32(a) | to_int32(b)) as f64)
+            }
+            Expression::LogicAnd { left, right, .. } => {
+                let a = self.eval(left)?;
+                if a.to_boolean() {
+                    self.eval(right)
+                } else {
+                    Ok(a)
+                }
+            }
+            Expression::LogicOr { left, right, .. } => {
+                let a = self.eval(left)?;
+                if a.to_boolean() {
+                    Ok(a)
+                } else {
+                    self.eval(right)
+                }
+            }
+            Expression::NullishCoalescing { left, right, .. } => {
+                let a = self.eval(left)?;
+                match &*a {
+                    JsValue::Undefined | JsValue::Null => self.eval(right),
+                    _ => Ok(a),
+                }
+            }
+
+            Expression::Cond {
+                cond,
+                true_br,
+                false_br,
+                ..
+            } => {
+                if self.eval(cond)?.to_boolean() {
+                    self.eval(true_br)
+                } else {
+                    self.eval(false_br)
+                }
+            }
+        }
+    }
+
+    fn eval_numbers(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+    ) -> Result<(f64, f64), EvalError> {
+        let a = self.eval(left)?.to_number();
+        let b = self.eval(right)?.to_number();
+        Ok((a, b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::{tag::Node, tag::Value, TemplateStructure};
+
+    fn parse_expr(src: &str) -> Expression {
+        let (template, _) = crate::parse::parse("TEST", &format!("{{{{ {} }}}}", src));
+        match template.content.into_iter().next() {
+            Some(Node::Text(Value::Dynamic { expression, .. })) => *expression,
+            _ => panic!("not an expression: {}", src),
+        }
+    }
+
+    fn eval_str(src: &str) -> String {
+        let data = JsValue::from(&serde_json::json!({
+            "a": 1,
+            "s": "2",
+            "n": null,
+            "arr": [1, 2, 3],
+            "obj": { "x": 1, "y": [true] },
+            "str": "héllo",
+            "big": 1e21,
+            "small": 1e-7,
+        }));
+        let v = Evaluator::new(&data, &[]).eval(&parse_expr(src)).unwrap();
+        format!("{}:{}", v.type_of(), v.to_js_string())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval_str("1 + 2"), "number:3");
+        assert_eq!(eval_str("'a' + 'b'"), "string:ab");
+        assert_eq!(eval_str("1 + '2'"), "string:12");
+        assert_eq!(eval_str("'' + true"), "string:true");
+        assert_eq!(eval_str("s * 3"), "number:6");
+        assert_eq!(eval_str("a - 's'"), "number:NaN");
+        assert_eq!(eval_str("7 % -3"), "number:1");
+        assert_eq!(eval_str("-7 % 3"), "number:-1");
+        assert_eq!(eval_str("1 / 0"), "number:Infinity");
+        assert_eq!(eval_str("-1 / 0"), "number:-Infinity");
+        assert_eq!(eval_str("0 / 0"), "number:NaN");
+        assert_eq!(eval_str("-0"), "number:0");
+        assert_eq!(eval_str("0.1 + 0.2"), "number:0.30000000000000004");
+        assert_eq!(eval_str("big"), "number:1e+21");
+        assert_eq!(eval_str("small"), "number:1e-7");
+        assert_eq!(eval_str("1e21 + 1"), "number:1e+21");
+        assert_eq!(eval_str("123456789012 + 1"), "number:123456789013");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(eval_str("!s"), "boolean:false");
+        assert_eq!(eval_str("!!''"), "boolean:false");
+        assert_eq!(eval_str("+s"), "number:2");
+        assert_eq!(eval_str("+'  12  '"), "number:12");
+        assert_eq!(eval_str("+'0x1f'"), "number:31");
+        assert_eq!(eval_str("+'1e3'"), "number:1000");
+        assert_eq!(eval_str("+'abc'"), "number:NaN");
+        assert_eq!(eval_str("+''"), "number:0");
+        assert_eq!(eval_str("+true"), "number:1");
+        assert_eq!(eval_str("+[]"), "number:0");
+        assert_eq!(eval_str("+[5]"), "number:5");
+        assert_eq!(eval_str("+'Infinity'"), "number:Infinity");
+        assert_eq!(eval_str("-'-Infinity'"), "number:Infinity");
+        assert_eq!(eval_str("+'infinity'"), "number:NaN");
+        assert_eq!(
+            eval_str("`${a}-${n}-${missing}-${arr}-${obj}`"),
+            "string:1-null-undefined-1,2,3-[object Object]",
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(eval_str("'10' < '9'"), "boolean:true");
+        assert_eq!(eval_str("10 < 9"), "boolean:false");
+        assert_eq!(eval_str("'10' < 9"), "boolean:false");
+        assert_eq!(eval_str("null >= 0"), "boolean:true");
+        assert_eq!(eval_str("undefined >= 0"), "boolean:false");
+        assert_eq!(eval_str("(0 / 0) < 1"), "boolean:false");
+        assert_eq!(eval_str("null == undefined"), "boolean:true");
+        assert_eq!(eval_str("null === undefined"), "boolean:false");
+        assert_eq!(eval_str("null == 0"), "boolean:false");
+        assert_eq!(eval_str("'1' == 1"), "boolean:true");
+        assert_eq!(eval_str("'1' != 1"), "boolean:false");
+        assert_eq!(eval_str("true == 1"), "boolean:true");
+        assert_eq!(eval_str("true === 1"), "boolean:false");
+        assert_eq!(eval_str("true !== 1"), "boolean:true");
+        assert_eq!(eval_str("'' == 0"), "boolean:true");
+        assert_eq!(eval_str("[1] == 1"), "boolean:true");
+        assert_eq!(eval_str("arr == '1,2,3'"), "boolean:true");
+        assert_eq!(eval_str("(0 / 0) == (0 / 0)"), "boolean:false");
+    }
+
+    #[test]
+    fn unary_and_logic() {
+        assert_eq!(eval_str("typeof a"), "string:number");
+        assert_eq!(eval_str("typeof s"), "string:string");
+        assert_eq!(eval_str("typeof n"), "string:object");
+        assert_eq!(eval_str("typeof arr"), "string:object");
+        assert_eq!(eval_str("typeof undefined"), "string:undefined");
+        assert_eq!(eval_str("typeof missing"), "string:undefined");
+        assert_eq!(eval_str("typeof true"), "string:boolean");
+        assert_eq!(eval_str("void a"), "undefined:undefined");
+        assert_eq!(eval_str("n ?? 'd'"), "string:d");
+        assert_eq!(eval_str("0 ?? 'd'"), "number:0");
+        assert_eq!(eval_str("missing ?? 'd'"), "string:d");
+        assert_eq!(eval_str("0 || 'd'"), "string:d");
+        assert_eq!(eval_str("1 && 'd'"), "string:d");
+        assert_eq!(eval_str("'' && 'd'"), "string:");
+        assert_eq!(eval_str("a > 0 ? 'pos' : 'neg'"), "string:pos");
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(eval_str("~a"), "number:-2");
+        assert_eq!(eval_str("5 & 3"), "number:1");
+        assert_eq!(eval_str("5 | 3"), "number:7");
+        assert_eq!(eval_str("5 ^ 3"), "number:6");
+        assert_eq!(eval_str("1 << 31"), "number:-2147483648");
+        assert_eq!(eval_str("-1 >> 28"), "number:-1");
+        assert_eq!(eval_str("-1 >>> 28"), "number:15");
+        assert_eq!(eval_str("2.9 | 0"), "number:2");
+        assert_eq!(eval_str("-2.9 | 0"), "number:-2");
+        assert_eq!(eval_str("4294967297 | 0"), "number:1");
+    }
+
+    #[test]
+    fn members() {
+        assert_eq!(eval_str("arr.length"), "number:3");
+        assert_eq!(eval_str("str.length"), "number:5");
+        assert_eq!(eval_str("str[1]"), "string:é");
+        assert_eq!(eval_str("arr[1]"), "number:2");
+        assert_eq!(eval_str("arr['2']"), "number:3");
+        assert_eq!(eval_str("arr[5]"), "undefined:undefined");
+        assert_eq!(eval_str("obj.y[0]"), "boolean:true");
+        assert_eq!(eval_str("obj?.y?.[0]"), "boolean:true");
+        assert_eq!(eval_str("n.x"), "undefined:undefined");
+        assert_eq!(eval_str("missing.x.y"), "undefined:undefined");
+        assert_eq!(eval_str("a.x"), "undefined:undefined");
+        assert_eq!(eval_str("{ a: 1 }.a"), "number:1");
+    }
+
+    #[test]
+    fn literals_and_spreads() {
+        let data = JsValue::from(&serde_json::json!({ "arr": [1, 2], "obj": { "x": 1, "y": 2 } }));
+        let eval_json = |src: &str| {
+            Evaluator::new(&data, &[])
+                .eval(&parse_expr(src))
+                .unwrap()
+                .to_json()
+        };
+        assert_eq!(
+            eval_json("[...arr, , ...'ab', undefined, null]"),
+            serde_json::json!([1, 2, null, "a", "b", null, null]),
+        );
+        assert_eq!(
+            eval_json("{ ...obj, y: 3, z: undefined, ...null, ...arr }"),
+            serde_json::json!({ "x": 1, "y": 3, "0": 1, "1": 2 }),
+        );
+        assert_eq!(eval_json("1 / 0"), serde_json::Value::Null);
+        assert_eq!(eval_json("2.5"), serde_json::json!(2.5));
+    }
+
+    #[test]
+    fn scopes_and_calls() {
+        let (template, _) = crate::parse::parse(
+            "TEST",
+            r#"<block wx:for="{{ list }}">{{ f(item, index) + obj.f(1) }}</block>"#,
+        );
+        let expr = match &template.content[0] {
+            Node::Element(elem) => match &elem.children().unwrap()[0] {
+                Node::Text(Value::Dynamic { expression, .. }) => expression.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let data = JsValue::from(&serde_json::json!({ "obj": {} }));
+        let scopes = [JsValue::String("x".into()), JsValue::Number(1.)];
+        let mut calls = vec![];
+        let mut evaluator = Evaluator::new(&data, &scopes);
+        assert_eq!(evaluator.eval(&expr).unwrap().to_js_string(), "NaN");
+        evaluator.set_call_handler(|func, args| {
+            calls.push(args.clone());
+            match func {
+                Expression::DataField { .. } => Ok(JsValue::String(format!("{:?}", args))),
+                _ => Err(EvalError::new("no such function", func.location())),
+            }
+        });
+        let err = evaluator.eval(&expr).unwrap_err();
+        assert_eq!(err.location.start.line, 0);
+        assert_eq!(err.location.start.utf16_col, 47);
+        assert_eq!(err.to_string(), "no such function (at 1:48)");
+        drop(evaluator);
+        assert_eq!(
+            calls,
+            vec![
+                vec![JsValue::String("x".into()), JsValue::Number(1.)],
+                vec![JsValue::Number(1.)],
+            ],
+        );
+
+        let err = Evaluator::new(&data, &[]).eval(&expr).unwrap_err();
+        assert_eq!(err.message, "scope not found");
+        let err = Evaluator::new(&data, &[])
+            .eval(&parse_expr("a instanceof b"))
+            .unwrap_err();
+        assert_eq!(err.location.start.utf16_col, 5);
+    }
+}
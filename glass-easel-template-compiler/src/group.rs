@@ -1,17 +1,28 @@
 //! The template group for cross references
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fmt::Write;
 use std::ops::Range;
 use std::str::FromStr as _;
+use std::sync::{Arc, Mutex};
 
 use wasm_bindgen::prelude::*;
 
+use crate::completion::CompletionItem;
+use crate::data_deps::DataDependencyReport;
 use crate::escape::gen_lit_str;
-use crate::parse::{ParseError, Position, Template};
-use crate::proc_gen::{JsFunctionScopeWriter, JsTopScopeWriter};
+use crate::navigation::TmplLocation;
+use crate::parse::tag::{Element, ElementKind, Script, TemplateDefinition, Value};
+use crate::parse::visit::{self, Visit};
+use crate::parse::{
+    ParseError, ParseErrorKind, Position, Template, TemplateSource, TemplateStructure, TextEdit,
+};
+use crate::proc_gen::{extract_source_map, JsExprWriter, JsFunctionScopeWriter, JsTopScopeWriter};
+use crate::semantic_tokens::SemanticToken;
+use crate::stringify::SourceMap;
 use crate::stringify::Stringify;
+use crate::tree_shaking::TreeShakingReport;
 
 // PRESERVED one-letter vars
 // A: the binding map object
@@ -144,6 +155,60 @@ fn runtime_fns<W: std::fmt::Write>(
     Ok(())
 }
 
+lazy_static! {
+    static ref SCRIPT_REQUIRE_REGEX: regex::Regex =
+        regex::Regex::new(r#"\brequire\s*\(\s*(?:"([^"]*)"|'([^']*)')\s*\)"#).unwrap();
+}
+
+/// The module file name of the shared runtime in per-module output.
+pub const RUNTIME_MODULE_NAME: &str = "glass-easel-runtime.js";
+
+/// The module format of per-module output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleFormat {
+    /// ES modules, i.e. `import` and `export default` .
+    EsModule,
+    /// CommonJS modules, i.e. `require` and `module.exports` .
+    CommonJs,
+}
+
+fn write_module_import(
+    w: &mut JsFunctionScopeWriter<String>,
+    format: ModuleFormat,
+    binding: Option<&str>,
+    from: &str,
+    to: &str,
+) -> Result<(), TmplError> {
+    let specifier = gen_lit_str(&crate::path::relative(from, to));
+    w.expr_stmt(|w| {
+        match (format, binding) {
+            (ModuleFormat::EsModule, Some(binding)) => {
+                write!(w, "import {} from {}", binding, specifier)?
+            }
+            (ModuleFormat::EsModule, None) => write!(w, "import {}", specifier)?,
+            (ModuleFormat::CommonJs, Some(binding)) => {
+                write!(w, "var {}=require({})", binding, specifier)?
+            }
+            (ModuleFormat::CommonJs, None) => write!(w, "require({})", specifier)?,
+        }
+        Ok(())
+    })
+}
+
+fn write_module_export<R>(
+    w: &mut JsFunctionScopeWriter<String>,
+    format: ModuleFormat,
+    f: impl FnOnce(&mut JsExprWriter<String>) -> Result<R, TmplError>,
+) -> Result<R, TmplError> {
+    w.expr_stmt(|w| {
+        match format {
+            ModuleFormat::EsModule => write!(w, "export default ")?,
+            ModuleFormat::CommonJs => write!(w, "module.exports=")?,
+        }
+        f(w)
+    })
+}
+
 fn runtime_var_list() -> Vec<&'static str> {
     let mut ret: Vec<_> = RUNTIME_ITEMS.iter().map(|(k, _)| *k).collect();
     ret.push("Q");
@@ -180,22 +245,32 @@ impl std::error::Error for TmplError {}
 /// A template group in which the templates can ref each other.
 #[derive(Debug)]
 pub struct TmplGroup {
-    trees: HashMap<String, Template>,
-    scripts: HashMap<String, String>,
+    trees: BTreeMap<String, Template>,
+    sources: BTreeMap<String, TemplateSource>,
+    scripts: BTreeMap<String, String>,
     has_scripts: bool,
     extra_runtime_string: String,
     dev_mode: bool,
+    tree_shaking: bool,
+    /// The tree-shaking report of the current trees, cleared when any tree is changed.
+    tree_shaking_cache: Mutex<Option<Arc<TreeShakingReport>>>,
+    /// The constant-folded trees, cleared when the tree is changed.
+    folded_trees: Mutex<HashMap<String, Arc<Template>>>,
 }
 
 impl TmplGroup {
     /// Create a new template group.
     pub fn new() -> Self {
         Self {
-            trees: HashMap::new(),
-            scripts: HashMap::new(),
+            trees: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            scripts: BTreeMap::new(),
             has_scripts: false,
             extra_runtime_string: String::new(),
             dev_mode: false,
+            tree_shaking: false,
+            tree_shaking_cache: Mutex::new(None),
+            folded_trees: Mutex::new(HashMap::new()),
         }
     }
 
@@ -211,9 +286,81 @@ impl TmplGroup {
         self.dev_mode
     }
 
+    /// Enable or disable tree-shaking in the generated code.
+    ///
+    /// When enabled, the sub-templates and `<wxs>` modules listed in `tree_shaking_report`
+    /// are left out of the gen objects and the template modules.
+    pub fn set_tree_shaking(&mut self, enabled: bool) {
+        self.tree_shaking = enabled;
+    }
+
+    /// Get whether tree-shaking is enabled.
+    pub fn tree_shaking(&self) -> bool {
+        self.tree_shaking
+    }
+
+    /// Find out the sub-templates, `<wxs>` modules and binding map keys which are never used in the group.
+    ///
+    /// A sub-template is used if it is reachable through a static `<template is>`
+    /// from the content of any template file.
+    /// A dynamic `<template is>` keeps all the sub-templates it may refer to.
+    /// A `<wxs>` module is used if it is referenced in the template file,
+    /// except in the unused sub-templates.
+    /// A binding map key is used if it is referenced after constant folding.
+    pub fn tree_shaking_report(&self) -> TreeShakingReport {
+        (*self.cached_tree_shaking_report()).clone()
+    }
+
+    fn cached_tree_shaking_report(&self) -> Arc<TreeShakingReport> {
+        if let Some(report) = self.tree_shaking_cache.lock().unwrap().as_ref() {
+            return report.clone();
+        }
+        let report = Arc::new(crate::tree_shaking::analyze(self));
+        *self.tree_shaking_cache.lock().unwrap() = Some(report.clone());
+        report
+    }
+
+    fn tree_shaking_result(&self) -> Option<Arc<TreeShakingReport>> {
+        if self.tree_shaking {
+            Some(self.cached_tree_shaking_report())
+        } else {
+            None
+        }
+    }
+
+    fn trees_changed(&mut self) {
+        *self.tree_shaking_cache.get_mut().unwrap() = None;
+    }
+
+    fn tree_changed(&mut self, path: &str) {
+        self.trees_changed();
+        self.folded_trees.get_mut().unwrap().remove(path);
+    }
+
+    /// Get the constant-folded tree, which is used for code generation.
+    ///
+    /// A tree is folded once and reused until it is changed.
+    pub(crate) fn get_folded_tree(&self, path: &str) -> Result<Arc<Template>, TmplError> {
+        if let Some(tree) = self.folded_trees.lock().unwrap().get(path) {
+            return Ok(tree.clone());
+        }
+        let mut tree = self.get_tree(path)?.clone();
+        crate::proc_gen::fold::fold_template(&mut tree);
+        let tree = Arc::new(tree);
+        self.folded_trees
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), tree.clone());
+        Ok(tree)
+    }
+
     /// import another group.
     pub fn import_group(&mut self, group: &TmplGroup) {
+        for path in group.trees.keys() {
+            self.tree_changed(path);
+        }
         self.trees.extend(group.trees.clone());
+        self.sources.extend(group.sources.clone());
         self.scripts.extend(group.scripts.clone());
         self.has_scripts = self.has_scripts || group.has_scripts;
         self.extra_runtime_string
@@ -232,6 +379,10 @@ impl TmplGroup {
 
     /// Get a mutable ref of a parsed tree in the group.
     pub fn get_tree_mut(&mut self, path: &str) -> Result<&mut Template, TmplError> {
+        self.tree_changed(path);
+        if let Some(source) = self.sources.get_mut(path) {
+            source.mark_tree_modified();
+        }
         match self.trees.get_mut(path) {
             Some(x) => Ok(x),
             None => Err(TmplError {
@@ -242,15 +393,57 @@ impl TmplGroup {
 
     /// Add a template into the group.
     pub fn add_tmpl(&mut self, path: &str, tmpl_str: &str) -> Vec<ParseError> {
-        let (template, mut parse_state) = crate::parse::parse(path, tmpl_str);
+        let (template, source) = TemplateSource::parse(path, tmpl_str.to_string());
+        self.tree_changed(&template.path);
         if template.inline_script_module_names().next().is_some() {
             self.has_scripts = true;
         }
-        let ret = parse_state.take_warnings();
+        let ret = source.warnings();
+        self.sources.insert(template.path.clone(), source);
         self.trees.insert(template.path.clone(), template);
         ret
     }
 
+    /// Add a parsed (or deserialized) template tree into the group.
+    ///
+    /// This is useful when the tree is cached, e.g. serialized with the `serde` feature,
+    /// so that it can be compiled without re-parsing the source.
+    /// The template path is taken from the tree.
+    /// Since the source is not available, `update_tmpl` cannot be used on this template.
+    pub fn add_tmpl_tree(&mut self, mut template: Template) {
+        self.tree_changed(&template.path);
+        template.reinit_scopes_and_binding_map_keys();
+        if template.inline_script_module_names().next().is_some() {
+            self.has_scripts = true;
+        }
+        self.sources.remove(&template.path);
+        self.trees.insert(template.path.clone(), template);
+    }
+
+    /// Apply text edits to a template added by `add_tmpl` .
+    ///
+    /// Only the elements containing the edits are re-parsed when possible.
+    /// The result is the same as adding the edited template again.
+    /// Returns all warnings of the edited template.
+    pub fn update_tmpl(
+        &mut self,
+        path: &str,
+        edits: &[TextEdit],
+    ) -> Result<Vec<ParseError>, TmplError> {
+        self.tree_changed(path);
+        let (Some(template), Some(source)) = (self.trees.get_mut(path), self.sources.get_mut(path))
+        else {
+            return Err(TmplError {
+                message: format!(r#"no template source "{}" found"#, path),
+            });
+        };
+        source.apply_edits(template, edits);
+        if template.inline_script_module_names().next().is_some() {
+            self.has_scripts = true;
+        }
+        Ok(source.warnings())
+    }
+
     /// Remove a template from the group.
     ///
     /// This simply removes a template path.
@@ -258,6 +451,8 @@ impl TmplGroup {
     /// but not suitable for final builds since it does not do cleanups.
     /// Returns true when a template is actually removed.
     pub fn remove_tmpl(&mut self, path: &str) -> bool {
+        self.tree_changed(path);
+        self.sources.remove(path);
         self.trees.remove(path).is_some()
     }
 
@@ -343,6 +538,114 @@ impl TmplGroup {
         Ok(self.get_tree(path)?.script_dependencies())
     }
 
+    /// Get all template files which the specified template depends on, directly or indirectly.
+    ///
+    /// The template files which are not in the group are also included,
+    /// but their dependencies are unknown.
+    /// The result is sorted, and does not contain `path` itself unless it is in a dependency cycle.
+    pub fn transitive_dependencies(&self, path: &str) -> Result<Vec<String>, TmplError> {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<String> = self.get_tree(path)?.direct_dependencies().collect();
+        while let Some(p) = stack.pop() {
+            if visited.contains(&p) {
+                continue;
+            }
+            if let Some(tree) = self.trees.get(&p) {
+                stack.extend(tree.direct_dependencies());
+            }
+            visited.insert(p);
+        }
+        let mut ret: Vec<_> = visited.into_iter().collect();
+        ret.sort();
+        Ok(ret)
+    }
+
+    /// Get all script files which the specified template or its template dependencies depend on.
+    ///
+    /// The result is sorted.
+    pub fn transitive_script_dependencies(&self, path: &str) -> Result<Vec<String>, TmplError> {
+        let mut ret: Vec<_> = self.script_dependencies(path)?.collect();
+        for dep in self.transitive_dependencies(path)? {
+            if let Some(tree) = self.trees.get(&dep) {
+                ret.extend(tree.script_dependencies());
+            }
+        }
+        ret.sort();
+        ret.dedup();
+        Ok(ret)
+    }
+
+    /// Get all templates which depend on the specified template file, directly or indirectly.
+    ///
+    /// The template file itself does not need to be in the group.
+    /// This is useful to find out the templates that should be rebuilt when a file changes.
+    /// The result is sorted, and does not contain `path` itself unless it is in a dependency cycle.
+    pub fn dependents(&self, path: &str) -> Vec<String> {
+        self.collect_dependents(|tree| tree.direct_dependencies().any(|x| x == path))
+    }
+
+    /// Get all templates which depend on the specified script file, directly or indirectly.
+    ///
+    /// The result is sorted.
+    pub fn script_dependents(&self, path: &str) -> Vec<String> {
+        self.collect_dependents(|tree| tree.script_dependencies().any(|x| x == path))
+    }
+
+    fn collect_dependents(&self, is_direct_dependent: impl Fn(&Template) -> bool) -> Vec<String> {
+        let mut reversed: HashMap<String, Vec<&str>> = HashMap::new();
+        let mut stack = vec![];
+        for (path, tree) in self.trees.iter() {
+            for dep in tree.direct_dependencies() {
+                reversed.entry(dep).or_default().push(path);
+            }
+            if is_direct_dependent(tree) {
+                stack.push(path.as_str());
+            }
+        }
+        let mut visited = HashSet::new();
+        while let Some(p) = stack.pop() {
+            if !visited.insert(p) {
+                continue;
+            }
+            if let Some(list) = reversed.get(p) {
+                stack.extend(list.iter().copied());
+            }
+        }
+        let mut ret: Vec<_> = visited.into_iter().map(|x| x.to_string()).collect();
+        ret.sort();
+        ret
+    }
+
+    /// List all templates in the group in topological order.
+    ///
+    /// A template is always listed after the templates it depends on,
+    /// except that the dependency cycles are broken at an arbitrary but stable point.
+    /// The template files which are not in the group are not listed.
+    pub fn topological_order(&self) -> Vec<String> {
+        fn rec<'a>(
+            group: &'a TmplGroup,
+            path: &'a str,
+            visited: &mut HashSet<&'a str>,
+            ret: &mut Vec<String>,
+        ) {
+            if !visited.insert(path) {
+                return;
+            }
+            for dep in group.trees[path].direct_dependencies() {
+                if let Some((dep, _)) = group.trees.get_key_value(&dep) {
+                    rec(group, dep, visited, ret);
+                }
+            }
+            ret.push(path.to_string());
+        }
+        let mut visited = HashSet::new();
+        let mut ret = vec![];
+        for path in self.trees.keys() {
+            rec(self, path, &mut visited, &mut ret);
+        }
+        ret
+    }
+
     /// Get inline script module names.
     pub fn inline_script_module_names<'a>(
         &'a self,
@@ -389,13 +692,45 @@ impl TmplGroup {
 
     /// Convert to WXML GenObject js string.
     pub fn get_tmpl_gen_object(&self, path: &str) -> Result<String, TmplError> {
-        let tree = self.get_tree(path)?;
         let mut w = JsTopScopeWriter::new(String::new());
+        self.write_tmpl_gen_object(&mut w, path)?;
+        Ok(w.finish())
+    }
+
+    /// Convert to WXML GenObject js string, with a source map to the WXML source.
+    pub fn get_tmpl_gen_object_with_source_map(
+        &self,
+        path: &str,
+    ) -> Result<(String, SourceMap), TmplError> {
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.enable_source_map();
+        self.write_tmpl_gen_object(&mut w, path)?;
+        Ok(self.extract_source_map(&w.finish()))
+    }
+
+    fn write_tmpl_gen_object(
+        &self,
+        w: &mut JsTopScopeWriter<String>,
+        path: &str,
+    ) -> Result<(), TmplError> {
+        let tree = self.get_folded_tree(path)?;
+        let shaking = self.tree_shaking_result();
         w.expr_scope(|w| {
-            tree.to_proc_gen(w, self)?;
+            tree.to_proc_gen(w, self, shaking.as_deref())?;
             Ok(())
-        })?;
-        Ok(w.finish())
+        })
+    }
+
+    fn extract_source_map(&self, code: &str) -> (String, SourceMap) {
+        extract_source_map(code, |source_path| {
+            if let Some(p) = source_path.strip_suffix(".wxml") {
+                self.sources.get(p).and_then(|x| x.content())
+            } else if let Some(p) = source_path.strip_suffix(".wxs") {
+                self.scripts.get(p).map(|x| x.as_str())
+            } else {
+                None
+            }
+        })
     }
 
     fn write_group_global_content(
@@ -416,10 +751,12 @@ impl TmplGroup {
                 w.expr_stmt(|w| {
                     write!(
                         w,
-                        r#"R[{path}]=D({path},(require,exports,module)=>{{{}}})"#,
-                        script,
+                        r#"R[{path}]=D({path},(require,exports,module)=>{{"#,
                         path = gen_lit_str(p)
                     )?;
+                    w.set_source_path(&format!("{}.wxs", p));
+                    w.source_content(script, Position::default())?;
+                    write!(w, "}})")?;
                     Ok(())
                 })?;
             }
@@ -430,6 +767,24 @@ impl TmplGroup {
     /// Convert all to WXML GenObject js string.
     pub fn get_tmpl_gen_object_groups(&self) -> Result<String, TmplError> {
         let mut w = JsTopScopeWriter::new(String::new());
+        self.write_tmpl_gen_object_groups(&mut w)?;
+        Ok(w.finish())
+    }
+
+    /// Convert all to WXML GenObject js string, with a source map to the WXML and WXS sources.
+    pub fn get_tmpl_gen_object_groups_with_source_map(
+        &self,
+    ) -> Result<(String, SourceMap), TmplError> {
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.enable_source_map();
+        self.write_tmpl_gen_object_groups(&mut w)?;
+        Ok(self.extract_source_map(&w.finish()))
+    }
+
+    fn write_tmpl_gen_object_groups(
+        &self,
+        w: &mut JsTopScopeWriter<String>,
+    ) -> Result<(), TmplError> {
         w.expr_scope(|w| {
             w.paren(|w| {
                 w.function(|w| {
@@ -442,10 +797,12 @@ impl TmplGroup {
                         Ok(())
                     })?;
                     self.write_group_global_content(w)?;
-                    for (path, tree) in self.trees.iter() {
+                    let shaking = self.tree_shaking_result();
+                    for path in self.trees.keys() {
+                        let tree = self.get_folded_tree(path)?;
                         w.expr_stmt(|w| {
                             write!(w, r#"G[{}]="#, gen_lit_str(path))?;
-                            tree.to_proc_gen(w, self)?;
+                            tree.to_proc_gen(w, self, shaking.as_deref())?;
                             Ok(())
                         })?;
                     }
@@ -458,13 +815,30 @@ impl TmplGroup {
             })?;
             w.paren(|_| Ok(()))?;
             Ok(())
-        })?;
-        Ok(w.finish())
+        })
     }
 
     /// Convert all to WXML GenObject js string, with wx environment support.
     pub fn get_wx_gen_object_groups(&self) -> Result<String, TmplError> {
         let mut w = JsTopScopeWriter::new(String::new());
+        self.write_wx_gen_object_groups(&mut w)?;
+        Ok(w.finish())
+    }
+
+    /// Convert all to WXML GenObject js string for wx environment, with a source map.
+    pub fn get_wx_gen_object_groups_with_source_map(
+        &self,
+    ) -> Result<(String, SourceMap), TmplError> {
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.enable_source_map();
+        self.write_wx_gen_object_groups(&mut w)?;
+        Ok(self.extract_source_map(&w.finish()))
+    }
+
+    fn write_wx_gen_object_groups(
+        &self,
+        w: &mut JsTopScopeWriter<String>,
+    ) -> Result<(), TmplError> {
         w.expr_scope(|w| {
             w.paren(|w| {
                 w.function(|w| {
@@ -477,10 +851,12 @@ impl TmplGroup {
                         Ok(())
                     })?;
                     self.write_group_global_content(w)?;
-                    for (path, tree) in self.trees.iter() {
+                    let shaking = self.tree_shaking_result();
+                    for path in self.trees.keys() {
+                        let tree = self.get_folded_tree(path)?;
                         w.expr_stmt(|w| {
                             write!(w, r#"__wxCodeSpace__.addCompiledTemplate({path},{{groupList:G,content:G[{path}]="#, path = gen_lit_str(path))?;
-                            tree.to_proc_gen(w, self)?;
+                            tree.to_proc_gen(w, self, shaking.as_deref())?;
                             write!(w, "}})")?;
                             Ok(())
                         })?;
@@ -490,8 +866,121 @@ impl TmplGroup {
             })?;
             w.paren(|_| Ok(()))?;
             Ok(())
-        })?;
-        Ok(w.finish())
+        })
+    }
+
+    /// Render a template to an HTML string with the given data.
+    ///
+    /// Script function calls evaluate to `undefined` and slots are left empty.
+    /// Use `render_html_with_host` to provide them.
+    pub fn render_html(&self, path: &str, data: &serde_json::Value) -> Result<String, TmplError> {
+        crate::ssr::render(self, path, data, &mut crate::ssr::DefaultSsrHost)
+    }
+
+    /// Render a template to an HTML string, with script calls and slots handled by `host` .
+    pub fn render_html_with_host(
+        &self,
+        path: &str,
+        data: &serde_json::Value,
+        host: &mut dyn crate::ssr::SsrHost,
+    ) -> Result<String, TmplError> {
+        crate::ssr::render(self, path, data, host)
+    }
+
+    /// Get the data paths read by a template, and whether its bindings can be updated through the binding map.
+    ///
+    /// Fails rather than reporting fewer data paths if any expression cannot be analyzed.
+    pub fn data_dependency_report(&self, path: &str) -> Result<DataDependencyReport, TmplError> {
+        crate::data_deps::analyze(self.get_tree(path)?)
+    }
+
+    /// Find the definition of the symbol at the position of a template.
+    ///
+    /// The symbol can be a scope variable, a `<wxs>` module, the target of a static `<template is>` ,
+    /// or the `src` of `<import>` and `<include>` .
+    /// Returns `None` if there is no such symbol or the definition cannot be found.
+    pub fn find_definition(
+        &self,
+        path: &str,
+        pos: Position,
+    ) -> Result<Option<TmplLocation>, TmplError> {
+        let tree = self.get_tree(path)?;
+        Ok(crate::navigation::find_definition(self, tree, pos))
+    }
+
+    /// Find all references of the symbol at the position of a template, including its definition.
+    ///
+    /// Scope variables are only referenced in the same file,
+    /// while sub-templates and imported files can be referenced across the group.
+    pub fn find_references(
+        &self,
+        path: &str,
+        pos: Position,
+    ) -> Result<Vec<TmplLocation>, TmplError> {
+        let tree = self.get_tree(path)?;
+        Ok(crate::navigation::find_references(self, tree, pos))
+    }
+
+    /// Compute the text edits to rename the symbol at the position of a template.
+    ///
+    /// The edits are grouped by template paths, and sorted in the reversed order of positions in each file.
+    /// Returns an error if the new name is not a valid identifier, collides with other names in use,
+    /// or the symbol cannot be renamed.
+    pub fn rename_symbol(
+        &self,
+        path: &str,
+        pos: Position,
+        new_name: &str,
+    ) -> Result<BTreeMap<String, Vec<TextEdit>>, TmplError> {
+        let tree = self.get_tree(path)?;
+        crate::navigation::rename(self, tree, pos, new_name)
+    }
+
+    /// List the completion candidates at the position of a template.
+    ///
+    /// Inside `{{ }}` these are the visible scopes, and the sub-templates inside `<template is>` .
+    /// Attribute prefixes are listed where an attribute name is expected,
+    /// and the template paths are listed inside `<import src>` and `<include src>` .
+    /// The source can be incomplete.
+    /// The source text is always used, even if the template tree is modified after parsing,
+    /// but no candidate is found for a template added by `add_tmpl_tree` .
+    pub fn completions(&self, path: &str, pos: Position) -> Result<Vec<CompletionItem>, TmplError> {
+        self.get_tree(path)?;
+        let Some(source) = self.sources.get(path) else {
+            return Ok(vec![]);
+        };
+        Ok(crate::completion::completions(
+            self,
+            path,
+            source.text(),
+            pos,
+        ))
+    }
+
+    /// Classify the tokens of a template for syntax highlighting.
+    ///
+    /// The tokens are sorted and never span multiple lines.
+    /// Use `SemanticToken::encode` to get the LSP semantic tokens encoding.
+    pub fn semantic_tokens(&self, path: &str) -> Result<Vec<SemanticToken>, TmplError> {
+        let tree = self.get_tree(path)?;
+        let Some(src) = self.sources.get(path).and_then(|x| x.content()) else {
+            return Err(TmplError {
+                message: format!(r#"the source of template "{}" is not available"#, path),
+            });
+        };
+        Ok(crate::semantic_tokens::semantic_tokens(tree, src))
+    }
+
+    /// Compile a template into an instruction list, which can be executed without `eval` .
+    ///
+    /// See the `instr` module for the semantics of the instructions.
+    pub fn get_tmpl_instructions(
+        &self,
+        path: &str,
+    ) -> Result<crate::instr::InstrTemplate, TmplError> {
+        let tmpl = self.get_folded_tree(path)?;
+        let shaking = self.tree_shaking_result();
+        Ok(crate::instr::compile(&tmpl, shaking.as_deref()))
     }
 
     pub fn export_globals(&self) -> Result<String, TmplError> {
@@ -515,6 +1004,215 @@ impl TmplGroup {
         Ok(w.finish())
     }
 
+    /// Get the module file name of a template in per-module output.
+    pub fn tmpl_module_name(path: &str) -> String {
+        format!("{}.wxml.js", path)
+    }
+
+    /// Get the module file name of a script in per-module output.
+    pub fn script_module_name(path: &str) -> String {
+        format!("{}.wxs.js", path)
+    }
+
+    /// Generate the shared runtime module.
+    ///
+    /// This is the module version of `export_globals` , which every template and script module imports.
+    pub fn get_runtime_module(&self, format: ModuleFormat) -> Result<String, TmplError> {
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.function_scope(|w| {
+            runtime_fns(w, self.has_scripts)?;
+            if !self.extra_runtime_string.is_empty() {
+                w.custom_stmt_str(&self.extra_runtime_string)?;
+            }
+            let vars = self.module_runtime_vars();
+            w.expr_stmt(|w| {
+                match format {
+                    ModuleFormat::EsModule => write!(w, "export{{{}}}", vars)?,
+                    ModuleFormat::CommonJs => write!(w, "module.exports={{{}}}", vars)?,
+                }
+                Ok(())
+            })
+        })?;
+        Ok(w.finish())
+    }
+
+    fn module_runtime_vars(&self) -> String {
+        let mut vars = runtime_var_list();
+        if self.has_scripts {
+            vars.push("D");
+        }
+        vars.join(",")
+    }
+
+    /// Generate the module of a script.
+    ///
+    /// The other scripts which are `require` d with string literals are imported,
+    /// so that they are always registered before use.
+    pub fn get_script_module(&self, path: &str, format: ModuleFormat) -> Result<String, TmplError> {
+        let Some(script) = self.scripts.get(path) else {
+            return Err(TmplError {
+                message: format!(r#"no script "{}" found"#, path),
+            });
+        };
+        let module_name = Self::script_module_name(path);
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.function_scope(|w| {
+            write_module_import(w, format, Some("{D}"), &module_name, RUNTIME_MODULE_NAME)?;
+            let mut deps: Vec<_> = SCRIPT_REQUIRE_REGEX
+                .captures_iter(script)
+                .filter_map(|cap| cap.get(1).or_else(|| cap.get(2)))
+                .map(|rel| crate::path::resolve(path, rel.as_str()))
+                .filter(|dep| dep != path && self.scripts.contains_key(dep))
+                .collect();
+            deps.sort();
+            deps.dedup();
+            for dep in deps {
+                write_module_import(
+                    w,
+                    format,
+                    None,
+                    &module_name,
+                    &Self::script_module_name(&dep),
+                )?;
+            }
+            write_module_export(w, format, |w| {
+                write!(
+                    w,
+                    r#"D({path},(require,exports,module)=>{{{}}})"#,
+                    script,
+                    path = gen_lit_str(path)
+                )?;
+                Ok(())
+            })
+        })?;
+        Ok(w.finish())
+    }
+
+    /// Generate the module of a template.
+    ///
+    /// The module exports the same thing as `get_tmpl_gen_object` .
+    /// The runtime, the scripts and the `<import>` / `<include>` targets are imported as modules.
+    pub fn get_tmpl_module(&self, path: &str, format: ModuleFormat) -> Result<String, TmplError> {
+        let tree = self.get_tree(path)?;
+        let module_name = Self::tmpl_module_name(path);
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.function_scope(|w| {
+            let runtime_binding = format!("{{{}}}", self.module_runtime_vars());
+            write_module_import(
+                w,
+                format,
+                Some(&runtime_binding),
+                &module_name,
+                RUNTIME_MODULE_NAME,
+            )?;
+
+            // scripts are loaded when the template module is evaluated
+            let shaking = self.tree_shaking_result();
+            let mut scripts = vec![];
+            for script in tree.globals.scripts.iter() {
+                let dep = match script {
+                    Script::GlobalRef { src, .. } => crate::path::resolve(&tree.path, &src.name),
+                    Script::Inline { .. } => continue,
+                };
+                if let Some(shaking) = shaking.as_deref() {
+                    if shaking.is_script_removed(path, &script.module_name().name) {
+                        continue;
+                    }
+                }
+                if !self.scripts.contains_key(&dep) || scripts.iter().any(|(x, _)| x == &dep) {
+                    continue;
+                }
+                let ident = w.gen_private_ident().to_string();
+                let dep_module_name = Self::script_module_name(&dep);
+                write_module_import(w, format, Some(&ident), &module_name, &dep_module_name)?;
+                scripts.push((dep, ident));
+            }
+
+            // templates are visited lazily, so circular dependencies are allowed
+            let mut deps = vec![];
+            for dep in tree.direct_dependencies() {
+                if !self.trees.contains_key(&dep) || deps.iter().any(|(x, _)| x == &dep) {
+                    continue;
+                }
+                let dep_module_name = Self::tmpl_module_name(&dep);
+                let expr = match format {
+                    ModuleFormat::EsModule => {
+                        let ident = w.gen_private_ident().to_string();
+                        write_module_import(
+                            w,
+                            format,
+                            Some(&ident),
+                            &module_name,
+                            &dep_module_name,
+                        )?;
+                        ident
+                    }
+                    ModuleFormat::CommonJs => {
+                        let specifier = crate::path::relative(&module_name, &dep_module_name);
+                        format!("require({})", gen_lit_str(&specifier))
+                    }
+                };
+                deps.push((dep, expr));
+            }
+
+            w.expr_stmt(|w| {
+                write!(w, "var R={{")?;
+                for (i, (dep, ident)) in scripts.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ",")?;
+                    }
+                    write!(w, "{}:{}", gen_lit_str(dep), ident)?;
+                }
+                write!(w, "}}")?;
+                Ok(())
+            })?;
+            w.expr_stmt(|w| {
+                write!(w, "var G={{")?;
+                for (i, (dep, expr)) in deps.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ",")?;
+                    }
+                    write!(w, "get {}(){{return {}}}", gen_lit_str(dep), expr)?;
+                }
+                write!(w, "}}")?;
+                Ok(())
+            })?;
+            write_module_export(w, format, |w| {
+                self.get_folded_tree(path)?
+                    .to_proc_gen(w, self, shaking.as_deref())
+            })
+        })?;
+        Ok(w.finish())
+    }
+
+    /// Generate all modules in the group, including the runtime, the scripts and the templates.
+    ///
+    /// Returns a list of module file names and module contents.
+    /// The module file names are relative to a common root directory,
+    /// and the modules import each other with relative paths.
+    pub fn get_all_modules(
+        &self,
+        format: ModuleFormat,
+    ) -> Result<Vec<(String, String)>, TmplError> {
+        let mut ret = vec![(
+            RUNTIME_MODULE_NAME.to_string(),
+            self.get_runtime_module(format)?,
+        )];
+        for path in self.scripts.keys() {
+            ret.push((
+                Self::script_module_name(path),
+                self.get_script_module(path, format)?,
+            ));
+        }
+        for path in self.trees.keys() {
+            ret.push((
+                Self::tmpl_module_name(path),
+                self.get_tmpl_module(path, format)?,
+            ));
+        }
+        Ok(ret)
+    }
+
     /// Get a string that used to check TypeScript problems.
     pub fn get_tmpl_converted_expr(
         &self,
@@ -524,7 +1222,7 @@ impl TmplGroup {
         let tree = self.get_tree(path)?;
         let env = crate::stringify::typescript::tmpl_converted_expr_runtime_string();
         let (code, source_map) =
-            crate::stringify::typescript::generate_tmpl_converted_expr(tree, ts_env, env);
+            crate::stringify::typescript::generate_tmpl_converted_expr(self, tree, ts_env, env);
         Ok(TmplConvertedExpr { code, source_map })
     }
 
@@ -542,6 +1240,177 @@ impl TmplGroup {
     pub fn list_template_trees(&self) -> impl Iterator<Item = (&str, &Template)> {
         self.trees.iter().map(|(name, tmpl)| (name.as_str(), tmpl))
     }
+
+    /// List the template files whose `<template name>` can be used in `tree` .
+    ///
+    /// The order is the same as the runtime lookup order:
+    /// `tree` itself first, and then the later imported ones.
+    pub(crate) fn sub_template_sources<'a>(
+        &'a self,
+        tree: &'a Template,
+    ) -> impl Iterator<Item = &'a Template> {
+        std::iter::once(tree).chain(tree.globals.imports.iter().rev().filter_map(move |i| {
+            let p = crate::path::resolve(&tree.path, &i.src.name);
+            self.trees.get(&p)
+        }))
+    }
+
+    /// Find the `<template name>` that a `<template is>` in `tree` refers to.
+    ///
+    /// Returns the template file containing it and the definition.
+    pub(crate) fn find_sub_template<'a>(
+        &'a self,
+        tree: &'a Template,
+        name: &str,
+    ) -> Option<(&'a Template, &'a TemplateDefinition)> {
+        self.sub_template_sources(tree).find_map(|t| {
+            t.globals
+                .sub_templates
+                .iter()
+                .rev()
+                .find(|x| x.name.name == name)
+                .map(|x| (t, x))
+        })
+    }
+
+    /// Check the references between templates and scripts in the group.
+    ///
+    /// The following problems are reported at the locations where they are referenced:
+    ///
+    /// - an `<import src>` or `<include src>` target is not in the group;
+    /// - a `<wxs src>` target is not added by `add_script` ;
+    /// - a static `<template is>` name is neither defined in the file nor in its imported files;
+    /// - a template name shadows another one with the same name in the imported files;
+    /// - an `<include>` leads to a circular inclusion.
+    ///
+    /// The results are sorted by template paths and locations.
+    pub fn validate(&self) -> Vec<ParseError> {
+        let mut ret = vec![];
+        for (path, tree) in self.trees.iter() {
+            let mut add_error = |kind, location| {
+                ret.push(ParseError {
+                    path: path.clone(),
+                    kind,
+                    location,
+                })
+            };
+
+            // check referenced files
+            let dep_locations = tree
+                .globals
+                .imports
+                .iter()
+                .map(|x| x.src.location())
+                .chain(tree.globals.includes.iter().map(|x| x.src.location()));
+            for (dep, location) in tree.direct_dependencies().zip(dep_locations) {
+                if !self.trees.contains_key(&dep) {
+                    add_error(ParseErrorKind::TemplateFileNotFound, location);
+                }
+            }
+            for (dep, script) in
+                tree.script_dependencies()
+                    .zip(tree.globals.scripts.iter().filter_map(|x| match x {
+                        Script::GlobalRef { src, .. } => Some(src.location()),
+                        Script::Inline { .. } => None,
+                    }))
+            {
+                if !self.scripts.contains_key(&dep) {
+                    add_error(ParseErrorKind::ScriptFileNotFound, script);
+                }
+            }
+
+            // collect template names, the later ones override the former ones
+            let mut names = HashSet::new();
+            for import in tree.globals.imports.iter() {
+                let dep = crate::path::resolve(path, &import.src.name);
+                let Some(imported) = self.trees.get(&dep) else {
+                    continue;
+                };
+                for t in imported.globals.sub_templates.iter() {
+                    if !names.insert(t.name.name.as_str()) {
+                        add_error(ParseErrorKind::ShadowedTemplateName, import.src.location());
+                    }
+                }
+            }
+            for t in tree.globals.sub_templates.iter() {
+                if !names.insert(t.name.name.as_str()) {
+                    add_error(ParseErrorKind::ShadowedTemplateName, t.name.location());
+                }
+            }
+
+            // check template refs
+            let mut collector = TemplateRefCollector { refs: vec![] };
+            collector.visit_template(tree);
+            for (name, location) in collector.refs {
+                if !names.contains(name) {
+                    add_error(ParseErrorKind::TemplateNameNotFound, location);
+                }
+            }
+        }
+
+        // check circular includes
+        let mut include_states = HashMap::new();
+        for path in self.trees.keys() {
+            if !include_states.contains_key(path.as_str()) {
+                self.check_circular_includes(path, &mut include_states, &mut ret);
+            }
+        }
+
+        ret.sort_by(|a, b| {
+            a.path
+                .cmp(&b.path)
+                .then_with(|| a.location.start.cmp(&b.location.start))
+        });
+        ret
+    }
+
+    /// Depth-first search in the include graph.
+    ///
+    /// The state of a path is `false` when it is still being searched, and `true` when it is done.
+    fn check_circular_includes<'a>(
+        &'a self,
+        path: &'a str,
+        states: &mut HashMap<&'a str, bool>,
+        errors: &mut Vec<ParseError>,
+    ) {
+        states.insert(path, false);
+        let tree = &self.trees[path];
+        for include in tree.globals.includes.iter() {
+            let dep = crate::path::resolve(path, &include.src.name);
+            let Some((dep, _)) = self.trees.get_key_value(&dep) else {
+                continue;
+            };
+            match states.get(dep.as_str()) {
+                Some(false) => errors.push(ParseError {
+                    path: path.to_string(),
+                    kind: ParseErrorKind::CircularInclude,
+                    location: include.src.location(),
+                }),
+                Some(true) => {}
+                None => self.check_circular_includes(dep, states, errors),
+            }
+        }
+        states.insert(path, true);
+    }
+}
+
+struct TemplateRefCollector<'a> {
+    refs: Vec<(&'a str, Range<Position>)>,
+}
+
+impl<'a> Visit<'a> for TemplateRefCollector<'a> {
+    fn visit_element(&mut self, x: &'a Element) {
+        if let ElementKind::TemplateRef {
+            target: (_, Value::Static { value, location }),
+            ..
+        } = &x.kind
+        {
+            if !value.is_empty() {
+                self.refs.push((value.as_str(), location.clone()));
+            }
+        }
+        visit::walk_element(self, x)
+    }
 }
 
 /// A string for TypeScript type checks with metadata.
@@ -0,0 +1,775 @@
+//! Compile templates into instruction lists that can be executed without `eval` .
+//!
+//! The gen-object output is JavaScript code, which cannot be loaded in environments with a strict CSP.
+//! An [`InstrTemplate`] describes the same template as plain data,
+//! so it can be serialized (JSON or any other serde format) and executed by an interpreter in the runtime.
+//!
+//! The interpreter should follow the same semantics as the gen-object code.
+//!
+//! - Scopes are indexed like [`Expression::ScopeRef`](crate::parse::expr::Expression::ScopeRef) :
+//!   the scripts of the file come first, then each element pushes its `slot_value_refs` , then its `let_vars` ,
+//!   and each `wx:for` item pushes the item and then the index.
+//!   The scopes pushed by an element are only visible to its subtree.
+//! - Member accesses never throw: reading a field of `null` or `undefined` gives `undefined` .
+//!   Calling a non-function value gives `undefined` .
+//! - A [`InstrValue::Dynamic`] should be re-evaluated on updates when any of its `deps` overlaps the changed paths,
+//!   i.e. one of the paths is a prefix of the other.
+//!   A scope dependency is changed when the value of the scope is re-evaluated with overlapping changes.
+//! - `<template is>` targets are resolved among the sub-templates of the file and its imports,
+//!   in which the local ones take precedence and later imports take precedence over earlier ones.
+//!   A sub-template runs with only the scripts of its defining file as scopes,
+//!   and the `data` value as the data.
+
+use crate::{
+    parse::{
+        expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
+        tag::{
+            Attribute, ClassAttribute, CommonElementAttributes, ElementKind, Node,
+            NormalAttributePrefix, Script, StyleAttribute, Value,
+        },
+        visit::{self, Visit},
+        Template,
+    },
+    TreeShakingReport,
+};
+
+/// The format version of [`InstrTemplate`] .
+pub const INSTR_VERSION: u32 = 1;
+
+/// A compiled template file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrTemplate {
+    /// The format version, always [`INSTR_VERSION`] .
+    pub version: u32,
+    /// The template path.
+    pub path: String,
+    /// The absolute paths of the `<import>` ed templates, in source order.
+    pub imports: Vec<String>,
+    /// The `<wxs>` modules in scope order, or `None` for the ones removed by tree-shaking.
+    pub scripts: Vec<Option<InstrScript>>,
+    /// The `<template name>` definitions.
+    pub sub_templates: Vec<InstrSubTemplate>,
+    /// The content of the file.
+    pub content: Vec<Instr>,
+}
+
+/// A `<wxs>` module.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrScript {
+    /// A `<wxs src>` , with the absolute path of the script.
+    Global { src: String },
+    /// An inline `<wxs>` , identified as `{template path}#{module name}` .
+    Inline { id: String, code: String },
+}
+
+/// A `<template name>` definition.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrSubTemplate {
+    pub name: String,
+    pub content: Vec<Instr>,
+}
+
+/// A node in the template.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instr {
+    /// A text node, converted to string with `undefined` as an empty string.
+    Text(InstrValue),
+    /// A normal element.
+    Element {
+        tag: String,
+        generics: Vec<(String, String)>,
+        /// The target slot, converted to string like text nodes.
+        slot: Option<InstrValue>,
+        slot_value_refs: Vec<String>,
+        let_vars: Vec<InstrValue>,
+        props: Vec<InstrProp>,
+        children: Vec<Instr>,
+    },
+    /// A `<block>` or any other element without a real node.
+    Virtual {
+        slot: Option<InstrValue>,
+        slot_value_refs: Vec<String>,
+        let_vars: Vec<InstrValue>,
+        children: Vec<Instr>,
+    },
+    /// A `wx:if` chain; the first branch with a truthy condition (or without a condition) is rendered.
+    If { branches: Vec<InstrBranch> },
+    /// A `wx:for` list.
+    For {
+        list: InstrValue,
+        /// The `wx:key` , if any.
+        key: Option<String>,
+        children: Vec<Instr>,
+    },
+    /// A `<slot>` .
+    Slot {
+        name: InstrValue,
+        slot: Option<InstrValue>,
+        props: Vec<InstrProp>,
+        values: Vec<(String, InstrValue)>,
+    },
+    /// A `<template is>` .
+    TemplateRef {
+        target: InstrValue,
+        data: InstrValue,
+    },
+    /// An `<include>` , with the absolute path of the included template.
+    Include { path: String },
+}
+
+/// A branch of [`Instr::If`] .
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrBranch {
+    /// The condition, or `None` for `wx:else` .
+    pub cond: Option<InstrValue>,
+    pub children: Vec<Instr>,
+}
+
+/// A property update on an element, applied in list order.
+///
+/// The names and values are passed to the same runtime methods as the gen-object code.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrProp {
+    ExtraAttr {
+        name: String,
+        value: String,
+    },
+    Class(InstrValue),
+    /// `class:` items; an item without a value is always added.
+    ClassList(Vec<(String, Option<InstrValue>)>),
+    Style(InstrValue),
+    /// `style:` items, each value converted to string like text nodes.
+    StyleList(Vec<(String, InstrValue)>),
+    Worklet {
+        name: String,
+        value: String,
+    },
+    Change {
+        name: String,
+        value: InstrValue,
+    },
+    /// A normal attribute; `model` means changes should be written back to the path of the expression.
+    Attr {
+        name: String,
+        value: InstrValue,
+        model: bool,
+    },
+    /// A `data-` (with `hyphen` ) or `data:` attribute.
+    Data {
+        name: String,
+        value: InstrValue,
+        hyphen: bool,
+    },
+    Mark {
+        name: String,
+        value: InstrValue,
+    },
+    Event {
+        name: String,
+        value: InstrValue,
+        catch: bool,
+        mutated: bool,
+        capture: bool,
+    },
+    Id(InstrValue),
+}
+
+/// A value in the template.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrValue {
+    Static(String),
+    Dynamic {
+        expr: InstrExpr,
+        /// The data paths that the expression reads; an empty list means it never changes.
+        deps: Vec<InstrPath>,
+    },
+}
+
+/// A data path read by an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrPath {
+    pub root: InstrPathRoot,
+    /// The static fields after the root; the path stops before any dynamic member.
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrPathRoot {
+    Data(String),
+    Scope(usize),
+}
+
+/// An expression without source locations.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrExpr {
+    Scope(usize),
+    Data(String),
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Obj(Vec<InstrObjField>),
+    Arr(Vec<InstrArrField>),
+    /// A template string; all parts are converted to string and concatenated.
+    Concat(Vec<InstrExpr>),
+    /// Convert to string, with `undefined` as an empty string.
+    ToStr(Box<InstrExpr>),
+    Member(Box<InstrExpr>, Box<InstrExpr>),
+    Call(Box<InstrExpr>, Vec<InstrExpr>),
+    Unary(InstrUnaryOp, Box<InstrExpr>),
+    Binary(InstrBinaryOp, Box<InstrExpr>, Box<InstrExpr>),
+    Cond(Box<InstrExpr>, Box<InstrExpr>, Box<InstrExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrObjField {
+    Named(String, InstrExpr),
+    Spread(InstrExpr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrArrField {
+    Normal(InstrExpr),
+    Spread(InstrExpr),
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrUnaryOp {
+    Not,
+    BitNot,
+    Positive,
+    Negative,
+    TypeOf,
+    Void,
+}
+
+/// A binary operator, with the same semantics as the JavaScript one.
+///
+/// `LogicAnd` `LogicOr` and `NullishCoalescing` short-circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstrBinaryOp {
+    Multiply,
+    Divide,
+    Remainder,
+    Plus,
+    Minus,
+    LeftShift,
+    RightShift,
+    UnsignedRightShift,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+    InstanceOf,
+    Eq,
+    Ne,
+    EqFull,
+    NeFull,
+    BitAnd,
+    BitXor,
+    BitOr,
+    LogicAnd,
+    LogicOr,
+    NullishCoalescing,
+}
+
+/// Compile a template that has already been constant-folded.
+pub(crate) fn compile(tmpl: &Template, shaking: Option<&TreeShakingReport>) -> InstrTemplate {
+    let path = tmpl.path.as_str();
+    let c = Compiler {
+        script_count: tmpl.globals.scripts.len(),
+        path,
+    };
+    let scripts = tmpl
+        .globals
+        .scripts
+        .iter()
+        .map(|script| {
+            let name = &script.module_name().name;
+            if shaking
+                .map(|x| x.is_script_removed(path, name))
+                .unwrap_or(false)
+            {
+                return None;
+            }
+            let ret = match script {
+                Script::GlobalRef { src, .. } => InstrScript::Global {
+                    src: crate::path::resolve(path, &src.name),
+                },
+                Script::Inline { content, .. } => InstrScript::Inline {
+                    id: format!("{}#{}", path, name),
+                    code: content.clone(),
+                },
+            };
+            Some(ret)
+        })
+        .collect();
+    let sub_templates = tmpl
+        .globals
+        .sub_templates
+        .iter()
+        .filter(|t| {
+            !shaking
+                .map(|x| x.is_template_removed(path, &t.name.name))
+                .unwrap_or(false)
+        })
+        .map(|t| InstrSubTemplate {
+            name: t.name.name.to_string(),
+            content: c.nodes(&t.content),
+        })
+        .collect();
+    InstrTemplate {
+        version: INSTR_VERSION,
+        path: path.to_string(),
+        imports: tmpl
+            .globals
+            .imports
+            .iter()
+            .map(|i| crate::path::resolve(path, &i.src.name))
+            .collect(),
+        scripts,
+        sub_templates,
+        content: c.nodes(&tmpl.content),
+    }
+}
+
+struct Compiler<'a> {
+    script_count: usize,
+    path: &'a str,
+}
+
+impl<'a> Compiler<'a> {
+    fn nodes(&self, list: &[Node]) -> Vec<Instr> {
+        list.iter().filter_map(|x| self.node(x)).collect()
+    }
+
+    fn node(&self, node: &Node) -> Option<Instr> {
+        let elem = match node {
+            Node::Text(value) => return Some(Instr::Text(self.value(value))),
+            Node::Element(elem) => elem,
+            Node::Comment(..) | Node::UnknownMetaTag(..) => return None,
+        };
+        let ret = match &elem.kind {
+            ElementKind::Normal {
+                tag_name,
+                attributes,
+                class,
+                style,
+                change_attributes,
+                worklet_attributes,
+                children,
+                generics,
+                extra_attr,
+                let_vars,
+                common,
+            } => {
+                let mut props = vec![];
+                for attr in extra_attr {
+                    props.push(InstrProp::ExtraAttr {
+                        name: attr.name.name.to_string(),
+                        value: attr.value.name.to_string(),
+                    });
+                }
+                match class {
+                    ClassAttribute::None => {}
+                    ClassAttribute::String(_, value) => {
+                        props.push(InstrProp::Class(self.value(value)))
+                    }
+                    ClassAttribute::Multiple(list) => props.push(InstrProp::ClassList(
+                        list.iter()
+                            .map(|(_, name, value)| {
+                                let value = match value {
+                                    None | Some(Value::Static { .. }) => None,
+                                    Some(value) => Some(self.value(value)),
+                                };
+                                (name.name.to_string(), value)
+                            })
+                            .collect(),
+                    )),
+                }
+                match style {
+                    StyleAttribute::None => {}
+                    StyleAttribute::String(_, value) => {
+                        props.push(InstrProp::Style(self.value(value)))
+                    }
+                    StyleAttribute::Multiple(list) => props.push(InstrProp::StyleList(
+                        list.iter()
+                            .map(|(_, name, value)| {
+                                (name.name.to_string(), self.to_str_value(value))
+                            })
+                            .collect(),
+                    )),
+                }
+                for attr in worklet_attributes {
+                    props.push(InstrProp::Worklet {
+                        name: attr.name.name.to_string(),
+                        value: attr.value.name.to_string(),
+                    });
+                }
+                for attr in change_attributes {
+                    // static values never change, so nothing should be observed
+                    if let Some(value @ Value::Dynamic { .. }) = &attr.value {
+                        props.push(InstrProp::Change {
+                            name: attr.name.name.to_string(),
+                            value: self.value(value),
+                        });
+                    }
+                }
+                for attr in attributes {
+                    let value = match &attr.value {
+                        None => constant(InstrExpr::Bool(true)),
+                        Some(value) => self.value(value),
+                    };
+                    props.push(InstrProp::Attr {
+                        name: attr.name.name.to_string(),
+                        model: matches!(attr.prefix, NormalAttributePrefix::Model(_))
+                            && matches!(value, InstrValue::Dynamic { .. }),
+                        value,
+                    });
+                }
+                self.common_props(common, &mut props);
+                Instr::Element {
+                    tag: tag_name.name.to_string(),
+                    generics: generics
+                        .iter()
+                        .map(|x| (x.name.name.to_string(), x.value.name.to_string()))
+                        .collect(),
+                    slot: common.slot.as_ref().map(|(_, x)| self.to_str_value(x)),
+                    slot_value_refs: common
+                        .slot_value_refs
+                        .iter()
+                        .map(|x| x.name.name.to_string())
+                        .collect(),
+                    let_vars: self.let_vars(let_vars),
+                    props,
+                    children: self.nodes(children),
+                }
+            }
+            ElementKind::Pure {
+                children,
+                let_vars,
+                slot,
+                slot_value_refs,
+            } => Instr::Virtual {
+                slot: slot.as_ref().map(|(_, x)| self.to_str_value(x)),
+                slot_value_refs: slot_value_refs
+                    .iter()
+                    .map(|x| x.name.name.to_string())
+                    .collect(),
+                let_vars: self.let_vars(let_vars),
+                children: self.nodes(children),
+            },
+            ElementKind::For {
+                list,
+                key,
+                children,
+                ..
+            } => Instr::For {
+                list: self.value(&list.1),
+                key: match key.1.name.as_str() {
+                    "" => None,
+                    key => Some(key.to_string()),
+                },
+                children: self.nodes(children),
+            },
+            ElementKind::If {
+                branches,
+                else_branch,
+            } => {
+                let mut list: Vec<_> = branches
+                    .iter()
+                    .map(|(_, cond, children)| InstrBranch {
+                        cond: Some(self.value(cond)),
+                        children: self.nodes(children),
+                    })
+                    .collect();
+                if let Some((_, children)) = else_branch {
+                    list.push(InstrBranch {
+                        cond: None,
+                        children: self.nodes(children),
+                    });
+                }
+                Instr::If { branches: list }
+            }
+            ElementKind::TemplateRef { target, data } => Instr::TemplateRef {
+                target: self.value(&target.1),
+                data: self.value(&data.1),
+            },
+            ElementKind::Include { path } => Instr::Include {
+                path: crate::path::resolve(self.path, &path.1.name),
+            },
+            ElementKind::Slot {
+                name,
+                values,
+                common,
+            } => {
+                let mut props = vec![];
+                self.common_props(common, &mut props);
+                Instr::Slot {
+                    name: self.to_str_value(&name.1),
+                    slot: common.slot.as_ref().map(|(_, x)| self.to_str_value(x)),
+                    props,
+                    values: values
+                        .iter()
+                        .map(|attr| {
+                            let value = match &attr.value {
+                                None => InstrValue::Static(String::new()),
+                                Some(value) => self.value(value),
+                            };
+                            (attr.name.name.to_string(), value)
+                        })
+                        .collect(),
+                }
+            }
+        };
+        Some(ret)
+    }
+
+    fn common_props(&self, common: &CommonElementAttributes, props: &mut Vec<InstrProp>) {
+        let attr_value = |attr: &Attribute| match &attr.value {
+            None => constant(InstrExpr::Bool(true)),
+            Some(value) => self.value(value),
+        };
+        for attr in common.data.iter() {
+            props.push(InstrProp::Data {
+                name: attr.name.name.to_string(),
+                value: attr_value(attr),
+                hyphen: attr.prefix_location.is_none(),
+            });
+        }
+        for attr in common.marks.iter() {
+            props.push(InstrProp::Mark {
+                name: attr.name.name.to_string(),
+                value: attr_value(attr),
+            });
+        }
+        for ev in common.event_bindings.iter() {
+            props.push(InstrProp::Event {
+                name: ev.name.name.to_string(),
+                value: match &ev.value {
+                    None => InstrValue::Static(String::new()),
+                    Some(value) => self.value(value),
+                },
+                catch: ev.is_catch,
+                mutated: ev.is_mut,
+                capture: ev.is_capture,
+            });
+        }
+        if let Some((_, value)) = common.id.as_ref() {
+            props.push(InstrProp::Id(self.value(value)));
+        }
+    }
+
+    fn let_vars(&self, list: &[Attribute]) -> Vec<InstrValue> {
+        list.iter()
+            .map(|attr| match &attr.value {
+                None => constant(InstrExpr::Undefined),
+                Some(value) => self.value(value),
+            })
+            .collect()
+    }
+
+    fn value(&self, value: &Value) -> InstrValue {
+        match value {
+            Value::Static { value, .. } => InstrValue::Static(value.to_string()),
+            Value::Dynamic { expression, .. } => InstrValue::Dynamic {
+                expr: expr(expression),
+                deps: self.deps(expression),
+            },
+        }
+    }
+
+    fn to_str_value(&self, value: &Value) -> InstrValue {
+        match self.value(value) {
+            InstrValue::Dynamic { expr, deps } => InstrValue::Dynamic {
+                expr: InstrExpr::ToStr(Box::new(expr)),
+                deps,
+            },
+            x => x,
+        }
+    }
+
+    fn deps(&self, expression: &Expression) -> Vec<InstrPath> {
+        let mut v = DepCollector {
+            script_count: self.script_count,
+            deps: vec![],
+        };
+        v.visit_expression(expression);
+        v.deps
+    }
+}
+
+fn constant(expr: InstrExpr) -> InstrValue {
+    InstrValue::Dynamic { expr, deps: vec![] }
+}
+
+struct DepCollector {
+    script_count: usize,
+    deps: Vec<InstrPath>,
+}
+
+impl DepCollector {
+    fn path_of(x: &Expression) -> Option<InstrPath> {
+        match x {
+            Expression::ScopeRef { index, .. } => Some(InstrPath {
+                root: InstrPathRoot::Scope(*index),
+                fields: vec![],
+            }),
+            Expression::DataField { name, .. } => Some(InstrPath {
+                root: InstrPathRoot::Data(name.to_string()),
+                fields: vec![],
+            }),
+            Expression::StaticMember {
+                obj, field_name, ..
+            }
+            | Expression::OptionalStaticMember {
+                obj, field_name, ..
+            } => {
+                let mut p = Self::path_of(obj)?;
+                p.fields.push(field_name.to_string());
+                Some(p)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Visit<'a> for DepCollector {
+    fn visit_expression(&mut self, x: &'a Expression) {
+        let Some(p) = Self::path_of(x) else {
+            visit::walk_expression(self, x);
+            return;
+        };
+        // script modules never change
+        if let InstrPathRoot::Scope(index) = p.root {
+            if index < self.script_count {
+                return;
+            }
+        }
+        if !self.deps.contains(&p) {
+            self.deps.push(p);
+        }
+    }
+}
+
+fn expr(x: &Expression) -> InstrExpr {
+    let b = |x: &Expression| Box::new(expr(x));
+    let unary = |op, x: &Expression| InstrExpr::Unary(op, b(x));
+    let binary = |op, l: &Expression, r: &Expression| InstrExpr::Binary(op, b(l), b(r));
+    match x {
+        Expression::ScopeRef { index, .. } => InstrExpr::Scope(*index),
+        Expression::DataField { name, .. } => InstrExpr::Data(name.to_string()),
+        Expression::ToStringWithoutUndefined { value, .. } => InstrExpr::ToStr(b(value)),
+        Expression::LitUndefined { .. } => InstrExpr::Undefined,
+        Expression::LitNull { .. } => InstrExpr::Null,
+        Expression::LitStr { value, .. } => InstrExpr::Str(value.to_string()),
+        Expression::LitInt { value, .. } => InstrExpr::Number(*value as f64),
+        Expression::LitFloat { value, .. } => InstrExpr::Number(*value),
+        Expression::LitBool { value, .. } => InstrExpr::Bool(*value),
+        Expression::LitObj { fields, .. } => InstrExpr::Obj(
+            fields
+                .iter()
+                .map(|f| match f {
+                    ObjectFieldKind::Named { name, value, .. } => {
+                        InstrObjField::Named(name.to_string(), expr(value))
+                    }
+                    ObjectFieldKind::Spread { value, .. } => InstrObjField::Spread(expr(value)),
+                })
+                .collect(),
+        ),
+        Expression::LitArr { fields, .. } => InstrExpr::Arr(
+            fields
+                .iter()
+                .map(|f| match f {
+                    ArrayFieldKind::Normal { value } => InstrArrField::Normal(expr(value)),
+                    ArrayFieldKind::Spread { value, .. } => InstrArrField::Spread(expr(value)),
+                    ArrayFieldKind::EmptySlot => InstrArrField::Empty,
+                })
+                .collect(),
+        ),
+        Expression::LitTemplateStr { parts, .. } => InstrExpr::Concat(
+            parts
+                .iter()
+                .map(|part| match part {
+                    TemplateStrPart::Static { value, .. } => InstrExpr::Str(value.to_string()),
+                    TemplateStrPart::Dynamic { value, .. } => expr(value),
+                })
+                .collect(),
+        ),
+        Expression::StaticMember {
+            obj, field_name, ..
+        }
+        | Expression::OptionalStaticMember {
+            obj, field_name, ..
+        } => InstrExpr::Member(b(obj), Box::new(InstrExpr::Str(field_name.to_string()))),
+        Expression::DynamicMember {
+            obj, field_name, ..
+        }
+        | Expression::OptionalDynamicMember {
+            obj, field_name, ..
+        } => InstrExpr::Member(b(obj), b(field_name)),
+        Expression::FuncCall { func, args, .. }
+        | Expression::OptionalFuncCall { func, args, .. } => {
+            InstrExpr::Call(b(func), args.iter().map(expr).collect())
+        }
+        Expression::Reverse { value, .. } => unary(InstrUnaryOp::Not, value),
+        Expression::BitReverse { value, .. } => unary(InstrUnaryOp::BitNot, value),
+        Expression::Positive { value, .. } => unary(InstrUnaryOp::Positive, value),
+        Expression::Negative { value, .. } => unary(InstrUnaryOp::Negative, value),
+        Expression::TypeOf { value, .. } => unary(InstrUnaryOp::TypeOf, value),
+        Expression::Void { value, .. } => unary(InstrUnaryOp::Void, value),
+        Expression::Multiply { left, right, .. } => binary(InstrBinaryOp::Multiply, left, right),
+        Expression::Divide { left, right, .. } => binary(InstrBinaryOp::Divide, left, right),
+        Expression::Remainer { left, right, .. } => binary(InstrBinaryOp::Remainder, left, right),
+        Expression::Plus { left, right, .. } => binary(InstrBinaryOp::Plus, left, right),
+        Expression::Minus { left, right, .. } => binary(InstrBinaryOp::Minus, left, right),
+        Expression::LeftShift { left, right, .. } => binary(InstrBinaryOp::LeftShift, left, right),
+        Expression::RightShift { left, right, .. } => {
+            binary(InstrBinaryOp::RightShift, left, right)
+        }
+        Expression::UnsignedRightShift { left, right, .. } => {
+            binary(InstrBinaryOp::UnsignedRightShift, left, right)
+        }
+        Expression::Lt { left, right, .. } => binary(InstrBinaryOp::Lt, left, right),
+        Expression::Gt { left, right, .. } => binary(InstrBinaryOp::Gt, left, right),
+        Expression::Lte { left, right, .. } => binary(InstrBinaryOp::Lte, left, right),
+        Expression::Gte { left, right, .. } => binary(InstrBinaryOp::Gte, left, right),
+        Expression::InstanceOf { left, right, .. } => {
+            binary(InstrBinaryOp::InstanceOf, left, right)
+        }
+        Expression::Eq { left, right, .. } => binary(InstrBinaryOp::Eq, left, right),
+        Expression::Ne { left, right, .. } => binary(InstrBinaryOp::Ne, left, right),
+        Expression::EqFull { left, right, .. } => binary(InstrBinaryOp::EqFull, left, right),
+        Expression::NeFull { left, right, .. } => binary(InstrBinaryOp::NeFull, left, right),
+        Expression::BitAnd { left, right, .. } => binary(InstrBinaryOp::BitAnd, left, right),
+        Expression::BitXor { left, right, .. } => binary(InstrBinaryOp::BitXor, left, right),
+        Expression::BitOr { left, right, .. } => binary(InstrBinaryOp::BitOr, left, right),
+        Expression::LogicAnd { left, right, .. } => binary(InstrBinaryOp::LogicAnd, left, right),
+        Expression::LogicOr { left, right, .. } => binary(InstrBinaryOp::LogicOr, left, right),
+        Expression::NullishCoalescing { left, right, .. } => {
+            binary(InstrBinaryOp::NullishCoalescing, left, right)
+        }
+        Expression::Cond {
+            cond,
+            true_br,
+            false_br,
+            ..
+        } => InstrExpr::Cond(b(cond), b(true_br), b(false_br)),
+    }
+}
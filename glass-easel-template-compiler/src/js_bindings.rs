@@ -2,12 +2,13 @@
 
 #![cfg(feature = "js_bindings")]
 
+use std::collections::BTreeMap;
 use std::str;
 
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
-use self::parse::{ParseError, ParseErrorLevel};
+use self::parse::{ParseError, ParseErrorLevel, Position, TextEdit};
 
 use super::*;
 
@@ -41,11 +42,175 @@ impl From<ParseError> for TemplateParseError {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateTextEdit {
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+    new_text: String,
+}
+
+impl From<TemplateTextEdit> for TextEdit {
+    fn from(value: TemplateTextEdit) -> Self {
+        let start = Position {
+            line: value.start_line,
+            utf16_col: value.start_column,
+        };
+        let end = Position {
+            line: value.end_line,
+            utf16_col: value.end_column,
+        };
+        Self {
+            range: start..end,
+            new_text: value.new_text,
+        }
+    }
+}
+
+impl From<TextEdit> for TemplateTextEdit {
+    fn from(value: TextEdit) -> Self {
+        Self {
+            start_line: value.range.start.line,
+            start_column: value.range.start.utf16_col,
+            end_line: value.range.end.line,
+            end_column: value.range.end.utf16_col,
+            new_text: value.new_text,
+        }
+    }
+}
+
 #[wasm_bindgen]
 pub struct TmplGroup {
     group: crate::TmplGroup,
 }
 
+#[derive(Serialize)]
+pub struct TemplateTreeShakingItem {
+    path: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+pub struct TemplateTreeShakingReport {
+    templates: Vec<TemplateTreeShakingItem>,
+    scripts: Vec<TemplateTreeShakingItem>,
+}
+
+impl From<crate::TreeShakingReport> for TemplateTreeShakingReport {
+    fn from(value: crate::TreeShakingReport) -> Self {
+        let convert = |list: Vec<crate::TreeShakingItem>| {
+            list.into_iter()
+                .map(|x| TemplateTreeShakingItem {
+                    path: x.path,
+                    name: x.name,
+                })
+                .collect()
+        };
+        Self {
+            templates: convert(value.templates),
+            scripts: convert(value.scripts),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateDataBinding {
+    sub_template: Option<String>,
+    name: String,
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+    data_paths: Vec<String>,
+    binding_map: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateDataDependencyReport {
+    path: String,
+    data_paths: Vec<String>,
+    includes: Vec<String>,
+    binding_map_disabled: bool,
+    full_update_fields: Vec<String>,
+    bindings: Vec<TemplateDataBinding>,
+}
+
+impl From<crate::DataDependencyReport> for TemplateDataDependencyReport {
+    fn from(value: crate::DataDependencyReport) -> Self {
+        let bindings = value
+            .bindings
+            .into_iter()
+            .map(|x| TemplateDataBinding {
+                sub_template: x.sub_template,
+                name: x.name,
+                start_line: x.location.start.line,
+                start_column: x.location.start.utf16_col,
+                end_line: x.location.end.line,
+                end_column: x.location.end.utf16_col,
+                data_paths: x.data_paths,
+                binding_map: x.binding_map,
+            })
+            .collect();
+        Self {
+            path: value.path,
+            data_paths: value.data_paths,
+            includes: value.includes,
+            binding_map_disabled: value.binding_map_disabled,
+            full_update_fields: value.full_update_fields,
+            bindings,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateLocation {
+    path: String,
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+}
+
+impl From<crate::TmplLocation> for TemplateLocation {
+    fn from(value: crate::TmplLocation) -> Self {
+        Self {
+            path: value.path,
+            start_line: value.location.start.line,
+            start_column: value.location.start.utf16_col,
+            end_line: value.location.end.line,
+            end_column: value.location.end.utf16_col,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateCompletionItem {
+    label: String,
+    kind: &'static str,
+}
+
+impl From<crate::CompletionItem> for TemplateCompletionItem {
+    fn from(value: crate::CompletionItem) -> Self {
+        let kind = match value.kind {
+            crate::CompletionKind::Module => "module",
+            crate::CompletionKind::Variable => "variable",
+            crate::CompletionKind::Template => "template",
+            crate::CompletionKind::AttributePrefix => "attributePrefix",
+            crate::CompletionKind::Path => "path",
+        };
+        Self {
+            label: value.label,
+            kind,
+        }
+    }
+}
+
 fn convert_str_arr<T: ToString>(arr: impl Iterator<Item = T>) -> js_sys::Array {
     let ret = js_sys::Array::new();
     for (index, item) in arr.enumerate() {
@@ -54,6 +219,63 @@ fn convert_str_arr<T: ToString>(arr: impl Iterator<Item = T>) -> js_sys::Array {
     ret
 }
 
+fn code_with_source_map(
+    code: String,
+    sm: crate::stringify::SourceMap,
+) -> Result<js_sys::Array, JsError> {
+    let mut sm_json = vec![];
+    sm.to_writer(&mut sm_json)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    let ret = js_sys::Array::new();
+    ret.push(&JsValue::from(code));
+    ret.push(&JsValue::from(
+        String::from_utf8_lossy(&sm_json).into_owned(),
+    ));
+    Ok(ret)
+}
+
+struct JsSsrHost {
+    host: Option<js_sys::Object>,
+}
+
+impl JsSsrHost {
+    fn call(&self, method: &str, args: &js_sys::Array) -> Option<JsValue> {
+        let host = self.host.as_ref()?;
+        let f = js_sys::Reflect::get(host, &JsValue::from(method)).ok()?;
+        let f = f.dyn_ref::<js_sys::Function>()?;
+        f.apply(host, args).ok()
+    }
+}
+
+impl crate::ssr::SsrHost for JsSsrHost {
+    fn call_script(
+        &mut self,
+        module: &str,
+        func: &str,
+        args: Vec<serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        let js_args = js_sys::Array::new();
+        js_args.push(&JsValue::from(module));
+        js_args.push(&JsValue::from(func));
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        js_args.push(&args.serialize(&serializer).ok()?);
+        let ret = self.call("callScript", &js_args)?;
+        serde_wasm_bindgen::from_value(ret).ok()
+    }
+
+    fn render_slot(
+        &mut self,
+        name: &str,
+        values: &serde_json::Map<String, serde_json::Value>,
+    ) -> Option<String> {
+        let js_args = js_sys::Array::new();
+        js_args.push(&JsValue::from(name));
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        js_args.push(&values.serialize(&serializer).ok()?);
+        self.call("renderSlot", &js_args)?.as_string()
+    }
+}
+
 #[wasm_bindgen]
 impl TmplGroup {
     #[wasm_bindgen(constructor)]
@@ -86,6 +308,22 @@ impl TmplGroup {
         serde_wasm_bindgen::to_value(&ret).unwrap()
     }
 
+    /// Apply text edits to a template added by `addTmpl` .
+    ///
+    /// Each edit is an object with `startLine` `startColumn` `endLine` `endColumn` and `newText` ,
+    /// and its range is based on the template content with all previous edits applied.
+    /// Returns all warnings and errors of the edited template like `addTmpl` .
+    ///
+    #[wasm_bindgen(js_name = updateTmpl)]
+    pub fn update_tmpl(&mut self, path: &str, edits: JsValue) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let edits: Vec<TemplateTextEdit> = serde_wasm_bindgen::from_value(edits)?;
+        let edits: Vec<TextEdit> = edits.into_iter().map(|x| x.into()).collect();
+        let errors = self.group.update_tmpl(&path, &edits)?;
+        let ret: Vec<_> = errors.into_iter().map(TemplateParseError::from).collect();
+        Ok(serde_wasm_bindgen::to_value(&ret).unwrap())
+    }
+
     #[wasm_bindgen(js_name = removeTmpl)]
     pub fn remove_tmpl(&mut self, path: &str) -> bool {
         let path = crate::path::normalize(path);
@@ -123,6 +361,33 @@ impl TmplGroup {
         Ok(convert_str_arr(dependencies))
     }
 
+    #[wasm_bindgen(js_name = "getTransitiveDependencies")]
+    pub fn get_transitive_dependencies(&self, path: &str) -> Result<js_sys::Array, JsError> {
+        let dependencies = self.group.transitive_dependencies(&path)?;
+        Ok(convert_str_arr(dependencies.into_iter()))
+    }
+
+    #[wasm_bindgen(js_name = "getTransitiveScriptDependencies")]
+    pub fn get_transitive_script_dependencies(&self, path: &str) -> Result<js_sys::Array, JsError> {
+        let dependencies = self.group.transitive_script_dependencies(&path)?;
+        Ok(convert_str_arr(dependencies.into_iter()))
+    }
+
+    #[wasm_bindgen(js_name = "getDependents")]
+    pub fn get_dependents(&self, path: &str) -> js_sys::Array {
+        convert_str_arr(self.group.dependents(path).into_iter())
+    }
+
+    #[wasm_bindgen(js_name = "getScriptDependents")]
+    pub fn get_script_dependents(&self, path: &str) -> js_sys::Array {
+        convert_str_arr(self.group.script_dependents(path).into_iter())
+    }
+
+    #[wasm_bindgen(js_name = "getTopologicalOrder")]
+    pub fn get_topological_order(&self) -> js_sys::Array {
+        convert_str_arr(self.group.topological_order().into_iter())
+    }
+
     #[wasm_bindgen(js_name = "getInlineScriptModuleNames")]
     pub fn get_inline_script_module_names(&self, path: &str) -> Result<js_sys::Array, JsError> {
         let names = self.group.inline_script_module_names(path)?;
@@ -167,6 +432,142 @@ impl TmplGroup {
         self.group.set_extra_runtime_script(s)
     }
 
+    /// Enable or disable tree-shaking of unused sub-templates and `<wxs>` modules.
+    #[wasm_bindgen(js_name = "setTreeShaking")]
+    pub fn set_tree_shaking(&mut self, enabled: bool) {
+        self.group.set_tree_shaking(enabled)
+    }
+
+    /// Get the sub-templates and `<wxs>` modules which are never used.
+    ///
+    /// Returns an object with `templates` and `scripts` arrays, each item containing `path` and `name` .
+    ///
+    #[wasm_bindgen(js_name = "getTreeShakingReport")]
+    pub fn get_tree_shaking_report(&self) -> JsValue {
+        let ret = TemplateTreeShakingReport::from(self.group.tree_shaking_report());
+        serde_wasm_bindgen::to_value(&ret).unwrap()
+    }
+
+    /// Get the data paths read by a template.
+    ///
+    /// Returns an object with `path` `dataPaths` `includes` `bindingMapDisabled` `fullUpdateFields` and `bindings` ,
+    /// in which each binding contains `subTemplate` `name` `dataPaths` `bindingMap` and its location.
+    ///
+    #[wasm_bindgen(js_name = "getDataDependencyReport")]
+    pub fn get_data_dependency_report(&self, path: &str) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let ret = TemplateDataDependencyReport::from(self.group.data_dependency_report(&path)?);
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Find the definition of the symbol at the position of a template.
+    ///
+    /// Returns an object with `path` `startLine` `startColumn` `endLine` `endColumn` , or `undefined` if not found.
+    ///
+    #[wasm_bindgen(js_name = "findDefinition")]
+    pub fn find_definition(&self, path: &str, line: u32, column: u32) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let pos = Position {
+            line,
+            utf16_col: column,
+        };
+        let ret = self
+            .group
+            .find_definition(&path, pos)?
+            .map(TemplateLocation::from);
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Find all references of the symbol at the position, including its definition.
+    ///
+    /// Returns an array of objects with `path` `startLine` `startColumn` `endLine` `endColumn` .
+    ///
+    #[wasm_bindgen(js_name = "findReferences")]
+    pub fn find_references(&self, path: &str, line: u32, column: u32) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let pos = Position {
+            line,
+            utf16_col: column,
+        };
+        let ret: Vec<_> = self
+            .group
+            .find_references(&path, pos)?
+            .into_iter()
+            .map(TemplateLocation::from)
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Rename the symbol at the position.
+    ///
+    /// Returns an object mapping template paths to the text edits, which can be applied with `updateTmpl` .
+    ///
+    #[wasm_bindgen(js_name = "renameSymbol")]
+    pub fn rename_symbol(
+        &self,
+        path: &str,
+        line: u32,
+        column: u32,
+        new_name: &str,
+    ) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let pos = Position {
+            line,
+            utf16_col: column,
+        };
+        let ret: BTreeMap<String, Vec<TemplateTextEdit>> = self
+            .group
+            .rename_symbol(&path, pos, new_name)?
+            .into_iter()
+            .map(|(k, v)| (k, v.into_iter().map(TemplateTextEdit::from).collect()))
+            .collect();
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        Ok(ret.serialize(&serializer)?)
+    }
+
+    /// List the completion candidates at the position.
+    ///
+    /// Returns an array of objects with `label` and `kind` ,
+    /// in which `kind` is one of `module` `variable` `template` `attributePrefix` `path` .
+    ///
+    #[wasm_bindgen(js_name = "getCompletions")]
+    pub fn get_completions(&self, path: &str, line: u32, column: u32) -> Result<JsValue, JsError> {
+        let path = crate::path::normalize(path);
+        let pos = Position {
+            line,
+            utf16_col: column,
+        };
+        let ret: Vec<_> = self
+            .group
+            .completions(&path, pos)?
+            .into_iter()
+            .map(TemplateCompletionItem::from)
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&ret)?)
+    }
+
+    /// Classify the tokens of a template in the LSP semantic tokens encoding.
+    ///
+    /// The token types and modifiers are listed by `getSemanticTokensLegend` .
+    ///
+    #[wasm_bindgen(js_name = "getSemanticTokens")]
+    pub fn get_semantic_tokens(&self, path: &str) -> Result<Vec<u32>, JsError> {
+        let path = crate::path::normalize(path);
+        let tokens = self.group.semantic_tokens(&path)?;
+        Ok(crate::SemanticToken::encode(&tokens))
+    }
+
+    /// Get the legend of `getSemanticTokens` , i.e. an object with `tokenTypes` and `tokenModifiers` .
+    #[wasm_bindgen(js_name = "getSemanticTokensLegend")]
+    pub fn get_semantic_tokens_legend() -> JsValue {
+        let ret = js_sys::Object::new();
+        let token_types = convert_str_arr(crate::SemanticTokenType::LEGEND.iter());
+        let token_modifiers = convert_str_arr(crate::SemanticToken::MODIFIERS_LEGEND.iter());
+        js_sys::Reflect::set(&ret, &JsValue::from("tokenTypes"), &token_types).unwrap();
+        js_sys::Reflect::set(&ret, &JsValue::from("tokenModifiers"), &token_modifiers).unwrap();
+        ret.into()
+    }
+
     #[wasm_bindgen(js_name = "getRuntimeVarList")]
     pub fn get_runtime_var_list() -> String {
         crate::TmplGroup::get_runtime_var_list().join(",")
@@ -187,6 +588,87 @@ impl TmplGroup {
         Ok(self.group.get_wx_gen_object_groups()?)
     }
 
+    /// Generate the GenObject of a template with a source map.
+    ///
+    /// Returns an JavaScript array `[code, sourceMapJson]` .
+    #[wasm_bindgen(js_name = "getTmplGenObjectWithSourceMap")]
+    pub fn get_tmpl_gen_object_with_source_map(
+        &self,
+        path: &str,
+    ) -> Result<js_sys::Array, JsError> {
+        let (code, sm) = self.group.get_tmpl_gen_object_with_source_map(path)?;
+        code_with_source_map(code, sm)
+    }
+
+    /// Generate the GenObject groups with a source map.
+    ///
+    /// Returns an JavaScript array `[code, sourceMapJson]` .
+    #[wasm_bindgen(js_name = "getTmplGenObjectGroupsWithSourceMap")]
+    pub fn get_tmpl_gen_object_groups_with_source_map(&self) -> Result<js_sys::Array, JsError> {
+        let (code, sm) = self.group.get_tmpl_gen_object_groups_with_source_map()?;
+        code_with_source_map(code, sm)
+    }
+
+    /// Generate the GenObject groups for wx environment with a source map.
+    ///
+    /// Returns an JavaScript array `[code, sourceMapJson]` .
+    #[wasm_bindgen(js_name = "getWxGenObjectGroupsWithSourceMap")]
+    pub fn get_wx_gen_object_groups_with_source_map(&self) -> Result<js_sys::Array, JsError> {
+        let (code, sm) = self.group.get_wx_gen_object_groups_with_source_map()?;
+        code_with_source_map(code, sm)
+    }
+
+    /// Render a template to an HTML string with the given data.
+    ///
+    /// The optional `host` object can have a `callScript(module, func, args)` method for script calls,
+    /// and a `renderSlot(name, values)` method returning the HTML content of a slot.
+    #[wasm_bindgen(js_name = "renderHtml")]
+    pub fn render_html(
+        &self,
+        path: &str,
+        data: JsValue,
+        host: Option<js_sys::Object>,
+    ) -> Result<String, JsError> {
+        let path = crate::path::normalize(path);
+        let data: serde_json::Value = serde_wasm_bindgen::from_value(data)?;
+        let mut host = JsSsrHost { host };
+        Ok(self.group.render_html_with_host(&path, &data, &mut host)?)
+    }
+
+    /// Compile a template into a JSON instruction list, which can be executed without `eval` .
+    #[wasm_bindgen(js_name = "getTmplInstructions")]
+    pub fn get_tmpl_instructions(&self, path: &str) -> Result<String, JsError> {
+        let path = crate::path::normalize(path);
+        let ret = self.group.get_tmpl_instructions(&path)?;
+        Ok(serde_json::to_string(&ret)?)
+    }
+
+    /// Generate all modules in the group.
+    ///
+    /// `format` should be `esm` or `cjs` .
+    /// Returns an JavaScript array, and each item is a `[moduleFileName, moduleContent]` pair.
+    #[wasm_bindgen(js_name = "getAllModules")]
+    pub fn get_all_modules(&self, format: &str) -> Result<js_sys::Array, JsError> {
+        let format = match format {
+            "esm" => crate::ModuleFormat::EsModule,
+            "cjs" => crate::ModuleFormat::CommonJs,
+            _ => {
+                return Err(JsError::new(&format!(
+                    r#"unknown module format "{}""#,
+                    format
+                )))
+            }
+        };
+        let ret = js_sys::Array::new();
+        for (name, content) in self.group.get_all_modules(format)? {
+            let item = js_sys::Array::new();
+            item.push(&JsValue::from(name));
+            item.push(&JsValue::from(content));
+            ret.push(&item);
+        }
+        Ok(ret)
+    }
+
     #[wasm_bindgen(js_name = "exportGlobals")]
     pub fn export_globals(&self) -> Result<String, JsError> {
         Ok(self.group.export_globals()?)
@@ -7,14 +7,28 @@ extern crate log;
 extern crate lazy_static;
 
 mod binding_map;
+pub mod eval;
 mod group;
+pub mod instr;
+pub mod lsp;
 pub mod parse;
+pub mod ssr;
 pub mod stringify;
+pub use completion::{CompletionItem, CompletionKind};
+pub use data_deps::{DataBinding, DataDependencyReport};
 pub use group::*;
+pub use navigation::TmplLocation;
+pub use semantic_tokens::{SemanticToken, SemanticTokenType};
+pub use tree_shaking::{TreeShakingItem, TreeShakingReport};
 #[cfg(feature = "c_bindings")]
 pub mod cbinding;
+mod completion;
+mod data_deps;
 mod entities;
 mod escape;
 mod js_bindings;
+mod navigation;
 mod path;
 mod proc_gen;
+mod semantic_tokens;
+mod tree_shaking;
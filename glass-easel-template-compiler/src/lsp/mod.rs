@@ -0,0 +1,455 @@
+//! A language server for WXML files.
+//!
+//! The server speaks JSON-RPC with the `Content-Length` framing, usually over stdio.
+//! It keeps all `.wxml` and `.wxs` files of the workspace in a `TmplGroup` ,
+//! and publishes the parsing errors of the opened documents as diagnostics.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::group::TmplGroup;
+use crate::navigation::TmplLocation;
+use crate::parse::{ParseError, Position, TextEdit};
+use crate::{SemanticToken, SemanticTokenType};
+
+mod protocol;
+
+use protocol::*;
+pub use protocol::{read_message, write_message};
+
+/// The language server state.
+pub struct Server {
+    group: TmplGroup,
+    root: Option<PathBuf>,
+    /// The opened documents, mapping URIs to template paths.
+    documents: HashMap<String, String>,
+    initialized: bool,
+    shutdown: bool,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    /// Create a server without a workspace.
+    pub fn new() -> Self {
+        Self {
+            group: TmplGroup::new(),
+            root: None,
+            documents: HashMap::new(),
+            initialized: false,
+            shutdown: false,
+        }
+    }
+
+    /// Get the template group containing the workspace files.
+    pub fn group(&self) -> &TmplGroup {
+        &self.group
+    }
+
+    /// Serve until the `exit` notification or the end of the input.
+    ///
+    /// Returns the process exit code, which is `0` only if `shutdown` is requested before `exit` .
+    pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+        while let Some(buf) = read_message(input)? {
+            let msg: Value = match serde_json::from_slice(&buf) {
+                Ok(x) => x,
+                Err(err) => {
+                    let resp = error_response(Value::Null, PARSE_ERROR, &err.to_string());
+                    write_message(output, &resp)?;
+                    continue;
+                }
+            };
+            if msg.get("method").and_then(|x| x.as_str()) == Some("exit") {
+                return Ok(if self.shutdown { 0 } else { 1 });
+            }
+            for out in self.handle(&msg) {
+                write_message(output, &out)?;
+            }
+        }
+        Ok(1)
+    }
+
+    /// Handle an incoming message, returning the outgoing messages.
+    pub fn handle(&mut self, msg: &Value) -> Vec<Value> {
+        let Some(method) = msg.get("method").and_then(|x| x.as_str()) else {
+            // responses from the client are not used
+            return vec![];
+        };
+        let params = msg.get("params").unwrap_or(&Value::Null);
+        match msg.get("id").cloned() {
+            Some(id) => vec![self.handle_request(id, method, params)],
+            None => self.handle_notification(method, params),
+        }
+    }
+
+    fn handle_request(&mut self, id: Value, method: &str, params: &Value) -> Value {
+        if self.shutdown {
+            return error_response(id, INVALID_REQUEST, "the server is shutting down");
+        }
+        if method == "initialize" {
+            return response(id, self.initialize(params));
+        }
+        if !self.initialized {
+            return error_response(id, SERVER_NOT_INITIALIZED, "the server is not initialized");
+        }
+        let ret = match method {
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/rename" => self.rename(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method {:?}", method))),
+        };
+        match ret {
+            Ok(result) => response(id, result),
+            Err((code, message)) => error_response(id, code, &message),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        if !self.initialized {
+            return vec![];
+        }
+        match method {
+            "textDocument/didOpen" => self.did_open(params),
+            "textDocument/didChange" => self.did_change(params),
+            "textDocument/didClose" => self.did_close(params),
+            "workspace/didChangeWatchedFiles" => self.did_change_watched_files(params),
+            _ => vec![],
+        }
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        let root_uri = params
+            .get("workspaceFolders")
+            .and_then(|x| x.get(0))
+            .and_then(|x| x.get("uri"))
+            .or_else(|| params.get("rootUri"))
+            .and_then(|x| x.as_str());
+        self.root = root_uri
+            .and_then(uri_to_file_path)
+            .or_else(|| {
+                params
+                    .get("rootPath")
+                    .and_then(|x| x.as_str())
+                    .map(|x| x.to_string())
+            })
+            .map(PathBuf::from);
+        if let Some(root) = self.root.clone() {
+            self.load_dir(&root, &mut vec![]);
+        }
+        self.initialized = true;
+        json!({
+            "capabilities": {
+                "textDocumentSync": {
+                    "openClose": true,
+                    "change": 2,
+                },
+                "definitionProvider": true,
+                "referencesProvider": true,
+                "renameProvider": true,
+                "semanticTokensProvider": {
+                    "legend": {
+                        "tokenTypes": SemanticTokenType::LEGEND,
+                        "tokenModifiers": SemanticToken::MODIFIERS_LEGEND,
+                    },
+                    "full": true,
+                },
+                "completionProvider": {
+                    "triggerCharacters": ["{", "\"", "/", " "],
+                },
+            },
+            "serverInfo": {
+                "name": "glass-easel-wxml-lsp",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    fn load_dir(&mut self, dir: &Path, rel: &mut Vec<String>) {
+        let Ok(list) = fs::read_dir(dir) else {
+            warn!("List dir failed: {}", dir.to_str().unwrap_or(""));
+            return;
+        };
+        for entry in list.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            if path.is_dir() {
+                if name.starts_with('.') || name == "node_modules" {
+                    continue;
+                }
+                rel.push(name);
+                self.load_dir(&path, rel);
+                rel.pop();
+            } else if let Some(stem) = name.strip_suffix(".wxml") {
+                rel.push(stem.to_string());
+                let tmpl_path = rel.join("/");
+                rel.pop();
+                self.load_file(&path, &tmpl_path, false);
+            } else if let Some(stem) = name.strip_suffix(".wxs") {
+                rel.push(stem.to_string());
+                let script_path = rel.join("/");
+                rel.pop();
+                self.load_file(&path, &script_path, true);
+            }
+        }
+    }
+
+    /// Load a file from the file system, or remove it if it does not exist.
+    fn load_file(&mut self, path: &Path, group_path: &str, is_script: bool) {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                if is_script {
+                    self.group.add_script(group_path, &content);
+                } else {
+                    self.group.add_tmpl(group_path, &content);
+                }
+            }
+            Err(_) => {
+                if is_script {
+                    self.group.remove_script(group_path);
+                } else {
+                    self.group.remove_tmpl(group_path);
+                }
+            }
+        }
+    }
+
+    /// Get the group path of a URI, and whether it is a script.
+    fn group_path(&self, uri: &str) -> Option<(String, bool)> {
+        let file_path = uri_to_file_path(uri)?;
+        let (stem, is_script) = if let Some(x) = file_path.strip_suffix(".wxml") {
+            (x, false)
+        } else if let Some(x) = file_path.strip_suffix(".wxs") {
+            (x, true)
+        } else {
+            return None;
+        };
+        let rel = self
+            .root
+            .as_ref()
+            .and_then(|root| Path::new(stem).strip_prefix(root).ok())
+            .map(|x| x.to_string_lossy().replace('\\', "/"));
+        Some((rel.unwrap_or_else(|| stem.to_string()), is_script))
+    }
+
+    /// Get the URI of a template path.
+    fn template_uri(&self, tmpl_path: &str) -> String {
+        let file_name = format!("{}.wxml", tmpl_path);
+        match self.root.as_ref() {
+            Some(root) if !tmpl_path.starts_with('/') => {
+                file_path_to_uri(&root.join(file_name).to_string_lossy())
+            }
+            _ => file_path_to_uri(&file_name),
+        }
+    }
+
+    /// Get the template path and the position of a `TextDocumentPositionParams` .
+    fn document_position(&self, params: &Value) -> Result<(String, Position), (i64, String)> {
+        let invalid = || (INVALID_PARAMS, "invalid text document position".to_string());
+        let uri = params["textDocument"]["uri"].as_str().ok_or_else(invalid)?;
+        let pos = position_from_json(&params["position"]).ok_or_else(invalid)?;
+        match self.group_path(uri) {
+            Some((path, false)) if self.group.contains_template(&path) => Ok((path, pos)),
+            _ => Err((INVALID_PARAMS, format!("unknown template {:?}", uri))),
+        }
+    }
+
+    fn location_to_json(&self, loc: &TmplLocation) -> Value {
+        json!({
+            "uri": self.template_uri(&loc.path),
+            "range": range_to_json(&loc.location),
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (path, pos) = self.document_position(params)?;
+        let ret = self
+            .group
+            .find_definition(&path, pos)
+            .map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+        Ok(match ret {
+            Some(loc) => self.location_to_json(&loc),
+            None => Value::Null,
+        })
+    }
+
+    fn references(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (path, pos) = self.document_position(params)?;
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        let mut list = self
+            .group
+            .find_references(&path, pos)
+            .map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+        if !include_declaration {
+            if let Ok(Some(def)) = self.group.find_definition(&path, pos) {
+                list.retain(|x| x != &def);
+            }
+        }
+        Ok(list.iter().map(|x| self.location_to_json(x)).collect())
+    }
+
+    fn rename(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (path, pos) = self.document_position(params)?;
+        let Some(new_name) = params["newName"].as_str() else {
+            return Err((INVALID_PARAMS, "missing the new name".to_string()));
+        };
+        let edits = self
+            .group
+            .rename_symbol(&path, pos, new_name)
+            .map_err(|err| (REQUEST_FAILED, err.to_string()))?;
+        let mut changes = serde_json::Map::new();
+        for (path, list) in edits {
+            let list: Vec<_> = list
+                .iter()
+                .map(|x| json!({ "range": range_to_json(&x.range), "newText": x.new_text }))
+                .collect();
+            changes.insert(self.template_uri(&path), Value::Array(list));
+        }
+        Ok(json!({ "changes": changes }))
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (path, pos) = self.document_position(params)?;
+        let list = self
+            .group
+            .completions(&path, pos)
+            .map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+        Ok(list.iter().map(completion_item).collect())
+    }
+
+    fn semantic_tokens(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let path = match self.group_path(uri) {
+            Some((path, false)) if self.group.contains_template(&path) => path,
+            _ => return Err((INVALID_PARAMS, format!("unknown template {:?}", uri))),
+        };
+        let tokens = self
+            .group
+            .semantic_tokens(&path)
+            .map_err(|err| (REQUEST_FAILED, err.to_string()))?;
+        Ok(json!({ "data": SemanticToken::encode(&tokens) }))
+    }
+
+    fn publish_diagnostics(&self, uri: &str, errors: &[ParseError]) -> Value {
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({
+                "uri": uri,
+                "diagnostics": errors.iter().map(diagnostic).collect::<Vec<_>>(),
+            }),
+        )
+    }
+
+    fn did_open(&mut self, params: &Value) -> Vec<Value> {
+        let doc = &params["textDocument"];
+        let (Some(uri), Some(text)) = (doc["uri"].as_str(), doc["text"].as_str()) else {
+            return vec![];
+        };
+        let Some((path, is_script)) = self.group_path(uri) else {
+            return vec![];
+        };
+        if is_script {
+            self.group.add_script(&path, text);
+            return vec![];
+        }
+        let errors = self.group.add_tmpl(&path, text);
+        self.documents.insert(uri.to_string(), path);
+        vec![self.publish_diagnostics(uri, &errors)]
+    }
+
+    fn did_change(&mut self, params: &Value) -> Vec<Value> {
+        let Some(uri) = params["textDocument"]["uri"].as_str() else {
+            return vec![];
+        };
+        let Some(changes) = params["contentChanges"].as_array() else {
+            return vec![];
+        };
+        let Some((path, is_script)) = self.group_path(uri) else {
+            return vec![];
+        };
+        if is_script {
+            // scripts are always synced as a whole
+            if let Some(text) = changes.last().and_then(|x| x["text"].as_str()) {
+                self.group.add_script(&path, text);
+            }
+            return vec![];
+        }
+
+        // apply ranged edits incrementally, and replace the whole text otherwise
+        let mut errors = vec![];
+        let mut edits: Vec<TextEdit> = vec![];
+        for change in changes {
+            let text = change["text"].as_str().unwrap_or_default().to_string();
+            match change.get("range").and_then(range_from_json) {
+                Some(range) => edits.push(TextEdit {
+                    range,
+                    new_text: text,
+                }),
+                None => {
+                    edits.clear();
+                    errors = self.group.add_tmpl(&path, &text);
+                }
+            }
+        }
+        if !edits.is_empty() {
+            match self.group.update_tmpl(&path, &edits) {
+                Ok(x) => errors = x,
+                Err(err) => {
+                    warn!("{}", err);
+                    return vec![];
+                }
+            }
+        }
+        vec![self.publish_diagnostics(uri, &errors)]
+    }
+
+    fn did_close(&mut self, params: &Value) -> Vec<Value> {
+        let Some(uri) = params["textDocument"]["uri"].as_str() else {
+            return vec![];
+        };
+        let Some(path) = self.documents.remove(uri) else {
+            return vec![];
+        };
+        // drop the unsaved changes
+        if let Some(file_path) = uri_to_file_path(uri) {
+            self.load_file(Path::new(&file_path), &path, false);
+        }
+        vec![self.publish_diagnostics(uri, &[])]
+    }
+
+    fn did_change_watched_files(&mut self, params: &Value) -> Vec<Value> {
+        let Some(changes) = params["changes"].as_array() else {
+            return vec![];
+        };
+        for change in changes {
+            let Some(uri) = change["uri"].as_str() else {
+                continue;
+            };
+            if self.documents.contains_key(uri) {
+                continue;
+            }
+            let (Some((path, is_script)), Some(file_path)) =
+                (self.group_path(uri), uri_to_file_path(uri))
+            else {
+                continue;
+            };
+            self.load_file(Path::new(&file_path), &path, is_script);
+        }
+        vec![]
+    }
+}
@@ -0,0 +1,155 @@
+//! The JSON-RPC message framing and the conversions of LSP structures.
+
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use serde_json::{json, Value};
+
+use crate::parse::{ParseError, ParseErrorLevel, Position};
+use crate::{CompletionItem, CompletionKind};
+
+pub(super) const PARSE_ERROR: i64 = -32700;
+pub(super) const INVALID_REQUEST: i64 = -32600;
+pub(super) const METHOD_NOT_FOUND: i64 = -32601;
+pub(super) const INVALID_PARAMS: i64 = -32602;
+pub(super) const SERVER_NOT_INITIALIZED: i64 = -32002;
+pub(super) const REQUEST_FAILED: i64 = -32803;
+
+/// Read a message with the `Content-Length` header.
+///
+/// Returns `None` when the input ends.
+pub fn read_message(r: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut buf = vec![0; content_length.unwrap_or(0)];
+    r.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+/// Write a message with the `Content-Length` header.
+pub fn write_message(w: &mut impl Write, msg: &Value) -> io::Result<()> {
+    let s = msg.to_string();
+    write!(w, "Content-Length: {}\r\n\r\n{}", s.len(), s)?;
+    w.flush()
+}
+
+pub(super) fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+pub(super) fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+pub(super) fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+pub(super) fn position_from_json(v: &Value) -> Option<Position> {
+    Some(Position {
+        line: v.get("line")?.as_u64()? as u32,
+        utf16_col: v.get("character")?.as_u64()? as u32,
+    })
+}
+
+pub(super) fn range_from_json(v: &Value) -> Option<Range<Position>> {
+    Some(position_from_json(v.get("start")?)?..position_from_json(v.get("end")?)?)
+}
+
+pub(super) fn position_to_json(pos: Position) -> Value {
+    json!({ "line": pos.line, "character": pos.utf16_col })
+}
+
+pub(super) fn range_to_json(range: &Range<Position>) -> Value {
+    json!({
+        "start": position_to_json(range.start),
+        "end": position_to_json(range.end),
+    })
+}
+
+pub(super) fn diagnostic(err: &ParseError) -> Value {
+    let severity = match err.level() {
+        ParseErrorLevel::Fatal | ParseErrorLevel::Error => 1,
+        ParseErrorLevel::Warn => 2,
+        ParseErrorLevel::Note => 3,
+    };
+    json!({
+        "range": range_to_json(&err.location),
+        "severity": severity,
+        "code": err.code(),
+        "source": "glass-easel",
+        "message": err.kind.to_string(),
+    })
+}
+
+pub(super) fn completion_item(item: &CompletionItem) -> Value {
+    let kind = match item.kind {
+        CompletionKind::Module => 9,
+        CompletionKind::Variable => 6,
+        CompletionKind::Template => 3,
+        CompletionKind::AttributePrefix => 10,
+        CompletionKind::Path => 17,
+    };
+    json!({ "label": item.label, "kind": kind })
+}
+
+/// Convert a `file:` URI to a file system path.
+pub(super) fn uri_to_file_path(uri: &str) -> Option<String> {
+    let s = uri.strip_prefix("file://")?;
+    let s = s.strip_prefix("localhost").unwrap_or(s);
+    let bytes = s.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
+            ret.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            ret.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let s = String::from_utf8(ret).ok()?;
+    // windows paths look like `/c:/dir`
+    match s.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => Some(s[1..].to_string()),
+        _ => Some(s),
+    }
+}
+
+/// Convert a file system path to a `file:` URI.
+pub(super) fn file_path_to_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut ret = String::from("file://");
+    if !path.starts_with('/') {
+        ret.push('/');
+    }
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                ret.push(b as char)
+            }
+            _ => ret.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    ret
+}
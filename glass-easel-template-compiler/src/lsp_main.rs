@@ -0,0 +1,14 @@
+use glass_easel_template_compiler::lsp::Server;
+
+fn main() {
+    env_logger::init();
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let code = Server::new()
+        .run(&mut stdin.lock(), &mut stdout.lock())
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            1
+        });
+    std::process::exit(code);
+}
@@ -12,15 +12,27 @@ struct CmdArgs {
     input: Option<PathBuf>,
     output: Option<PathBuf>,
     target: TargetType,
+    tree_shaking: bool,
 }
 
 #[derive(Debug)]
 enum TargetType {
     WxGenObject,
     Wxml,
+    #[cfg(feature = "serde")]
+    AstJson,
+    #[cfg(feature = "serde")]
+    InstrJson,
+    #[cfg(feature = "serde")]
+    DataDeps,
 }
 
 fn parse_cmd() -> CmdArgs {
+    let mut targets = vec!["gen-object", "wxml"];
+    // the JSON targets need the `serde` feature
+    if cfg!(feature = "serde") {
+        targets.extend(["ast-json", "instr-json", "data-deps"]);
+    }
     let matches = App::new("The Template Compiler for glass-easel")
         .author("wechat-miniprogram")
         .arg(
@@ -42,9 +54,14 @@ fn parse_cmd() -> CmdArgs {
                 .short("t")
                 .long("target")
                 .takes_value(true)
-                .possible_values(&["gen-object", "wxml"])
+                .possible_values(&targets)
                 .help("Compiling target"),
         )
+        .arg(
+            Arg::with_name("tree-shaking")
+                .long("tree-shaking")
+                .help("Remove unused sub-templates and wxs modules from the output"),
+        )
         .arg(
             Arg::with_name("DIRECTORY")
                 .help("Sets the root directory of WXML files")
@@ -58,15 +75,23 @@ fn parse_cmd() -> CmdArgs {
     let target = match matches.value_of("target").unwrap_or("gen-object") {
         "gen-object" => TargetType::WxGenObject,
         "wxml" => TargetType::Wxml,
+        #[cfg(feature = "serde")]
+        "ast-json" => TargetType::AstJson,
+        #[cfg(feature = "serde")]
+        "instr-json" => TargetType::InstrJson,
+        #[cfg(feature = "serde")]
+        "data-deps" => TargetType::DataDeps,
         _ => unreachable!(),
     };
     let input = matches.value_of("DIRECTORY").map(|x| x.into());
+    let tree_shaking = matches.is_present("tree-shaking");
 
     CmdArgs {
         interactive,
         input,
         output,
         target,
+        tree_shaking,
     }
 }
 
@@ -154,6 +179,12 @@ fn main() {
             &mut vec![],
         )
     };
+    if args.tree_shaking {
+        group.set_tree_shaking(true);
+        for line in group.tree_shaking_report().to_string().lines() {
+            info!("{}", line);
+        }
+    }
     let s = match args.target {
         TargetType::WxGenObject => {
             let s = group.get_wx_gen_object_groups().unwrap();
@@ -173,6 +204,45 @@ fn main() {
             );
             s
         }
+        #[cfg(feature = "serde")]
+        TargetType::AstJson => {
+            let trees: std::collections::BTreeMap<_, _> = group.list_template_trees().collect();
+            let s = serde_json::to_string(&trees).unwrap();
+            trace!(
+                "Generated AST JSON. {} bytes read. {} bytes generated.",
+                size,
+                s.len()
+            );
+            s
+        }
+        #[cfg(feature = "serde")]
+        TargetType::InstrJson => {
+            let list: std::collections::BTreeMap<_, _> = group
+                .list_template_trees()
+                .map(|(path, _)| (path, group.get_tmpl_instructions(path).unwrap()))
+                .collect();
+            let s = serde_json::to_string(&list).unwrap();
+            trace!(
+                "Generated instruction JSON. {} bytes read. {} bytes generated.",
+                size,
+                s.len()
+            );
+            s
+        }
+        #[cfg(feature = "serde")]
+        TargetType::DataDeps => {
+            let list: std::collections::BTreeMap<_, _> = group
+                .list_template_trees()
+                .map(|(path, _)| (path, group.data_dependency_report(path).unwrap()))
+                .collect();
+            let s = serde_json::to_string(&list).unwrap();
+            trace!(
+                "Generated data dependency report. {} bytes read. {} bytes generated.",
+                size,
+                s.len()
+            );
+            s
+        }
     };
     if let Some(output) = args.output {
         fs::write(output, s).unwrap();
@@ -0,0 +1,446 @@
+//! Source navigation queries, e.g. finding the definition of the symbol under the cursor.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::{
+    group::TmplGroup,
+    parse::{
+        expr::Expression,
+        tag::{
+            Element, ElementKind, ImportElement, IncludeElement, StrName, TemplateDefinition, Value,
+        },
+        visit::{self, Visit},
+        visit_scope::{self, Scope, ScopeVisit},
+        Position, Template, TextEdit,
+    },
+    TmplError,
+};
+
+/// A range in a template file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TmplLocation {
+    /// The template path.
+    pub path: String,
+    pub location: Range<Position>,
+}
+
+/// What a name in the source refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SymbolKey {
+    /// A scope variable, identified by the location and the name of its declaration in the same file.
+    ///
+    /// The default `wx:for-item` and `wx:for-index` share the location of `wx:for` .
+    Scope(Range<Position>, String),
+    /// A sub-template name, which should be resolved in the file it appears in.
+    SubTemplate(String),
+    /// A template file path.
+    File(String),
+}
+
+/// An appearance of a symbol in a template file.
+#[derive(Debug, Clone)]
+pub(crate) struct Occurrence {
+    pub(crate) location: Range<Position>,
+    pub(crate) key: SymbolKey,
+    pub(crate) is_definition: bool,
+    pub(crate) implicit: Option<ImplicitDecl>,
+}
+
+/// A scope declaration without its own name in the source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ImplicitDecl {
+    /// `slot:a` , which is the same as `slot:a="a"` .
+    SlotValue,
+    /// The default `wx:for-item` .
+    ForItem,
+    /// The default `wx:for-index` .
+    ForIndex,
+}
+
+/// Check whether renaming a scope would change the meaning of other names.
+struct RenameProbe {
+    decl: (Range<Position>, String),
+    new_name: String,
+    conflict: bool,
+}
+
+struct Collector {
+    path: String,
+    scopes: Vec<(Range<Position>, String)>,
+    list: Vec<Occurrence>,
+    probe: Option<RenameProbe>,
+}
+
+impl Collector {
+    fn add(&mut self, location: Range<Position>, key: SymbolKey, is_definition: bool) {
+        self.list.push(Occurrence {
+            location,
+            key,
+            is_definition,
+            implicit: None,
+        });
+    }
+
+    fn probe_decl_live(&self) -> bool {
+        match self.probe.as_ref() {
+            Some(probe) => self.scopes.contains(&probe.decl),
+            None => false,
+        }
+    }
+
+    fn declare(&mut self, location: &Range<Position>, name: &str, implicit: Option<ImplicitDecl>) {
+        let decl = (location.clone(), name.to_string());
+        if let Some(probe) = self.probe.as_ref() {
+            // the new name cannot be used by any scope visible together with the renamed one
+            let conflict = if probe.decl == decl {
+                self.scopes.iter().any(|x| x.1 == probe.new_name)
+            } else {
+                name == probe.new_name && self.probe_decl_live()
+            };
+            if conflict {
+                self.probe.as_mut().unwrap().conflict = true;
+            }
+        }
+        self.scopes.push(decl.clone());
+        self.list.push(Occurrence {
+            location: location.clone(),
+            key: SymbolKey::Scope(decl.0, decl.1),
+            is_definition: true,
+            implicit,
+        });
+    }
+}
+
+impl<'a> Visit<'a> for Collector {
+    fn visit_template(&mut self, x: &'a Template) {
+        visit_scope::walk_template(self, x);
+    }
+
+    fn visit_import_element(&mut self, x: &'a ImportElement) {
+        let p = crate::path::resolve(&self.path, &x.src.name);
+        self.add(x.src.location.clone(), SymbolKey::File(p), false);
+    }
+
+    fn visit_include_element(&mut self, x: &'a IncludeElement) {
+        let p = crate::path::resolve(&self.path, &x.src.name);
+        self.add(x.src.location.clone(), SymbolKey::File(p), false);
+    }
+
+    fn visit_template_definition(&mut self, x: &'a TemplateDefinition) {
+        self.add(
+            x.name.location.clone(),
+            SymbolKey::SubTemplate(x.name.name.to_string()),
+            true,
+        );
+        visit::walk_template_definition(self, x);
+    }
+
+    fn visit_element(&mut self, x: &'a Element) {
+        if let ElementKind::TemplateRef { target, .. } = &x.kind {
+            if let Value::Static { value, location } = &target.1 {
+                self.add(
+                    location.clone(),
+                    SymbolKey::SubTemplate(value.to_string()),
+                    false,
+                );
+            }
+        }
+        visit_scope::walk_element(self, x);
+    }
+
+    fn visit_expression(&mut self, x: &'a Expression) {
+        match x {
+            Expression::ScopeRef { location, index } => {
+                if let Some((decl, name)) = self.scopes.get(*index) {
+                    let key = SymbolKey::Scope(decl.clone(), name.clone());
+                    self.add(location.clone(), key, false);
+                }
+            }
+            Expression::DataField { name, .. } => {
+                // a data field would be shadowed by the new name
+                let shadowed = match self.probe.as_ref() {
+                    Some(probe) => name.as_str() == probe.new_name,
+                    None => false,
+                };
+                if shadowed && self.probe_decl_live() {
+                    self.probe.as_mut().unwrap().conflict = true;
+                }
+            }
+            _ => {}
+        }
+        visit::walk_expression(self, x);
+    }
+}
+
+impl<'a> ScopeVisit<'a> for Collector {
+    fn declare_scope(&mut self, scope: Scope<'a>) {
+        let implicit = match scope {
+            _ if !scope.is_implicit() => None,
+            Scope::SlotValue(_) => Some(ImplicitDecl::SlotValue),
+            Scope::ForItem { .. } => Some(ImplicitDecl::ForItem),
+            Scope::ForIndex { .. } => Some(ImplicitDecl::ForIndex),
+            Scope::Script(_) | Scope::LetVar(_) => None,
+        };
+        self.declare(scope.location(), scope.name(), implicit);
+    }
+
+    fn end_scopes(&mut self, count: usize) {
+        self.scopes.truncate(self.scopes.len() - count);
+    }
+}
+
+fn collect(tree: &Template, probe: Option<RenameProbe>) -> Collector {
+    let mut c = Collector {
+        path: tree.path.clone(),
+        scopes: vec![],
+        list: vec![],
+        probe,
+    };
+    c.visit_template(tree);
+    c.list.sort_by_key(|x| (x.location.start, x.location.end));
+    c
+}
+
+/// List all symbol occurrences in a template file, in the source order.
+pub(crate) fn occurrences(tree: &Template) -> Vec<Occurrence> {
+    collect(tree, None).list
+}
+
+/// Find the occurrence under the position.
+///
+/// The position can be at the start or the end of the occurrence,
+/// so that the cursor just after a name also refers to it.
+pub(crate) fn occurrence_at(tree: &Template, pos: Position) -> Option<Occurrence> {
+    occurrences(tree)
+        .into_iter()
+        .filter(|x| x.location.start <= pos && pos <= x.location.end)
+        .filter(|x| {
+            !matches!(
+                x.implicit,
+                Some(ImplicitDecl::ForItem | ImplicitDecl::ForIndex)
+            )
+        })
+        .min_by_key(|x| x.location.end == pos)
+}
+
+/// Find the definition of a sub-template used in a template file.
+fn resolve_sub_template(group: &TmplGroup, tree: &Template, name: &str) -> Option<TmplLocation> {
+    let (t, def) = group.find_sub_template(tree, name)?;
+    Some(TmplLocation {
+        path: t.path.clone(),
+        location: def.name.location.clone(),
+    })
+}
+
+/// Resolve the definition of an occurrence in a template file.
+pub(crate) fn resolve_definition(
+    group: &TmplGroup,
+    tree: &Template,
+    occurrence: &Occurrence,
+) -> Option<TmplLocation> {
+    match &occurrence.key {
+        SymbolKey::Scope(decl, _) => Some(TmplLocation {
+            path: tree.path.clone(),
+            location: decl.clone(),
+        }),
+        SymbolKey::SubTemplate(name) => {
+            if occurrence.is_definition {
+                return Some(TmplLocation {
+                    path: tree.path.clone(),
+                    location: occurrence.location.clone(),
+                });
+            }
+            resolve_sub_template(group, tree, name)
+        }
+        SymbolKey::File(path) => {
+            let start = Position {
+                line: 0,
+                utf16_col: 0,
+            };
+            group.get_tree(path).ok().map(|t| TmplLocation {
+                path: t.path.clone(),
+                location: start..start,
+            })
+        }
+    }
+}
+
+/// Find the definition of the symbol at the position.
+pub(crate) fn find_definition(
+    group: &TmplGroup,
+    tree: &Template,
+    pos: Position,
+) -> Option<TmplLocation> {
+    let occurrence = occurrence_at(tree, pos)?;
+    resolve_definition(group, tree, &occurrence)
+}
+
+/// Find all references of the symbol at the position, including the definition.
+pub(crate) fn find_references(
+    group: &TmplGroup,
+    tree: &Template,
+    pos: Position,
+) -> Vec<TmplLocation> {
+    let Some(occurrence) = occurrence_at(tree, pos) else {
+        return vec![];
+    };
+    let mut ret: Vec<_> = symbol_occurrences(group, tree, &occurrence)
+        .into_iter()
+        .filter(|(_, x)| {
+            !matches!(
+                x.implicit,
+                Some(ImplicitDecl::ForItem | ImplicitDecl::ForIndex)
+            )
+        })
+        .map(|(path, x)| TmplLocation {
+            path,
+            location: x.location,
+        })
+        .collect();
+    ret.sort_by(|a, b| {
+        a.path
+            .cmp(&b.path)
+            .then(a.location.start.cmp(&b.location.start))
+    });
+    ret
+}
+
+/// List the occurrences of the same symbol in the group.
+fn symbol_occurrences(
+    group: &TmplGroup,
+    tree: &Template,
+    occurrence: &Occurrence,
+) -> Vec<(String, Occurrence)> {
+    let same_file = |key: &SymbolKey| {
+        occurrences(tree)
+            .into_iter()
+            .filter(|x| &x.key == key)
+            .map(|x| (tree.path.clone(), x))
+            .collect()
+    };
+    match &occurrence.key {
+        SymbolKey::Scope(..) => same_file(&occurrence.key),
+        SymbolKey::SubTemplate(_) => {
+            let Some(def) = resolve_definition(group, tree, occurrence) else {
+                return same_file(&occurrence.key);
+            };
+            let mut ret = vec![];
+            for (_, t) in group.list_template_trees() {
+                for x in occurrences(t) {
+                    let SymbolKey::SubTemplate(name) = &x.key else {
+                        continue;
+                    };
+                    let target = if x.is_definition {
+                        Some(TmplLocation {
+                            path: t.path.clone(),
+                            location: x.location.clone(),
+                        })
+                    } else {
+                        resolve_sub_template(group, t, name)
+                    };
+                    if target.as_ref() == Some(&def) {
+                        ret.push((t.path.clone(), x));
+                    }
+                }
+            }
+            ret
+        }
+        SymbolKey::File(_) => {
+            let mut ret = vec![];
+            for (_, t) in group.list_template_trees() {
+                for x in occurrences(t) {
+                    if x.key == occurrence.key {
+                        ret.push((t.path.clone(), x));
+                    }
+                }
+            }
+            ret
+        }
+    }
+}
+
+/// Compute the text edits of renaming the symbol at the position.
+pub(crate) fn rename(
+    group: &TmplGroup,
+    tree: &Template,
+    pos: Position,
+    new_name: &str,
+) -> Result<BTreeMap<String, Vec<TextEdit>>, TmplError> {
+    let err = |message: String| TmplError { message };
+    let valid = StrName {
+        name: new_name.into(),
+        location: pos..pos,
+    }
+    .is_valid_js_identifier();
+    if !valid {
+        return Err(err(format!("{:?} is not a valid identifier", new_name)));
+    }
+    let Some(occurrence) = occurrence_at(tree, pos) else {
+        return Err(err("no symbol can be renamed here".to_string()));
+    };
+    match &occurrence.key {
+        SymbolKey::Scope(decl, name) => {
+            let probe = RenameProbe {
+                decl: (decl.clone(), name.clone()),
+                new_name: new_name.to_string(),
+                conflict: false,
+            };
+            let c = collect(tree, Some(probe));
+            if new_name != name && c.probe.map(|x| x.conflict).unwrap_or(false) {
+                return Err(err(format!(
+                    "{:?} collides with other names in the scope",
+                    new_name
+                )));
+            }
+        }
+        SymbolKey::SubTemplate(name) => {
+            if new_name != name {
+                for (path, x) in symbol_occurrences(group, tree, &occurrence) {
+                    let t = group.get_tree(&path)?;
+                    let used = if x.is_definition {
+                        t.globals
+                            .sub_templates
+                            .iter()
+                            .any(|x| x.name.name == new_name)
+                    } else {
+                        resolve_sub_template(group, t, new_name).is_some()
+                    };
+                    if used {
+                        return Err(err(format!(
+                            "{:?} collides with other templates used in {:?}",
+                            new_name, path
+                        )));
+                    }
+                }
+            }
+        }
+        SymbolKey::File(_) => {
+            return Err(err("files cannot be renamed".to_string()));
+        }
+    }
+    let mut ret: BTreeMap<String, Vec<TextEdit>> = BTreeMap::new();
+    for (path, x) in symbol_occurrences(group, tree, &occurrence) {
+        let start = x.location.start;
+        let (range, new_text) = match x.implicit {
+            None => (x.location.clone(), new_name.to_string()),
+            Some(ImplicitDecl::SlotValue) => {
+                let end = x.location.end;
+                (end..end, format!("=\"{}\"", new_name))
+            }
+            Some(ImplicitDecl::ForItem) => (start..start, format!("wx:for-item=\"{}\" ", new_name)),
+            Some(ImplicitDecl::ForIndex) => {
+                (start..start, format!("wx:for-index=\"{}\" ", new_name))
+            }
+        };
+        ret.entry(path)
+            .or_default()
+            .push(TextEdit { range, new_text });
+    }
+    // edits are applied in order, so the later ones come first
+    for list in ret.values_mut() {
+        list.sort_by_key(|x| std::cmp::Reverse(x.range.start));
+    }
+    Ok(ret)
+}
@@ -7,6 +7,7 @@ use crate::binding_map::{BindingMapCollector, BindingMapKeys};
 use super::{ParseErrorKind, ParseState, Position, TemplateStructure};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Expression {
     /// A reference to a data scopes.
@@ -67,6 +68,11 @@ pub enum Expression {
         fields: Vec<ArrayFieldKind>,
         bracket_location: (Range<Position>, Range<Position>),
     },
+    /// A template literal string, e.g. `` `a${b}c` `` .
+    LitTemplateStr {
+        parts: Vec<TemplateStrPart>,
+        backtick_location: (Range<Position>, Range<Position>),
+    },
 
     StaticMember {
         obj: Box<Expression>,
@@ -84,6 +90,24 @@ pub enum Expression {
         args: Vec<Expression>,
         paren_location: (Range<Position>, Range<Position>),
     },
+    OptionalStaticMember {
+        obj: Box<Expression>,
+        field_name: CompactString,
+        question_dot_location: Range<Position>,
+        field_location: Range<Position>,
+    },
+    OptionalDynamicMember {
+        obj: Box<Expression>,
+        field_name: Box<Expression>,
+        question_dot_location: Range<Position>,
+        bracket_location: (Range<Position>, Range<Position>),
+    },
+    OptionalFuncCall {
+        func: Box<Expression>,
+        args: Vec<Expression>,
+        question_dot_location: Range<Position>,
+        paren_location: (Range<Position>, Range<Position>),
+    },
 
     Reverse {
         value: Box<Expression>,
@@ -240,6 +264,7 @@ pub enum Expression {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ObjectFieldKind {
     Named {
         name: CompactString,
@@ -254,6 +279,20 @@ pub enum ObjectFieldKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemplateStrPart {
+    Static {
+        value: CompactString,
+        location: Range<Position>,
+    },
+    Dynamic {
+        value: Expression,
+        brace_location: (Range<Position>, Range<Position>),
+    },
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArrayFieldKind {
     Normal {
         value: Expression,
@@ -285,9 +324,15 @@ impl TemplateStructure for Expression {
             Self::LitArr {
                 bracket_location, ..
             } => bracket_location.0.start,
+            Self::LitTemplateStr {
+                backtick_location, ..
+            } => backtick_location.0.start,
             Self::StaticMember { obj, .. } => obj.location_start(),
             Self::DynamicMember { obj, .. } => obj.location_start(),
             Self::FuncCall { func, .. } => func.location_start(),
+            Self::OptionalStaticMember { obj, .. } => obj.location_start(),
+            Self::OptionalDynamicMember { obj, .. } => obj.location_start(),
+            Self::OptionalFuncCall { func, .. } => func.location_start(),
             Self::Reverse { location, .. } => location.start,
             Self::BitReverse { location, .. } => location.start,
             Self::Positive { location, .. } => location.start,
@@ -336,9 +381,15 @@ impl TemplateStructure for Expression {
             Self::LitArr {
                 bracket_location, ..
             } => bracket_location.1.end,
+            Self::LitTemplateStr {
+                backtick_location, ..
+            } => backtick_location.1.end,
             Self::StaticMember { obj, .. } => obj.location_end(),
             Self::DynamicMember { obj, .. } => obj.location_end(),
             Self::FuncCall { func, .. } => func.location_end(),
+            Self::OptionalStaticMember { obj, .. } => obj.location_end(),
+            Self::OptionalDynamicMember { obj, .. } => obj.location_end(),
+            Self::OptionalFuncCall { func, .. } => func.location_end(),
             Self::Reverse { location, .. } => location.end,
             Self::BitReverse { location, .. } => location.end,
             Self::Positive { location, .. } => location.end,
@@ -665,62 +716,7 @@ impl Expression {
                     break;
                 };
                 if next == '\\' {
-                    let next = ps.next()?;
-                    let ch = match next {
-                        'r' => '\r',
-                        'n' => '\n',
-                        't' => '\t',
-                        'b' => '\x08',
-                        'f' => '\x0C',
-                        'v' => '\x0B',
-                        '0' => '\0',
-                        'x' | 'u' => {
-                            let range = if next == 'x' { 0..2 } else { 0..4 };
-                            let pos = ps.position();
-                            let ch = ps.try_parse(|ps| {
-                                let mut v = 0;
-                                for _ in range {
-                                    let next = ps.next()?;
-                                    let x = match next {
-                                        '0' => 0,
-                                        '1' => 1,
-                                        '2' => 2,
-                                        '3' => 3,
-                                        '4' => 4,
-                                        '5' => 5,
-                                        '6' => 6,
-                                        '7' => 7,
-                                        '8' => 8,
-                                        '9' => 9,
-                                        'a' | 'A' => 10,
-                                        'b' | 'B' => 11,
-                                        'c' | 'C' => 12,
-                                        'd' | 'D' => 13,
-                                        'e' | 'E' => 14,
-                                        'f' | 'F' => 15,
-                                        _ => {
-                                            ps.add_warning(
-                                                ParseErrorKind::IllegalEscapeSequence,
-                                                pos..ps.position(),
-                                            );
-                                            return None;
-                                        }
-                                    };
-                                    v = v * 16 + x;
-                                }
-                                let Some(ch) = char::from_u32(v) else {
-                                    ps.add_warning(
-                                        ParseErrorKind::IllegalEscapeSequence,
-                                        pos..ps.position(),
-                                    );
-                                    return None;
-                                };
-                                Some(ch)
-                            });
-                            ch.unwrap_or(' ')
-                        }
-                        x => x,
-                    };
+                    let ch = Self::parse_escape_sequence(ps)?;
                     ret.push(ch);
                 } else {
                     ret.push(next);
@@ -733,6 +729,121 @@ impl Expression {
         })
     }
 
+    fn parse_lit_template_str(ps: &mut ParseState) -> Option<Box<Self>> {
+        let Some(backtick_start_location) = ps.consume_str("`") else {
+            ps.add_warning_at_current_position(ParseErrorKind::UnexpectedExpressionCharacter);
+            return None;
+        };
+        ps.parse_off_auto_whitespace(|ps| {
+            let mut parts = vec![];
+            let mut static_value = CompactString::new_inline("");
+            let mut static_start = ps.position();
+            let backtick_end_location = loop {
+                let static_end = ps.position();
+                let end = ps.consume_str("`");
+                let dollar_brace = end.is_none().then(|| ps.consume_str("${")).flatten();
+                if (end.is_some() || dollar_brace.is_some()) && !static_value.is_empty() {
+                    parts.push(TemplateStrPart::Static {
+                        value: std::mem::replace(&mut static_value, CompactString::new_inline("")),
+                        location: static_start..static_end,
+                    });
+                }
+                if let Some(end) = end {
+                    break end;
+                }
+                if let Some(brace_start_location) = dollar_brace {
+                    let (value, brace_end_location) = ps.parse_on_auto_whitespace(
+                        |ps| ps.skip_whitespace_with_js_comments(),
+                        |ps| {
+                            let value = Self::parse_cond(ps)?;
+                            let Some(brace_end_location) = ps.consume_str("}") else {
+                                ps.add_warning_at_current_position(
+                                    ParseErrorKind::UnexpectedExpressionCharacter,
+                                );
+                                return None;
+                            };
+                            Some((value, brace_end_location))
+                        },
+                    )?;
+                    parts.push(TemplateStrPart::Dynamic {
+                        value: *value,
+                        brace_location: (brace_start_location, brace_end_location),
+                    });
+                    static_start = ps.position();
+                    continue;
+                }
+                let next = ps.next()?;
+                if next == '\\' {
+                    let ch = Self::parse_escape_sequence(ps)?;
+                    static_value.push(ch);
+                } else {
+                    static_value.push(next);
+                }
+            };
+            Some(Box::new(Self::LitTemplateStr {
+                parts,
+                backtick_location: (backtick_start_location, backtick_end_location),
+            }))
+        })
+    }
+
+    fn parse_escape_sequence(ps: &mut ParseState) -> Option<char> {
+        let next = ps.next()?;
+        let ch = match next {
+            'r' => '\r',
+            'n' => '\n',
+            't' => '\t',
+            'b' => '\x08',
+            'f' => '\x0C',
+            'v' => '\x0B',
+            '0' => '\0',
+            'x' | 'u' => {
+                let range = if next == 'x' { 0..2 } else { 0..4 };
+                let pos = ps.position();
+                let ch = ps.try_parse(|ps| {
+                    let mut v = 0;
+                    for _ in range {
+                        let next = ps.next()?;
+                        let x = match next {
+                            '0' => 0,
+                            '1' => 1,
+                            '2' => 2,
+                            '3' => 3,
+                            '4' => 4,
+                            '5' => 5,
+                            '6' => 6,
+                            '7' => 7,
+                            '8' => 8,
+                            '9' => 9,
+                            'a' | 'A' => 10,
+                            'b' | 'B' => 11,
+                            'c' | 'C' => 12,
+                            'd' | 'D' => 13,
+                            'e' | 'E' => 14,
+                            'f' | 'F' => 15,
+                            _ => {
+                                ps.add_warning(
+                                    ParseErrorKind::IllegalEscapeSequence,
+                                    pos..ps.position(),
+                                );
+                                return None;
+                            }
+                        };
+                        v = v * 16 + x;
+                    }
+                    let Some(ch) = char::from_u32(v) else {
+                        ps.add_warning(ParseErrorKind::IllegalEscapeSequence, pos..ps.position());
+                        return None;
+                    };
+                    Some(ch)
+                });
+                ch.unwrap_or(' ')
+            }
+            x => x,
+        };
+        Some(ch)
+    }
+
     fn parse_number(ps: &mut ParseState) -> Option<Box<Self>> {
         let peek = ps.peek::<0>()?;
         if !('0'..='9').contains(&peek) && peek != '.' {
@@ -922,6 +1033,9 @@ impl Expression {
         if ch == '"' || ch == '\'' {
             return Self::parse_lit_str(ps);
         }
+        if ch == '`' {
+            return Self::parse_lit_template_str(ps);
+        }
         if ('0'..='9').contains(&ch) || ch == '.' {
             return Self::parse_number(ps);
         }
@@ -976,17 +1090,7 @@ impl Expression {
                 continue;
             }
             if let Some(start) = ParseOperator::func_call(ps) {
-                let mut args = vec![];
-                loop {
-                    if ps.peek::<0>()? == ')' {
-                        break;
-                    }
-                    let value = *Self::parse_cond(ps)?;
-                    args.push(value);
-                    if ps.consume_str(",").is_none() {
-                        break;
-                    }
-                }
+                let args = Self::parse_func_call_args(ps)?;
                 let Some(end) = ParseOperator::func_call_end(ps) else {
                     if !ps.ended() {
                         ps.add_warning_at_current_position(ParseErrorKind::UnmatchedParenthesis);
@@ -1000,11 +1104,73 @@ impl Expression {
                 });
                 continue;
             }
+            if let Some(question_dot_location) = ParseOperator::optional_chain(ps) {
+                if let Some(start) = ParseOperator::dynamic_member(ps) {
+                    let field_name = Self::parse_cond(ps)?;
+                    let Some(end) = ParseOperator::dynamic_member_end(ps) else {
+                        if !ps.ended() {
+                            ps.add_warning_at_current_position(ParseErrorKind::UnmatchedBracket);
+                        }
+                        return None;
+                    };
+                    obj = Box::new(Self::OptionalDynamicMember {
+                        obj,
+                        field_name,
+                        question_dot_location,
+                        bracket_location: (start, end),
+                    });
+                    continue;
+                }
+                if let Some(start) = ParseOperator::func_call(ps) {
+                    let args = Self::parse_func_call_args(ps)?;
+                    let Some(end) = ParseOperator::func_call_end(ps) else {
+                        if !ps.ended() {
+                            ps.add_warning_at_current_position(
+                                ParseErrorKind::UnmatchedParenthesis,
+                            );
+                        }
+                        return None;
+                    };
+                    obj = Box::new(Self::OptionalFuncCall {
+                        func: obj,
+                        args,
+                        question_dot_location,
+                        paren_location: (start, end),
+                    });
+                    continue;
+                }
+                let Some((field_name, field_location)) = Self::try_parse_field_name(ps) else {
+                    ps.add_warning_at_current_position(ParseErrorKind::InvalidIdentifier);
+                    return None;
+                };
+                obj = Box::new(Self::OptionalStaticMember {
+                    obj,
+                    field_name,
+                    question_dot_location,
+                    field_location,
+                });
+                continue;
+            }
             break;
         }
         Some(obj)
     }
 
+    fn parse_func_call_args(ps: &mut ParseState) -> Option<Vec<Self>> {
+        let mut args = vec![];
+        loop {
+            if ps.peek::<0>()? == ')' {
+                break;
+            }
+            let value = *Self::parse_cond(ps)?;
+            args.push(value);
+            if ps.consume_str(",").is_none() {
+                break;
+            }
+        }
+        Some(args)
+    }
+
     fn parse_reverse(ps: &mut ParseState) -> Option<Box<Self>> {
         if let Some(location) = ParseOperator::reverse(ps) {
             let value = Self::parse_reverse(ps)?;
@@ -1110,7 +1276,6 @@ define_operator!(dynamic_member, "[", []);
 define_operator!(dynamic_member_end, "]", []);
 define_operator!(func_call, "(", []);
 define_operator!(func_call_end, ")", []);
-// `?.` is not supported
 // `new` is treated as an identifier
 
 // `++` `--` are not allowed
@@ -1162,6 +1327,16 @@ define_operator!(nullish_coalescing, "??", ["="]);
 define_operator!(condition_end, ":", []);
 
 impl ParseOperator {
+    fn optional_chain(ps: &mut ParseState) -> Option<Range<Position>> {
+        // `?.1` is a condition followed by a number rather than an optional chain
+        if let Some([p0, p1, p2]) = ps.peek_n::<3>() {
+            if p0 == '?' && p1 == '.' && p2.is_ascii_digit() {
+                return None;
+            }
+        }
+        ps.consume_str("?.")
+    }
+
     fn condition(ps: &mut ParseState) -> Option<Range<Position>> {
         ps.consume_str_except_followed("?", ["?", "."]).or_else(|| {
             let [p0, p1, p2] = ps.peek_n::<3>()?;
@@ -1437,7 +1612,15 @@ macro_rules! iter_sub_expr {
                             }
                         }
                     }
-                    Expression::StaticMember { obj, .. } => {
+                    Expression::LitTemplateStr { parts, .. } => loop {
+                        let x = parts.$get(self.index)?;
+                        self.index += 1;
+                        if let TemplateStrPart::Dynamic { value, .. } = x {
+                            break value;
+                        }
+                    },
+                    Expression::StaticMember { obj, .. }
+                    | Expression::OptionalStaticMember { obj, .. } => {
                         if self.index == 0 {
                             self.index = 1;
                             obj
@@ -1447,6 +1630,9 @@ macro_rules! iter_sub_expr {
                     }
                     Expression::DynamicMember {
                         obj, field_name, ..
+                    }
+                    | Expression::OptionalDynamicMember {
+                        obj, field_name, ..
                     } => {
                         if self.index == 0 {
                             self.index = 1;
@@ -1458,7 +1644,8 @@ macro_rules! iter_sub_expr {
                             return None;
                         }
                     }
-                    Expression::FuncCall { func, args, .. } => {
+                    Expression::FuncCall { func, args, .. }
+                    | Expression::OptionalFuncCall { func, args, .. } => {
                         if self.index == 0 {
                             self.index = 1;
                             func
@@ -1593,6 +1780,33 @@ mod test {
         case!(r#"{{ "" }}"#, "");
     }
 
+    #[test]
+    fn lit_template_str() {
+        case!("{{ `", "", ParseErrorKind::MissingExpressionEnd, 0..2);
+        case!("{{ `` }}", "{{``}}");
+        case!("{{ `a` }}", "{{`a`}}");
+        case!("{{ `a${ b }c` }}", "{{`a${b}c`}}");
+        case!(
+            "{{ `${ a + b }${ c ? `d${e}` : f }` }}",
+            "{{`${a+b}${c?`d${e}`:f}`}}"
+        );
+        case!(r#"{{ `\x41\`\${}${'}'}$` }}"#, r#"{{`A\`\${}${"}"}$`}}"#);
+        case!(
+            "{{ `${ a` }}",
+            "",
+            ParseErrorKind::UnexpectedExpressionCharacter,
+            8..8
+        );
+        case!(
+            "{{ `${ }` }}",
+            "",
+            ParseErrorKind::UnexpectedExpressionCharacter,
+            7..7
+        );
+        case!("{{ `a` + b }}", "{{`a`+b}}");
+        case!("{{ c(`a${b}`).d }}", "{{c(`a${b}`).d}}");
+    }
+
     #[test]
     fn number() {
         case!(r#"{{ 0 }}"#, r#"{{0}}"#);
@@ -1742,6 +1956,32 @@ mod test {
         case!("{{ a(0 , a ? b : c) }}", "{{a(0,a?b:c)}}");
     }
 
+    #[test]
+    fn optional_chain() {
+        case!("{{ a ?. b }}", "{{a?.b}}");
+        case!("{{ a?.b.c?.d }}", "{{a?.b.c?.d}}");
+        case!("{{ a?. }}", "", ParseErrorKind::InvalidIdentifier, 7..7);
+        case!(
+            "{{ a ?. 1 : 2 }}",
+            "",
+            ParseErrorKind::InvalidIdentifier,
+            8..8
+        );
+        case!("{{ a?.[ b ? c : d ] }}", "{{a?.[b?c:d]}}");
+        case!("{{ a?.[0 }}", "", ParseErrorKind::UnmatchedBracket, 9..9);
+        case!("{{ a?.( ) }}", "{{a?.()}}");
+        case!("{{ a?.(0 , b,) }}", "{{a?.(0,b)}}");
+        case!(
+            "{{ a?.(0 }}",
+            "",
+            ParseErrorKind::UnmatchedParenthesis,
+            9..9
+        );
+        case!("{{ a?.b?.[c]?.(d) }}", "{{a?.b?.[c]?.(d)}}");
+        case!("{{ a?.b ? c : d }}", "{{a?.b?c:d}}");
+        case!("{{ a?.b ?? c }}", "{{a?.b??c}}");
+    }
+
     #[test]
     fn reverse() {
         case!("{{ ! a.b }}", "{{!a.b}}");
@@ -2186,18 +2426,7 @@ mod test {
             ParseErrorKind::UnexpectedExpressionCharacter,
             9..9
         );
-        case!(
-            "{{ a ?. b }}",
-            "",
-            ParseErrorKind::UnexpectedExpressionCharacter,
-            5..5
-        );
-        case!(
-            "{{ a ?. 1 : 2 }}",
-            "",
-            ParseErrorKind::UnexpectedExpressionCharacter,
-            5..5
-        );
+        case!("{{ a ?. b }}", "{{a?.b}}");
         case!("{{ a ?.1 : 2 }}", "{{a?0.1:2}}");
         case!("{{ a ? b : c }}", "{{a?b:c}}");
     }
@@ -2237,9 +2466,13 @@ mod test {
         check_sub_expr("true", &[]);
         check_sub_expr("{ a, b: 1, ...c }", &["a", "1", "c"]);
         check_sub_expr("[a, ...b]", &["a", "b"]);
+        check_sub_expr("`a${b}c${d}`", &["b", "d"]);
         check_sub_expr("a.b", &["a"]);
         check_sub_expr("a[b]", &["a", "b"]);
         check_sub_expr("a(b, c,)", &["a", "b", "c"]);
+        check_sub_expr("a?.b", &["a"]);
+        check_sub_expr("a?.[b]", &["a", "b"]);
+        check_sub_expr("a?.(b, c,)", &["a", "b", "c"]);
         check_sub_expr("!a", &["a"]);
         check_sub_expr("~a", &["a"]);
         check_sub_expr("+a", &["a"]);
@@ -0,0 +1,592 @@
+//! Incremental re-parsing of edited templates.
+//!
+//! When a template source is edited, the smallest element which contains the whole edited range
+//! is re-parsed alone, and the positions after it are shifted.
+//! If the re-parsed element may interact with its siblings
+//! (e.g. it turns into a `wx:if` branch or contains a global item like `<wxs>` ),
+//! the element parent is tried instead, and the whole template is re-parsed at last.
+//! The result is always the same as a full re-parse.
+
+use std::ops::Range;
+
+use super::{
+    tag::{ElementKind, Node, TagLocation, Template, TemplateGlobals},
+    visit_mut::VisitMut,
+    ParseError, ParseErrorKind, ParseState, Position, TemplateStructure,
+};
+
+/// A text edit on a template source.
+///
+/// The `range` is based on the source text with all previous edits applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub range: Range<Position>,
+    pub new_text: String,
+}
+
+/// The source text of a parsed template, kept for incremental re-parsing.
+#[derive(Debug, Clone)]
+pub(crate) struct TemplateSource {
+    content: String,
+    warnings: Vec<ParseError>,
+    scope_warnings: Vec<ParseError>,
+    tree_modified: bool,
+}
+
+impl TemplateSource {
+    /// Parse the whole source.
+    pub(crate) fn parse(path: &str, content: String) -> (Template, Self) {
+        let mut ps = ParseState::new(path, &content, Position::default());
+        let mut template = Template::parse_nodes(&mut ps);
+        let warnings = ps.take_warnings();
+        template.init_scopes_and_binding_map_keys(&mut ps);
+        let scope_warnings = ps.take_warnings();
+        let this = Self {
+            content,
+            warnings,
+            scope_warnings,
+            tree_modified: false,
+        };
+        (template, this)
+    }
+
+    /// All warnings of the current source, in the same order as a full parsing.
+    pub(crate) fn warnings(&self) -> Vec<ParseError> {
+        self.warnings
+            .iter()
+            .chain(self.scope_warnings.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// The source text, or `None` if the template tree no longer matches it.
+    pub(crate) fn content(&self) -> Option<&str> {
+        if self.tree_modified {
+            None
+        } else {
+            Some(&self.content)
+        }
+    }
+
+    /// The source text, which may not match the template tree.
+    pub(crate) fn text(&self) -> &str {
+        &self.content
+    }
+
+    /// Mark that the template tree has been modified without editing the source.
+    ///
+    /// The next update will always re-parse the whole source.
+    pub(crate) fn mark_tree_modified(&mut self) {
+        self.tree_modified = true;
+    }
+
+    /// Apply edits to the source and update the template tree.
+    pub(crate) fn apply_edits(&mut self, template: &mut Template, edits: &[TextEdit]) {
+        let mut content = self.content.clone();
+        for edit in edits {
+            let line_index = LineIndex::new(&content);
+            let start = line_index.byte_index(&content, edit.range.start);
+            let end = line_index.byte_index(&content, edit.range.end).max(start);
+            content.replace_range(start..end, &edit.new_text);
+        }
+        if !self.tree_modified && self.update_partially(template, &content) {
+            self.content = content;
+            return;
+        }
+        let (new_template, new_source) = Self::parse(&template.path, content);
+        *template = new_template;
+        *self = new_source;
+    }
+
+    fn update_partially(&mut self, template: &mut Template, new_content: &str) -> bool {
+        let old_content = self.content.as_str();
+        if old_content == new_content {
+            return true;
+        }
+
+        // find the changed range
+        let mut prefix_len = old_content
+            .bytes()
+            .zip(new_content.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old_content.is_char_boundary(prefix_len) {
+            prefix_len -= 1;
+        }
+        let max_suffix_len = old_content.len().min(new_content.len()) - prefix_len;
+        let mut suffix_len = old_content
+            .bytes()
+            .rev()
+            .zip(new_content.bytes().rev())
+            .take(max_suffix_len)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old_content.is_char_boundary(old_content.len() - suffix_len) {
+            suffix_len -= 1;
+        }
+        let line_index = LineIndex::new(old_content);
+        let changed = line_index.position(old_content, prefix_len)
+            ..line_index.position(old_content, old_content.len() - suffix_len);
+
+        // collect the elements containing the changed range, from the outermost one
+        let mut candidates = vec![];
+        let tree_count = template.globals.sub_templates.len();
+        for (tree_index, sub) in template.globals.sub_templates.iter().enumerate() {
+            let tag_start = &sub.tag_location.start;
+            let mut ancestors = vec![tag_start.0.start..tag_start.1.end];
+            collect_candidates(
+                &sub.content,
+                None,
+                tree_index,
+                &changed,
+                &mut ancestors,
+                &mut candidates,
+            );
+        }
+        collect_candidates(
+            &template.content,
+            None,
+            tree_count,
+            &changed,
+            &mut vec![],
+            &mut candidates,
+        );
+
+        // try to re-parse from the innermost one
+        for candidate in candidates.iter().rev() {
+            if self.reparse_element(template, new_content, &line_index, candidate) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn reparse_element(
+        &mut self,
+        template: &mut Template,
+        new_content: &str,
+        line_index: &LineIndex,
+        candidate: &Candidate,
+    ) -> bool {
+        let old_content = self.content.as_str();
+        let old_location = candidate.location.clone();
+        if contains_globals(&template.globals, &old_location) {
+            return false;
+        }
+
+        // parse the new element with the following source
+        let start = line_index.byte_index(old_content, old_location.start);
+        let old_end = line_index.byte_index(old_content, old_location.end);
+        let new_end = old_end + new_content.len() - old_content.len();
+        let mut ps = ParseState::new(&template.path, &new_content[start..], old_location.start);
+        let mut globals = TemplateGlobals::new();
+        let mut new_nodes = vec![];
+        if !Node::parse_element(&mut ps, &mut globals, &mut new_nodes) {
+            return false;
+        }
+        if ps.cur_index() != new_end - start || !globals.is_empty() {
+            return false;
+        }
+        let new_end_pos = ps.position();
+        let new_warnings = ps.take_warnings();
+
+        // the siblings may be grouped into a `wx:if` with the new nodes
+        let last_node = new_nodes
+            .iter()
+            .rev()
+            .find(|x| !matches!(x, Node::Comment(..)));
+        match last_node {
+            None => return false,
+            Some(Node::Element(elem)) => {
+                if let ElementKind::If { .. } = &elem.kind {
+                    return false;
+                }
+            }
+            Some(_) => {}
+        }
+        if candidate.follows_if
+            && new_warnings
+                .iter()
+                .any(|x| x.kind == ParseErrorKind::InvalidAttribute)
+        {
+            return false;
+        }
+
+        // find out the warnings generated by the old element
+        let Some(warning_range) =
+            self.element_warning_range(&old_location, candidate, !new_warnings.is_empty())
+        else {
+            return false;
+        };
+        let Some(scope_warning_range) =
+            self.element_scope_warning_range(&template.globals, &old_location, candidate)
+        else {
+            return false;
+        };
+
+        // replace the element and shift the following positions
+        let mut shift = ShiftPositions {
+            old_end: old_location.end,
+            new_end: new_end_pos,
+        };
+        shift.visit_template(template);
+        shift.shift_warnings(&mut self.warnings);
+        shift.shift_warnings(&mut self.scope_warnings);
+        self.warnings.splice(warning_range, new_warnings);
+        let mut new_nodes = Some(new_nodes);
+        replace_element(&mut template.content, old_location.start, &mut new_nodes);
+        for sub in template.globals.sub_templates.iter_mut() {
+            replace_element(&mut sub.content, old_location.start, &mut new_nodes);
+        }
+
+        // re-collect scopes and binding map keys
+        let mut ps = ParseState::new(&template.path, "", Position::default());
+        template.init_scopes_and_binding_map_keys(&mut ps);
+        self.scope_warnings
+            .splice(scope_warning_range, ps.take_warnings());
+        true
+    }
+
+    fn element_warning_range(
+        &self,
+        location: &Range<Position>,
+        candidate: &Candidate,
+        has_new_warnings: bool,
+    ) -> Option<Range<usize>> {
+        let mut inside = None;
+        let mut before_count = 0;
+        for (index, w) in self.warnings.iter().enumerate() {
+            match classify_location(&w.location, location)? {
+                LocationClass::Before => before_count += 1,
+                LocationClass::Inside => match &mut inside {
+                    None => inside = Some(index..(index + 1)),
+                    Some(range) => {
+                        if range.end != index {
+                            return None;
+                        }
+                        range.end = index + 1;
+                    }
+                },
+                LocationClass::After => {}
+            }
+        }
+        if let Some(range) = inside {
+            return Some(range);
+        }
+        if !has_new_warnings {
+            return Some(0..0);
+        }
+
+        // ancestors may generate warnings at their start tags after parsing children,
+        // so the insert position is unknown
+        let has_late_ancestor_warnings = self.warnings.iter().any(|w| {
+            match w.kind {
+                ParseErrorKind::MissingEndTag | ParseErrorKind::InvalidAttribute => {}
+                _ => return false,
+            }
+            candidate
+                .ancestors
+                .iter()
+                .any(|x| x.start <= w.location.start && w.location.end <= x.end)
+        });
+        if has_late_ancestor_warnings {
+            return None;
+        }
+        let is_ordered = self.warnings[before_count..]
+            .iter()
+            .all(|w| w.location.start >= location.end);
+        is_ordered.then_some(before_count..before_count)
+    }
+
+    fn element_scope_warning_range(
+        &self,
+        globals: &TemplateGlobals,
+        location: &Range<Position>,
+        candidate: &Candidate,
+    ) -> Option<Range<usize>> {
+        let mut inside = None;
+        for (index, w) in self.scope_warnings.iter().enumerate() {
+            if let LocationClass::Inside = classify_location(&w.location, location)? {
+                match &mut inside {
+                    None => inside = Some(index..(index + 1)),
+                    Some(range) => {
+                        if range.end != index {
+                            return None;
+                        }
+                        range.end = index + 1;
+                    }
+                }
+            }
+        }
+        if let Some(range) = inside {
+            return Some(range);
+        }
+
+        // these warnings are ordered by trees and then positions
+        let mut sub_template_locations = vec![];
+        for x in globals.sub_templates.iter() {
+            let (_, end) = x.tag_location.end.as_ref()?;
+            sub_template_locations.push(x.tag_location.start.0.start..end.end);
+        }
+        let before_count = self
+            .scope_warnings
+            .iter()
+            .filter(|w| {
+                let tree_index = sub_template_locations
+                    .iter()
+                    .position(|x| x.start <= w.location.start && w.location.end <= x.end)
+                    .unwrap_or(sub_template_locations.len());
+                (tree_index, w.location.start) < (candidate.tree_index, location.start)
+            })
+            .count();
+        Some(before_count..before_count)
+    }
+}
+
+struct Candidate {
+    location: Range<Position>,
+    ancestors: Vec<Range<Position>>,
+    tree_index: usize,
+    follows_if: bool,
+}
+
+fn collect_candidates(
+    nodes: &[Node],
+    generated_by: Option<&Range<Position>>,
+    tree_index: usize,
+    changed: &Range<Position>,
+    ancestors: &mut Vec<Range<Position>>,
+    ret: &mut Vec<Candidate>,
+) {
+    let mut follows_if = false;
+    for node in nodes {
+        let Node::Element(elem) = node else {
+            // comments are skipped when finding the `wx:if` before
+            if let Node::Comment(..) = node {
+            } else {
+                follows_if = false;
+            }
+            continue;
+        };
+        let prev_follows_if = follows_if;
+        let is_if = matches!(elem.kind, ElementKind::If { .. });
+        follows_if = is_if;
+        let location = elem.location();
+        if location.start >= changed.start || changed.end >= location.end {
+            continue;
+        }
+
+        // an element generated by the `wx:for` or `wx:if` of the parent shares the same tag,
+        // and an element without the end tag may end at a different position
+        let tag_location = &elem.tag_location;
+        let start_tag = tag_location.start.0.start..tag_location.start.1.end;
+        let is_generated = match generated_by {
+            Some(x) => start_tag.start <= x.start && x.end <= start_tag.end,
+            None => false,
+        };
+        let is_closed = tag_location.end.is_some() || tag_location.close != tag_location.start.1;
+        if !is_if && !is_generated && is_closed {
+            ret.push(Candidate {
+                location,
+                ancestors: ancestors.clone(),
+                tree_index,
+                follows_if: prev_follows_if,
+            });
+        }
+        ancestors.push(start_tag);
+        match &elem.kind {
+            ElementKind::Normal { children, .. } | ElementKind::Pure { children, .. } => {
+                collect_candidates(children, None, tree_index, changed, ancestors, ret);
+            }
+            ElementKind::For { list, children, .. } => {
+                collect_candidates(children, Some(&list.0), tree_index, changed, ancestors, ret);
+            }
+            ElementKind::If {
+                branches,
+                else_branch,
+            } => {
+                for (location, _, children) in branches {
+                    collect_candidates(
+                        children,
+                        Some(location),
+                        tree_index,
+                        changed,
+                        ancestors,
+                        ret,
+                    );
+                }
+                if let Some((location, children)) = else_branch {
+                    collect_candidates(
+                        children,
+                        Some(location),
+                        tree_index,
+                        changed,
+                        ancestors,
+                        ret,
+                    );
+                }
+            }
+            ElementKind::TemplateRef { .. }
+            | ElementKind::Include { .. }
+            | ElementKind::Slot { .. } => {}
+        }
+        ancestors.pop();
+        return;
+    }
+}
+
+fn replace_element(nodes: &mut Vec<Node>, start: Position, new_nodes: &mut Option<Vec<Node>>) {
+    if new_nodes.is_none() {
+        return;
+    }
+    for index in 0..nodes.len() {
+        let Node::Element(elem) = &mut nodes[index] else {
+            continue;
+        };
+        let location = elem.location();
+        if location.start > start || location.end <= start {
+            continue;
+        }
+        let is_if = matches!(elem.kind, ElementKind::If { .. });
+        if location.start == start && !is_if {
+            let new_nodes = new_nodes.take().unwrap();
+            nodes.splice(index..(index + 1), new_nodes);
+            return;
+        }
+        match &mut elem.kind {
+            ElementKind::Normal { children, .. }
+            | ElementKind::Pure { children, .. }
+            | ElementKind::For { children, .. } => replace_element(children, start, new_nodes),
+            ElementKind::If {
+                branches,
+                else_branch,
+            } => {
+                for (_, _, children) in branches {
+                    replace_element(children, start, new_nodes);
+                }
+                if let Some((_, children)) = else_branch {
+                    replace_element(children, start, new_nodes);
+                }
+            }
+            ElementKind::TemplateRef { .. }
+            | ElementKind::Include { .. }
+            | ElementKind::Slot { .. } => {}
+        }
+        return;
+    }
+}
+
+fn contains_globals(globals: &TemplateGlobals, location: &Range<Position>) -> bool {
+    let TemplateGlobals {
+        imports,
+        includes,
+        sub_templates,
+        scripts,
+        binding_map_collector: _,
+    } = globals;
+    let inside = |tag_location: &TagLocation| {
+        let start = tag_location.start.0.start;
+        location.start <= start && start < location.end
+    };
+    imports.iter().any(|x| inside(&x.tag_location))
+        || includes.iter().any(|x| inside(&x.tag_location))
+        || sub_templates.iter().any(|x| inside(&x.tag_location))
+        || scripts.iter().any(|x| inside(&x.tag_location()))
+}
+
+enum LocationClass {
+    Before,
+    Inside,
+    After,
+}
+
+/// Find the relation between a warning location and an element location.
+///
+/// Returns `None` if it cannot be decided.
+fn classify_location(loc: &Range<Position>, elem: &Range<Position>) -> Option<LocationClass> {
+    if loc.start == loc.end && (loc.start == elem.start || loc.start == elem.end) {
+        return None;
+    }
+    if loc.end <= elem.start {
+        Some(LocationClass::Before)
+    } else if loc.start >= elem.end {
+        Some(LocationClass::After)
+    } else if loc.start >= elem.start && loc.end <= elem.end {
+        Some(LocationClass::Inside)
+    } else {
+        None
+    }
+}
+
+pub(crate) struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub(crate) fn new(s: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(s.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// Convert a position to the UTF-8 byte index, clamped to the source range.
+    pub(crate) fn byte_index(&self, s: &str, pos: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(pos.line as usize) else {
+            return s.len();
+        };
+        let line_end = match self.line_starts.get(pos.line as usize + 1) {
+            Some(x) => x - 1,
+            None => s.len(),
+        };
+        let mut col = 0;
+        for (index, ch) in s[line_start..line_end].char_indices() {
+            if col >= pos.utf16_col {
+                return line_start + index;
+            }
+            col += ch.len_utf16() as u32;
+        }
+        line_end
+    }
+
+    pub(crate) fn position(&self, s: &str, index: usize) -> Position {
+        let line = self.line_starts.partition_point(|x| *x <= index) - 1;
+        let line_start = self.line_starts[line];
+        Position {
+            line: line as u32,
+            utf16_col: s[line_start..index].encode_utf16().count() as u32,
+        }
+    }
+}
+
+/// Shift the positions after an edited range.
+struct ShiftPositions {
+    old_end: Position,
+    new_end: Position,
+}
+
+impl ShiftPositions {
+    fn shift(&self, pos: &mut Position) {
+        if *pos < self.old_end {
+            return;
+        }
+        if pos.line == self.old_end.line {
+            pos.utf16_col = pos.utf16_col - self.old_end.utf16_col + self.new_end.utf16_col;
+        }
+        pos.line = pos.line - self.old_end.line + self.new_end.line;
+    }
+
+    fn shift_warnings(&self, warnings: &mut [ParseError]) {
+        for warning in warnings {
+            self.shift(&mut warning.location.start);
+            self.shift(&mut warning.location.end);
+        }
+    }
+}
+
+impl VisitMut for ShiftPositions {
+    fn visit_location(&mut self, x: &mut Range<Position>) {
+        self.shift(&mut x.start);
+        self.shift(&mut x.end);
+    }
+}
@@ -1,9 +1,14 @@
 use std::ops::Range;
 
+use compact_str::CompactString;
+
 use super::binding_map;
+pub use incremental::TextEdit;
+pub(crate) use incremental::{LineIndex, TemplateSource};
 use serde::{Deserialize, Serialize};
-pub use tag::Template;
+pub use tag::{Template, TEMPLATE_TREE_FORMAT_VERSION};
 
+mod incremental;
 pub mod iter;
 
 #[cfg(test)]
@@ -45,6 +50,9 @@ macro_rules! case {
 
 pub mod expr;
 pub mod tag;
+pub mod visit;
+pub mod visit_mut;
+pub mod visit_scope;
 
 pub(crate) const fn is_template_whitespace(c: char) -> bool {
     match c {
@@ -75,6 +83,30 @@ pub struct ParseState<'s> {
     utf16_col: u32,
     auto_skip_whitespace: Option<for<'ss> fn(&mut ParseState<'ss>) -> Option<Range<Position>>>,
     warnings: Vec<ParseError>,
+    probe: Option<Probe>,
+}
+
+/// The syntax context of a position, found by parsing with a probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ParseContext {
+    /// Inside `{{ }}` .
+    Expression,
+    /// In a start tag, where an attribute name is expected.
+    AttributeName,
+    /// Inside a quoted attribute value.
+    AttributeValue {
+        tag: CompactString,
+        attr: CompactString,
+    },
+}
+
+#[derive(Debug)]
+struct Probe {
+    position: Position,
+    /// The name of the start tag being parsed.
+    tag: CompactString,
+    /// The innermost context found so far.
+    context: Option<(Range<Position>, ParseContext)>,
 }
 
 impl<'s> ParseState<'s> {
@@ -97,9 +129,64 @@ impl<'s> ParseState<'s> {
             utf16_col: position_offset.utf16_col,
             auto_skip_whitespace: None,
             warnings: vec![],
+            probe: None,
         }
     }
 
+    /// Find out the syntax context of a position while parsing, see `probe_context` .
+    pub(crate) fn set_probe(&mut self, pos: Position) {
+        self.probe = Some(Probe {
+            position: pos,
+            tag: CompactString::new_inline(""),
+            context: None,
+        });
+    }
+
+    /// The innermost syntax context containing the probe position.
+    pub(crate) fn probe_context(&self) -> Option<&ParseContext> {
+        self.probe.as_ref()?.context.as_ref().map(|(_, x)| x)
+    }
+
+    pub(crate) fn probe_position(&self) -> Option<Position> {
+        self.probe.as_ref().map(|x| x.position)
+    }
+
+    /// Record the syntax context of a range (both ends included).
+    ///
+    /// It is ignored if the probe position is outside, or the range is not inside the recorded one.
+    pub(crate) fn probe(&mut self, range: Range<Position>, context: impl FnOnce() -> ParseContext) {
+        let Some(probe) = self.probe.as_mut() else {
+            return;
+        };
+        if probe.position < range.start || range.end < probe.position {
+            return;
+        }
+        if let Some((old, _)) = &probe.context {
+            if range.start < old.start || old.end < range.end {
+                return;
+            }
+        }
+        probe.context = Some((range, context()));
+    }
+
+    /// Record the start tag being parsed, which is used in `ParseContext::AttributeValue` .
+    pub(crate) fn probe_start_tag(&mut self, tag: &str) {
+        if let Some(probe) = self.probe.as_mut() {
+            probe.tag = tag.into();
+        }
+    }
+
+    /// Record the attribute value range of the current start tag.
+    pub(crate) fn probe_attribute_value(&mut self, range: Range<Position>, attr: &str) {
+        let Some(tag) = self.probe.as_ref().map(|x| x.tag.clone()) else {
+            return;
+        };
+        self.probe(range, || ParseContext::AttributeValue {
+            tag,
+            attr: attr.into(),
+        });
+    }
+
     /// Add a new warning.
     pub fn add_warning(&mut self, kind: ParseErrorKind, location: Range<Position>) {
         self.warnings.push(ParseError {
@@ -395,8 +482,23 @@ pub fn parse<'s>(path: &str, source: &'s str) -> (tag::Template, ParseState<'s>)
     (template, state)
 }
 
+/// Parse a template and find out the syntax context of a position.
+///
+/// The source can be incomplete, e.g. end inside an unclosed `{{` .
+pub(crate) fn parse_with_probe(
+    path: &str,
+    source: &str,
+    pos: Position,
+) -> (tag::Template, Option<ParseContext>) {
+    let mut state = ParseState::new(path, source, Default::default());
+    state.set_probe(pos);
+    let template = tag::Template::parse(&mut state);
+    (template, state.probe_context().cloned())
+}
+
 /// A location in source code.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub line: u32,
     pub utf16_col: u32,
@@ -521,6 +623,11 @@ pub enum ParseErrorKind {
     InvalidInlineStyleString,
     DuplicatedStylePropertyNames,
     IncompatibleWithStyleColonAttributes,
+    TemplateFileNotFound,
+    ScriptFileNotFound,
+    TemplateNameNotFound,
+    ShadowedTemplateName,
+    CircularInclude,
 }
 
 impl ParseErrorKind {
@@ -574,6 +681,13 @@ impl ParseErrorKind {
             Self::IncompatibleWithStyleColonAttributes => {
                 "style data bindings are incompatible with `style:` attributes"
             }
+            Self::TemplateFileNotFound => "the template file is not found in the group",
+            Self::ScriptFileNotFound => "the script file is not found in the group",
+            Self::TemplateNameNotFound => "no template with this name is defined or imported",
+            Self::ShadowedTemplateName => {
+                "this template name shadows another template with the same name"
+            }
+            Self::CircularInclude => "this include leads to a circular inclusion",
         }
     }
 
@@ -619,6 +733,11 @@ impl ParseErrorKind {
             Self::InvalidInlineStyleString => ParseErrorLevel::Error,
             Self::DuplicatedStylePropertyNames => ParseErrorLevel::Error,
             Self::IncompatibleWithStyleColonAttributes => ParseErrorLevel::Error,
+            Self::TemplateFileNotFound => ParseErrorLevel::Error,
+            Self::ScriptFileNotFound => ParseErrorLevel::Error,
+            Self::TemplateNameNotFound => ParseErrorLevel::Error,
+            Self::ShadowedTemplateName => ParseErrorLevel::Warn,
+            Self::CircularInclude => ParseErrorLevel::Error,
         }
     }
 }
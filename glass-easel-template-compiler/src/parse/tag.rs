@@ -7,31 +7,104 @@ use crate::escape::dash_to_camel;
 use super::{
     binding_map::{BindingMapCollector, BindingMapKeys},
     expr::Expression,
-    ParseErrorKind, ParseState, Position, TemplateStructure,
+    ParseContext, ParseErrorKind, ParseState, Position, TemplateStructure,
 };
 
 pub const DEFAULT_FOR_ITEM_SCOPE_NAME: &'static str = "item";
 pub const DEFAULT_FOR_INDEX_SCOPE_NAME: &'static str = "index";
 
+/// The version of the serialized template tree format.
+///
+/// It is increased whenever the serialized shape of `Template` changes.
+pub const TEMPLATE_TREE_FORMAT_VERSION: u32 = 1;
+
+/// Serialized as `TEMPLATE_TREE_FORMAT_VERSION` , and checked when deserialized.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TemplateTreeFormatVersion;
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TemplateTreeFormatVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(TEMPLATE_TREE_FORMAT_VERSION)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TemplateTreeFormatVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = u32::deserialize(deserializer)?;
+        if version != TEMPLATE_TREE_FORMAT_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported template tree format version {} (expected {})",
+                version, TEMPLATE_TREE_FORMAT_VERSION
+            )));
+        }
+        Ok(Self)
+    }
+}
+
+/// A parsed template tree.
+///
+/// With the `serde` feature enabled, the tree can be serialized and deserialized.
+/// The JSON shape is the default one in serde:
+///
+/// - a struct (or a struct-like enum variant) is an object with the field names as keys;
+/// - an enum value is externally tagged, i.e. `{ "VariantName": <content> }` ,
+///   while a unit variant is simply `"VariantName"` ;
+/// - a `Position` is `{ "line": <u32>, "utf16_col": <u32> }` and a location range is `{ "start": <Position>, "end": <Position> }` ;
+/// - a tuple (like `(Range<Position>, Range<Position>)` ) is an array.
+///
+/// The object also has a `version` field, which is `TEMPLATE_TREE_FORMAT_VERSION` .
+/// Deserializing a tree with a different version fails.
+///
+/// Binding map info, i.e. `TemplateGlobals::binding_map_collector` and `Value::Dynamic::binding_map_keys` ,
+/// is not included since it can be regenerated from the tree.
+/// A deserialized tree should be added with `TmplGroup::add_tmpl_tree` , which regenerates it.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct Template {
+    #[cfg_attr(feature = "serde", serde(rename = "version"))]
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub(crate) format_version: TemplateTreeFormatVersion,
     pub path: String,
     pub content: Vec<Node>,
     pub globals: TemplateGlobals,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct TemplateGlobals {
     pub imports: Vec<ImportElement>,
     pub includes: Vec<IncludeElement>,
     pub sub_templates: Vec<TemplateDefinition>,
     pub scripts: Vec<Script>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "BindingMapCollector::new"))]
     pub(crate) binding_map_collector: BindingMapCollector,
 }
 
+impl TemplateGlobals {
+    pub(super) fn new() -> Self {
+        Self {
+            imports: vec![],
+            includes: vec![],
+            sub_templates: vec![],
+            scripts: vec![],
+            binding_map_collector: BindingMapCollector::new(),
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.imports.is_empty()
+            && self.includes.is_empty()
+            && self.sub_templates.is_empty()
+            && self.scripts.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct ImportElement {
     pub tag_location: TagLocation,
@@ -40,6 +113,7 @@ pub struct ImportElement {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct IncludeElement {
     pub tag_location: TagLocation,
@@ -48,6 +122,7 @@ pub struct IncludeElement {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct TemplateDefinition {
     pub tag_location: TagLocation,
@@ -57,6 +132,7 @@ pub struct TemplateDefinition {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct TagLocation {
     pub start: (Range<Position>, Range<Position>),
@@ -72,13 +148,13 @@ struct ScopeAnalyzeState {
 
 impl Template {
     pub(super) fn parse(ps: &mut ParseState) -> Self {
-        let mut globals = TemplateGlobals {
-            imports: vec![],
-            includes: vec![],
-            sub_templates: vec![],
-            scripts: vec![],
-            binding_map_collector: BindingMapCollector::new(),
-        };
+        let mut template = Self::parse_nodes(ps);
+        template.init_scopes_and_binding_map_keys(ps);
+        template
+    }
+
+    pub(super) fn parse_nodes(ps: &mut ParseState) -> Self {
+        let mut globals = TemplateGlobals::new();
 
         // 1st round: parse the string into AST
         let mut content = vec![];
@@ -91,7 +167,25 @@ impl Template {
             }
         }
 
-        // 2nd round: traverse tree to alternate some details
+        Template {
+            format_version: TemplateTreeFormatVersion,
+            path: ps.path.to_string(),
+            content,
+            globals,
+        }
+    }
+
+    /// Traverse the parsed tree to alternate some details.
+    ///
+    /// It is safe to call it again on a processed tree,
+    /// but warnings of the processed parts will not be reported again.
+    pub(super) fn init_scopes_and_binding_map_keys(&mut self, ps: &mut ParseState) {
+        let Template {
+            format_version: _,
+            path: _,
+            content,
+            globals,
+        } = self;
         for sub in globals.sub_templates.iter_mut() {
             let mut sas = ScopeAnalyzeState {
                 scopes: globals
@@ -121,16 +215,18 @@ impl Template {
             inside_dynamic_tree: 0,
             binding_map_collector: BindingMapCollector::new(),
         };
-        for node in &mut content {
+        for node in content.iter_mut() {
             node.init_scopes_and_binding_map_keys(ps, &mut sas);
         }
         globals.binding_map_collector = sas.binding_map_collector;
+    }
 
-        Template {
-            path: ps.path.to_string(),
-            content,
-            globals,
-        }
+    /// Regenerate scopes and binding map info for a tree that is not freshly parsed.
+    ///
+    /// Warnings are ignored since they have already been reported when the tree was parsed.
+    pub(crate) fn reinit_scopes_and_binding_map_keys(&mut self) {
+        let mut ps = ParseState::new(&self.path, "", Position::default());
+        self.init_scopes_and_binding_map_keys(&mut ps);
     }
 
     pub fn global_scopes(&self) -> Vec<&StrName> {
@@ -258,6 +354,7 @@ impl Template {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Node {
     Text(Value),
@@ -267,6 +364,7 @@ pub enum Node {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct Comment {
     pub content: String,
@@ -283,6 +381,7 @@ impl Comment {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct UnknownMetaTag {
     pub tag_name: Vec<Ident>,
@@ -327,6 +426,13 @@ impl Node {
                     };
                     if is_meta {
                         let tag_name = Ident::parse_colon_separated(ps);
+                        ps.probe_start_tag(
+                            &tag_name
+                                .iter()
+                                .map(|x| x.name.as_str())
+                                .collect::<Vec<_>>()
+                                .join(":"),
+                        );
                         let attributes = CustomAttribute::parse_until_tag_end(ps);
                         if ps.consume_str(">").is_none() {
                             ps.add_warning(ParseErrorKind::IncompleteTag, range.clone());
@@ -372,6 +478,23 @@ impl Node {
         }
     }
 
+    /// Parse the element at the current position into `ret` .
+    ///
+    /// Returns false if the input does not start with an element.
+    pub(super) fn parse_element(
+        ps: &mut ParseState,
+        globals: &mut TemplateGlobals,
+        ret: &mut Vec<Node>,
+    ) -> bool {
+        match ps.peek_n() {
+            Some(['<', ch]) if Ident::is_start_char(ch) => {
+                Element::parse(ps, globals, ret);
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn init_scopes_and_binding_map_keys(
         &mut self,
         ps: &mut ParseState,
@@ -390,6 +513,7 @@ impl Node {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct Element {
     pub kind: ElementKind,
@@ -397,6 +521,7 @@ pub struct Element {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum ElementKind {
     #[non_exhaustive]
@@ -685,6 +810,7 @@ impl Element {
         // create an empty element
         let default_attr_position = tag_name.location.end;
         let tag_name_str = tag_name.name.as_str();
+        ps.probe_start_tag(tag_name_str);
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         enum ExternalTagKind {
             Include,
@@ -1931,6 +2057,14 @@ impl Element {
             }
         }
 
+        // an attribute name is expected anywhere in the start tag outside the values,
+        // except right after the tag name
+        if ps.probe_position() != Some(tag_name.location.end) {
+            ps.probe(tag_name.location.end..ps.position(), || {
+                ParseContext::AttributeName
+            });
+        }
+
         // end the start tag
         let (self_close_location, start_tag_end_location) = match ps.peek::<0>() {
             None => {
@@ -2785,6 +2919,7 @@ impl Element {
 }
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct CommonElementAttributes {
     pub id: Option<(Range<Position>, Value)>,
@@ -2849,6 +2984,7 @@ impl CommonElementAttributes {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CustomAttribute {
     pub colon_separated_name: Vec<Ident>,
     pub value: Option<Value>,
@@ -2895,6 +3031,7 @@ impl CustomAttribute {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NormalAttribute {
     pub name: Ident,
     pub value: Option<Value>,
@@ -2902,12 +3039,14 @@ pub struct NormalAttribute {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NormalAttributePrefix {
     None,
     Model(Range<Position>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct Attribute {
     pub name: Ident,
@@ -2919,6 +3058,7 @@ impl Attribute {
     #[inline(always)]
     fn parse_optional_value_part<R>(
         ps: &mut ParseState,
+        name: &Ident,
         quoted_parser: impl FnOnce(&mut ParseState, char) -> R,
         expression_parser: impl FnOnce(&mut ParseState) -> Option<R>,
         str_name: impl FnOnce(StrName) -> R,
@@ -2937,7 +3077,9 @@ impl Attribute {
                         ps.add_warning(ParseErrorKind::UnexpectedWhitespace, range);
                     }
                     ps.next(); // ch
+                    let value_start = ps.position();
                     let value = quoted_parser(ps, ch);
+                    ps.probe_attribute_value(value_start..ps.position(), &name.name);
                     ps.next(); // ch
                     value
                 }
@@ -2974,6 +3116,7 @@ impl Attribute {
         let mut is_value_unspecified = false;
         let value = Attribute::parse_optional_value_part(
             ps,
+            &name,
             |ps, ch| {
                 Value::parse_until_before(ps, |ps: &mut ParseState| ps.peek::<0>() == Some(ch))
             },
@@ -3004,6 +3147,7 @@ impl Attribute {
         let mut is_value_unspecified = false;
         let value = Attribute::parse_optional_value_part(
             ps,
+            &name,
             |ps, ch| {
                 let v = ps.try_parse(|ps| {
                     let v = Value::parse_data_binding(ps, true)?;
@@ -3041,6 +3185,7 @@ impl Attribute {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct StaticAttribute {
     pub name: Ident,
@@ -3053,6 +3198,7 @@ impl StaticAttribute {
     pub fn parse_optional_value(ps: &mut ParseState, name: Ident) -> Option<Self> {
         let value = Attribute::parse_optional_value_part(
             ps,
+            &name,
             |ps, ch| {
                 StrName::parse_until_before(ps, |ps: &mut ParseState| ps.peek::<0>() == Some(ch))
             },
@@ -3073,6 +3219,7 @@ impl StaticAttribute {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum ClassAttribute {
     None,
@@ -3081,6 +3228,7 @@ pub enum ClassAttribute {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum StyleAttribute {
     None,
@@ -3089,6 +3237,7 @@ pub enum StyleAttribute {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct EventBinding {
     pub name: Ident,
@@ -3105,6 +3254,7 @@ pub struct EventBinding {
 /// Unlike JavaScript identifier, it can contain `-` .
 ///
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct Ident {
     pub name: CompactString,
@@ -3221,6 +3371,7 @@ impl Ident {
 
 /// A static string with location information.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub struct StrName {
     pub name: CompactString,
@@ -3415,6 +3566,7 @@ impl StrName {
 
 /// A static string or an expression.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Value {
     #[non_exhaustive]
@@ -3426,6 +3578,7 @@ pub enum Value {
     Dynamic {
         expression: Box<Expression>,
         double_brace_location: (Range<Position>, Range<Position>),
+        #[cfg_attr(feature = "serde", serde(skip))]
         binding_map_keys: Option<BindingMapKeys>,
     },
 }
@@ -3478,6 +3631,9 @@ impl Value {
             ps.skip_whitespace_with_js_comments();
             ps.consume_str("}}")
         }) {
+            ps.probe(double_brace_left.end..range.start, || {
+                ParseContext::Expression
+            });
             ps.add_warning(ParseErrorKind::EmptyExpression, range.start..range.start);
             return Some(Self::Static {
                 value: CompactString::new_inline(""),
@@ -3486,7 +3642,13 @@ impl Value {
         }
         let Some(expression) = Expression::parse_expression_or_object_inner(ps, is_template_data)
         else {
-            if ps.skip_until_after("}}").is_none() {
+            let found = ps.skip_until_before("}}");
+            ps.probe(double_brace_left.end..ps.position(), || {
+                ParseContext::Expression
+            });
+            if found.is_some() {
+                ps.consume_str("}}");
+            } else {
                 ps.add_warning(
                     ParseErrorKind::MissingExpressionEnd,
                     double_brace_left.clone(),
@@ -3499,7 +3661,11 @@ impl Value {
         };
         ps.skip_whitespace();
         let end_pos = ps.position();
-        match ps.skip_until_before("}}") {
+        let found = ps.skip_until_before("}}");
+        ps.probe(double_brace_left.end..ps.position(), || {
+            ParseContext::Expression
+        });
+        match found {
             None => {
                 ps.add_warning(
                     ParseErrorKind::MissingExpressionEnd,
@@ -3728,6 +3894,7 @@ impl Value {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Script {
     #[non_exhaustive]
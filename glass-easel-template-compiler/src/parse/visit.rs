@@ -0,0 +1,879 @@
+//! Recursive traversal of the template tree.
+//!
+//! Implement `Visit` and override the `visit_*` methods for the interesting parts of the tree.
+//! Each default `visit_*` method calls the corresponding `walk_*` function,
+//! which visits all the sub-structures in the source order as far as possible.
+//! When overriding, call the `walk_*` function to continue visiting the sub-structures.
+//!
+//! The `VisitMut` trait in `visit_mut` module is the same but visits mutable references.
+//! The `visit_scope` module visits the tree with the scope variables declared in order.
+//!
+//! Every location range in the tree is passed to `visit_location` ,
+//! so a visitor can collect or update all positions without knowing the tree structure.
+
+use std::ops::Range;
+
+use super::{
+    expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
+    tag::{
+        Attribute, ClassAttribute, Comment, CommonElementAttributes, CustomAttribute, Element,
+        ElementKind, EventBinding, Ident, ImportElement, IncludeElement, Node, NormalAttribute,
+        NormalAttributePrefix, Script, StaticAttribute, StrName, StyleAttribute, TagLocation,
+        Template, TemplateDefinition, TemplateGlobals, UnknownMetaTag, Value,
+    },
+    Position,
+};
+
+macro_rules! define_visitor {
+    ($visit:ident, [$($lt:lifetime)?], $sub_expressions:ident, $($mut:tt)?) => {
+        pub trait $visit $(<$lt>)? {
+            fn visit_template(&mut self, x: & $($lt)? $($mut)? Template) {
+                walk_template(self, x)
+            }
+
+            fn visit_template_globals(&mut self, x: & $($lt)? $($mut)? TemplateGlobals) {
+                walk_template_globals(self, x)
+            }
+
+            fn visit_import_element(&mut self, x: & $($lt)? $($mut)? ImportElement) {
+                walk_import_element(self, x)
+            }
+
+            fn visit_include_element(&mut self, x: & $($lt)? $($mut)? IncludeElement) {
+                walk_include_element(self, x)
+            }
+
+            fn visit_template_definition(&mut self, x: & $($lt)? $($mut)? TemplateDefinition) {
+                walk_template_definition(self, x)
+            }
+
+            fn visit_script(&mut self, x: & $($lt)? $($mut)? Script) {
+                walk_script(self, x)
+            }
+
+            fn visit_node(&mut self, x: & $($lt)? $($mut)? Node) {
+                walk_node(self, x)
+            }
+
+            fn visit_element(&mut self, x: & $($lt)? $($mut)? Element) {
+                walk_element(self, x)
+            }
+
+            fn visit_comment(&mut self, x: & $($lt)? $($mut)? Comment) {
+                walk_comment(self, x)
+            }
+
+            fn visit_unknown_meta_tag(&mut self, x: & $($lt)? $($mut)? UnknownMetaTag) {
+                walk_unknown_meta_tag(self, x)
+            }
+
+            fn visit_custom_attribute(&mut self, x: & $($lt)? $($mut)? CustomAttribute) {
+                walk_custom_attribute(self, x)
+            }
+
+            fn visit_normal_attribute(&mut self, x: & $($lt)? $($mut)? NormalAttribute) {
+                walk_normal_attribute(self, x)
+            }
+
+            fn visit_attribute(&mut self, x: & $($lt)? $($mut)? Attribute) {
+                walk_attribute(self, x)
+            }
+
+            fn visit_static_attribute(&mut self, x: & $($lt)? $($mut)? StaticAttribute) {
+                walk_static_attribute(self, x)
+            }
+
+            fn visit_class_attribute(&mut self, x: & $($lt)? $($mut)? ClassAttribute) {
+                walk_class_attribute(self, x)
+            }
+
+            fn visit_style_attribute(&mut self, x: & $($lt)? $($mut)? StyleAttribute) {
+                walk_style_attribute(self, x)
+            }
+
+            fn visit_event_binding(&mut self, x: & $($lt)? $($mut)? EventBinding) {
+                walk_event_binding(self, x)
+            }
+
+            fn visit_common_element_attributes(
+                &mut self,
+                x: & $($lt)? $($mut)? CommonElementAttributes,
+            ) {
+                walk_common_element_attributes(self, x)
+            }
+
+            fn visit_ident(&mut self, x: & $($lt)? $($mut)? Ident) {
+                walk_ident(self, x)
+            }
+
+            fn visit_str_name(&mut self, x: & $($lt)? $($mut)? StrName) {
+                walk_str_name(self, x)
+            }
+
+            fn visit_value(&mut self, x: & $($lt)? $($mut)? Value) {
+                walk_value(self, x)
+            }
+
+            fn visit_expression(&mut self, x: & $($lt)? $($mut)? Expression) {
+                walk_expression(self, x)
+            }
+
+            fn visit_tag_location(&mut self, x: & $($lt)? $($mut)? TagLocation) {
+                walk_tag_location(self, x)
+            }
+
+            fn visit_location(&mut self, _x: & $($lt)? $($mut)? Range<Position>) {}
+        }
+
+        pub fn walk_template<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Template,
+        ) {
+            let Template {
+                format_version: _,
+                path: _,
+                content,
+                globals,
+            } = x;
+            v.visit_template_globals(globals);
+            for node in content {
+                v.visit_node(node);
+            }
+        }
+
+        pub fn walk_template_globals<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? TemplateGlobals,
+        ) {
+            let TemplateGlobals {
+                imports,
+                includes,
+                sub_templates,
+                scripts,
+                binding_map_collector: _,
+            } = x;
+            for import in imports {
+                v.visit_import_element(import);
+            }
+            for include in includes {
+                v.visit_include_element(include);
+            }
+            for sub_template in sub_templates {
+                v.visit_template_definition(sub_template);
+            }
+            for script in scripts {
+                v.visit_script(script);
+            }
+        }
+
+        pub fn walk_import_element<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? ImportElement,
+        ) {
+            let ImportElement {
+                tag_location,
+                src_location,
+                src,
+            } = x;
+            v.visit_tag_location(tag_location);
+            v.visit_location(src_location);
+            v.visit_str_name(src);
+        }
+
+        pub fn walk_include_element<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? IncludeElement,
+        ) {
+            let IncludeElement {
+                tag_location,
+                src_location,
+                src,
+            } = x;
+            v.visit_tag_location(tag_location);
+            v.visit_location(src_location);
+            v.visit_str_name(src);
+        }
+
+        pub fn walk_template_definition<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? TemplateDefinition,
+        ) {
+            let TemplateDefinition {
+                tag_location,
+                name_location,
+                name,
+                content,
+            } = x;
+            v.visit_tag_location(tag_location);
+            v.visit_location(name_location);
+            v.visit_str_name(name);
+            for node in content {
+                v.visit_node(node);
+            }
+        }
+
+        pub fn walk_script<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Script,
+        ) {
+            match x {
+                Script::Inline {
+                    tag_location,
+                    module_location,
+                    module_name,
+                    content: _,
+                    content_location,
+                } => {
+                    v.visit_tag_location(tag_location);
+                    v.visit_location(module_location);
+                    v.visit_str_name(module_name);
+                    v.visit_location(content_location);
+                }
+                Script::GlobalRef {
+                    tag_location,
+                    module_location,
+                    module_name,
+                    src_location,
+                    src,
+                } => {
+                    v.visit_tag_location(tag_location);
+                    v.visit_location(module_location);
+                    v.visit_str_name(module_name);
+                    v.visit_location(src_location);
+                    v.visit_str_name(src);
+                }
+            }
+        }
+
+        pub fn walk_node<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Node,
+        ) {
+            match x {
+                Node::Text(x) => v.visit_value(x),
+                Node::Element(x) => v.visit_element(x),
+                Node::Comment(x) => v.visit_comment(x),
+                Node::UnknownMetaTag(x) => v.visit_unknown_meta_tag(x),
+            }
+        }
+
+        pub fn walk_element<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Element,
+        ) {
+            let Element { kind, tag_location } = x;
+            v.visit_tag_location(tag_location);
+            match kind {
+                ElementKind::Normal {
+                    tag_name,
+                    attributes,
+                    class,
+                    style,
+                    change_attributes,
+                    worklet_attributes,
+                    children,
+                    generics,
+                    extra_attr,
+                    let_vars,
+                    common,
+                } => {
+                    v.visit_ident(tag_name);
+                    for attr in attributes {
+                        v.visit_normal_attribute(attr);
+                    }
+                    v.visit_class_attribute(class);
+                    v.visit_style_attribute(style);
+                    for attr in change_attributes {
+                        v.visit_attribute(attr);
+                    }
+                    for attr in worklet_attributes {
+                        v.visit_static_attribute(attr);
+                    }
+                    for attr in generics {
+                        v.visit_static_attribute(attr);
+                    }
+                    for attr in extra_attr {
+                        v.visit_static_attribute(attr);
+                    }
+                    for attr in let_vars {
+                        v.visit_attribute(attr);
+                    }
+                    v.visit_common_element_attributes(common);
+                    for node in children {
+                        v.visit_node(node);
+                    }
+                }
+                ElementKind::Pure {
+                    children,
+                    let_vars,
+                    slot,
+                    slot_value_refs,
+                } => {
+                    for attr in let_vars {
+                        v.visit_attribute(attr);
+                    }
+                    if let Some((location, value)) = slot {
+                        v.visit_location(location);
+                        v.visit_value(value);
+                    }
+                    for attr in slot_value_refs {
+                        v.visit_static_attribute(attr);
+                    }
+                    for node in children {
+                        v.visit_node(node);
+                    }
+                }
+                ElementKind::For {
+                    list,
+                    item_name,
+                    index_name,
+                    key,
+                    children,
+                } => {
+                    v.visit_location(& $($mut)? list.0);
+                    v.visit_value(& $($mut)? list.1);
+                    v.visit_location(& $($mut)? item_name.0);
+                    v.visit_str_name(& $($mut)? item_name.1);
+                    v.visit_location(& $($mut)? index_name.0);
+                    v.visit_str_name(& $($mut)? index_name.1);
+                    v.visit_location(& $($mut)? key.0);
+                    v.visit_str_name(& $($mut)? key.1);
+                    for node in children {
+                        v.visit_node(node);
+                    }
+                }
+                ElementKind::If {
+                    branches,
+                    else_branch,
+                } => {
+                    for (location, value, children) in branches {
+                        v.visit_location(location);
+                        v.visit_value(value);
+                        for node in children {
+                            v.visit_node(node);
+                        }
+                    }
+                    if let Some((location, children)) = else_branch {
+                        v.visit_location(location);
+                        for node in children {
+                            v.visit_node(node);
+                        }
+                    }
+                }
+                ElementKind::TemplateRef { target, data } => {
+                    v.visit_location(& $($mut)? target.0);
+                    v.visit_value(& $($mut)? target.1);
+                    v.visit_location(& $($mut)? data.0);
+                    v.visit_value(& $($mut)? data.1);
+                }
+                ElementKind::Include { path } => {
+                    v.visit_location(& $($mut)? path.0);
+                    v.visit_str_name(& $($mut)? path.1);
+                }
+                ElementKind::Slot {
+                    name,
+                    values,
+                    common,
+                } => {
+                    v.visit_location(& $($mut)? name.0);
+                    v.visit_value(& $($mut)? name.1);
+                    for attr in values {
+                        v.visit_attribute(attr);
+                    }
+                    v.visit_common_element_attributes(common);
+                }
+            }
+        }
+
+        pub fn walk_comment<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Comment,
+        ) {
+            let Comment {
+                content: _,
+                location,
+            } = x;
+            v.visit_location(location);
+        }
+
+        pub fn walk_unknown_meta_tag<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? UnknownMetaTag,
+        ) {
+            let UnknownMetaTag {
+                tag_name,
+                attributes,
+                location,
+            } = x;
+            v.visit_location(location);
+            for ident in tag_name {
+                v.visit_ident(ident);
+            }
+            for attr in attributes {
+                v.visit_custom_attribute(attr);
+            }
+        }
+
+        pub fn walk_custom_attribute<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? CustomAttribute,
+        ) {
+            let CustomAttribute {
+                colon_separated_name,
+                value,
+            } = x;
+            for ident in colon_separated_name {
+                v.visit_ident(ident);
+            }
+            if let Some(value) = value {
+                v.visit_value(value);
+            }
+        }
+
+        pub fn walk_normal_attribute<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? NormalAttribute,
+        ) {
+            let NormalAttribute {
+                name,
+                value,
+                prefix,
+            } = x;
+            match prefix {
+                NormalAttributePrefix::None => {}
+                NormalAttributePrefix::Model(location) => v.visit_location(location),
+            }
+            v.visit_ident(name);
+            if let Some(value) = value {
+                v.visit_value(value);
+            }
+        }
+
+        pub fn walk_attribute<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Attribute,
+        ) {
+            let Attribute {
+                name,
+                value,
+                prefix_location,
+            } = x;
+            if let Some(location) = prefix_location {
+                v.visit_location(location);
+            }
+            v.visit_ident(name);
+            if let Some(value) = value {
+                v.visit_value(value);
+            }
+        }
+
+        pub fn walk_static_attribute<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? StaticAttribute,
+        ) {
+            let StaticAttribute {
+                name,
+                value,
+                prefix_location,
+            } = x;
+            if let Some(location) = prefix_location {
+                v.visit_location(location);
+            }
+            v.visit_ident(name);
+            v.visit_str_name(value);
+        }
+
+        pub fn walk_class_attribute<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? ClassAttribute,
+        ) {
+            match x {
+                ClassAttribute::None => {}
+                ClassAttribute::String(location, value) => {
+                    v.visit_location(location);
+                    v.visit_value(value);
+                }
+                ClassAttribute::Multiple(list) => {
+                    for (location, name, value) in list {
+                        v.visit_location(location);
+                        v.visit_ident(name);
+                        if let Some(value) = value {
+                            v.visit_value(value);
+                        }
+                    }
+                }
+            }
+        }
+
+        pub fn walk_style_attribute<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? StyleAttribute,
+        ) {
+            match x {
+                StyleAttribute::None => {}
+                StyleAttribute::String(location, value) => {
+                    v.visit_location(location);
+                    v.visit_value(value);
+                }
+                StyleAttribute::Multiple(list) => {
+                    for (location, name, value) in list {
+                        v.visit_location(location);
+                        v.visit_ident(name);
+                        v.visit_value(value);
+                    }
+                }
+            }
+        }
+
+        pub fn walk_event_binding<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? EventBinding,
+        ) {
+            let EventBinding {
+                name,
+                value,
+                is_catch: _,
+                is_mut: _,
+                is_capture: _,
+                prefix_location,
+            } = x;
+            v.visit_location(prefix_location);
+            v.visit_ident(name);
+            if let Some(value) = value {
+                v.visit_value(value);
+            }
+        }
+
+        pub fn walk_common_element_attributes<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? CommonElementAttributes,
+        ) {
+            let CommonElementAttributes {
+                id,
+                slot,
+                slot_value_refs,
+                event_bindings,
+                data,
+                marks,
+            } = x;
+            if let Some((location, value)) = id {
+                v.visit_location(location);
+                v.visit_value(value);
+            }
+            if let Some((location, value)) = slot {
+                v.visit_location(location);
+                v.visit_value(value);
+            }
+            for attr in slot_value_refs {
+                v.visit_static_attribute(attr);
+            }
+            for ev in event_bindings {
+                v.visit_event_binding(ev);
+            }
+            for attr in data {
+                v.visit_attribute(attr);
+            }
+            for attr in marks {
+                v.visit_attribute(attr);
+            }
+        }
+
+        pub fn walk_value<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Value,
+        ) {
+            match x {
+                Value::Static { value: _, location } => {
+                    v.visit_location(location);
+                }
+                Value::Dynamic {
+                    expression,
+                    double_brace_location,
+                    binding_map_keys: _,
+                } => {
+                    v.visit_location(& $($mut)? double_brace_location.0);
+                    v.visit_expression(expression);
+                    v.visit_location(& $($mut)? double_brace_location.1);
+                }
+            }
+        }
+
+        pub fn walk_expression<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Expression,
+        ) {
+            match x {
+                Expression::LitObj {
+                    fields,
+                    brace_location,
+                } => {
+                    v.visit_location(& $($mut)? brace_location.0);
+                    for field in fields {
+                        match field {
+                            ObjectFieldKind::Named {
+                                location,
+                                colon_location,
+                                ..
+                            } => {
+                                v.visit_location(location);
+                                if let Some(location) = colon_location {
+                                    v.visit_location(location);
+                                }
+                            }
+                            ObjectFieldKind::Spread { location, .. } => {
+                                v.visit_location(location);
+                            }
+                        }
+                    }
+                    v.visit_location(& $($mut)? brace_location.1);
+                }
+                Expression::LitArr {
+                    fields,
+                    bracket_location,
+                } => {
+                    v.visit_location(& $($mut)? bracket_location.0);
+                    for field in fields {
+                        match field {
+                            ArrayFieldKind::Spread { location, .. } => {
+                                v.visit_location(location);
+                            }
+                            ArrayFieldKind::Normal { .. } | ArrayFieldKind::EmptySlot => {}
+                        }
+                    }
+                    v.visit_location(& $($mut)? bracket_location.1);
+                }
+                Expression::LitTemplateStr {
+                    parts,
+                    backtick_location,
+                } => {
+                    v.visit_location(& $($mut)? backtick_location.0);
+                    for part in parts {
+                        match part {
+                            TemplateStrPart::Static { location, .. } => {
+                                v.visit_location(location);
+                            }
+                            TemplateStrPart::Dynamic { brace_location, .. } => {
+                                v.visit_location(& $($mut)? brace_location.0);
+                                v.visit_location(& $($mut)? brace_location.1);
+                            }
+                        }
+                    }
+                    v.visit_location(& $($mut)? backtick_location.1);
+                }
+                Expression::StaticMember {
+                    dot_location,
+                    field_location,
+                    ..
+                } => {
+                    v.visit_location(dot_location);
+                    v.visit_location(field_location);
+                }
+                Expression::DynamicMember {
+                    bracket_location, ..
+                }
+                | Expression::FuncCall {
+                    paren_location: bracket_location,
+                    ..
+                } => {
+                    v.visit_location(& $($mut)? bracket_location.0);
+                    v.visit_location(& $($mut)? bracket_location.1);
+                }
+                Expression::OptionalStaticMember {
+                    question_dot_location,
+                    field_location,
+                    ..
+                } => {
+                    v.visit_location(question_dot_location);
+                    v.visit_location(field_location);
+                }
+                Expression::OptionalDynamicMember {
+                    question_dot_location,
+                    bracket_location,
+                    ..
+                }
+                | Expression::OptionalFuncCall {
+                    question_dot_location,
+                    paren_location: bracket_location,
+                    ..
+                } => {
+                    v.visit_location(question_dot_location);
+                    v.visit_location(& $($mut)? bracket_location.0);
+                    v.visit_location(& $($mut)? bracket_location.1);
+                }
+                Expression::Cond {
+                    question_location,
+                    colon_location,
+                    ..
+                } => {
+                    v.visit_location(question_location);
+                    v.visit_location(colon_location);
+                }
+                Expression::ScopeRef { location, .. }
+                | Expression::DataField { location, .. }
+                | Expression::ToStringWithoutUndefined { location, .. }
+                | Expression::LitUndefined { location }
+                | Expression::LitNull { location }
+                | Expression::LitStr { location, .. }
+                | Expression::LitInt { location, .. }
+                | Expression::LitFloat { location, .. }
+                | Expression::LitBool { location, .. }
+                | Expression::Reverse { location, .. }
+                | Expression::BitReverse { location, .. }
+                | Expression::Positive { location, .. }
+                | Expression::Negative { location, .. }
+                | Expression::TypeOf { location, .. }
+                | Expression::Void { location, .. }
+                | Expression::Multiply { location, .. }
+                | Expression::Divide { location, .. }
+                | Expression::Remainer { location, .. }
+                | Expression::Plus { location, .. }
+                | Expression::Minus { location, .. }
+                | Expression::LeftShift { location, .. }
+                | Expression::RightShift { location, .. }
+                | Expression::UnsignedRightShift { location, .. }
+                | Expression::Lt { location, .. }
+                | Expression::Gt { location, .. }
+                | Expression::Lte { location, .. }
+                | Expression::Gte { location, .. }
+                | Expression::InstanceOf { location, .. }
+                | Expression::Eq { location, .. }
+                | Expression::Ne { location, .. }
+                | Expression::EqFull { location, .. }
+                | Expression::NeFull { location, .. }
+                | Expression::BitAnd { location, .. }
+                | Expression::BitXor { location, .. }
+                | Expression::BitOr { location, .. }
+                | Expression::LogicAnd { location, .. }
+                | Expression::LogicOr { location, .. }
+                | Expression::NullishCoalescing { location, .. } => {
+                    v.visit_location(location);
+                }
+            }
+            for sub in x.$sub_expressions() {
+                v.visit_expression(sub);
+            }
+        }
+
+        pub fn walk_ident<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? Ident,
+        ) {
+            v.visit_location(& $($mut)? x.location);
+        }
+
+        pub fn walk_str_name<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? StrName,
+        ) {
+            v.visit_location(& $($mut)? x.location);
+        }
+
+        pub fn walk_tag_location<$($lt,)? V: $visit $(<$lt>)? + ?Sized>(
+            v: &mut V,
+            x: & $($lt)? $($mut)? TagLocation,
+        ) {
+            let TagLocation { start, close, end } = x;
+            v.visit_location(& $($mut)? start.0);
+            v.visit_location(& $($mut)? start.1);
+            v.visit_location(close);
+            if let Some(end) = end {
+                v.visit_location(& $($mut)? end.0);
+                v.visit_location(& $($mut)? end.1);
+            }
+        }
+    };
+}
+
+pub(super) use define_visitor;
+
+define_visitor!(Visit, ['a], sub_expressions,);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::visit_mut::{self, VisitMut};
+
+    const SRC: &str = r#"
+        <import src="a" />
+        <include src="b" />
+        <wxs module="c" src="c" />
+        <template name="d">{{ d1 }}</template>
+        <div id="{{ e1 }}" slot="{{ e2 }}" class="{{ e3 }}" style="{{ e4 }}" attr="{{ e5 }}" change:attr="{{ e6 }}" data:a="{{ e7 }}" mark:a="{{ e8 }}" bind:tap="{{ e9 }}" let:v="{{ e10 }}">
+            <view class:a="{{ f1 }}" style:color="{{ f2 }}" model:value="{{ f3 }}" />
+            <block wx:for="{{ g1 }}">{{ g2 + item }}</block>
+            <block wx:if="{{ h1 }}">{{ h2 }}</block>
+            <block wx:elif="{{ h3 }}">{{ h4 }}</block>
+            <block wx:else>{{ h5 }}</block>
+            <template is="{{ i1 }}" data="{{ i2 }}" />
+            <slot name="{{ j1 }}" v="{{ j2 }}" />
+            <block slot="{{ k1 }}" let:v="{{ k2 }}">{{ `${ k3 }` }}</block>
+        </div>
+    "#;
+
+    #[test]
+    fn visit_all_data_fields() {
+        struct DataFields<'a>(Vec<&'a str>);
+        impl<'a> Visit<'a> for DataFields<'a> {
+            fn visit_expression(&mut self, x: &'a Expression) {
+                if let Expression::DataField { name, .. } = x {
+                    self.0.push(name);
+                }
+                walk_expression(self, x);
+            }
+        }
+        let (template, _) = crate::parse::parse("TEST", SRC);
+        let mut v = DataFields(vec![]);
+        v.visit_template(&template);
+        assert_eq!(
+            v.0,
+            [
+                "d1", "e5", "e3", "e4", "e6", "e10", "e1", "e2", "e9", "e7", "e8", "f3", "f1",
+                "f2", "g1", "g2", "h1", "h2", "h3", "h4", "h5", "i1", "i2", "j1", "j2", "k2", "k1",
+                "k3",
+            ]
+        );
+    }
+
+    #[test]
+    fn visit_mut_data_fields() {
+        struct Rename;
+        impl VisitMut for Rename {
+            fn visit_expression(&mut self, x: &mut Expression) {
+                if let Expression::DataField { name, .. } = x {
+                    name.push('_');
+                }
+                visit_mut::walk_expression(self, x);
+            }
+        }
+        struct Count(usize);
+        impl<'a> Visit<'a> for Count {
+            fn visit_expression(&mut self, x: &'a Expression) {
+                if let Expression::DataField { name, .. } = x {
+                    assert!(name.ends_with('_'));
+                    self.0 += 1;
+                }
+                walk_expression(self, x);
+            }
+        }
+        let (mut template, _) = crate::parse::parse("TEST", SRC);
+        Rename.visit_template(&mut template);
+        let mut count = Count(0);
+        count.visit_template(&template);
+        assert_eq!(count.0, 28);
+    }
+
+    #[test]
+    fn visit_names() {
+        #[derive(Default)]
+        struct Names(Vec<String>);
+        impl<'a> Visit<'a> for Names {
+            fn visit_str_name(&mut self, x: &'a StrName) {
+                self.0.push(x.name.to_string());
+            }
+        }
+        let (template, _) = crate::parse::parse("TEST", SRC);
+        let mut v = Names::default();
+        v.visit_template(&template);
+        assert_eq!(v.0, ["a", "b", "d", "c", "c", "b", "item", "index", ""]);
+    }
+}
@@ -0,0 +1,19 @@
+//! Recursive traversal of the template tree with mutable references.
+//!
+//! See `visit` module for details.
+
+use std::ops::Range;
+
+use super::{
+    expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
+    tag::{
+        Attribute, ClassAttribute, Comment, CommonElementAttributes, CustomAttribute, Element,
+        ElementKind, EventBinding, Ident, ImportElement, IncludeElement, Node, NormalAttribute,
+        NormalAttributePrefix, Script, StaticAttribute, StrName, StyleAttribute, TagLocation,
+        Template, TemplateDefinition, TemplateGlobals, UnknownMetaTag, Value,
+    },
+    visit::define_visitor,
+    Position,
+};
+
+define_visitor!(VisitMut, [], sub_expressions_mut, mut);
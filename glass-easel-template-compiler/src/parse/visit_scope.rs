@@ -0,0 +1,266 @@
+//! Recursive traversal of the template tree with the scope variables.
+//!
+//! The scope variables are the `<wxs>` modules, the `slot:` values, the `let:` variables,
+//! and the items and indices of `wx:for` .
+//! Implement `ScopeVisit` to be told when each of them is declared and when they are out of range.
+//! Call `walk_template` and `walk_element` in this module in `visit_template` and `visit_element` ,
+//! instead of the ones in `visit` module.
+//!
+//! A scope variable is always declared before the values which can read it,
+//! so the scope variables in range are the ones indexed by `Expression::ScopeRef` .
+
+use std::ops::Range;
+
+use super::{
+    tag::{Attribute, Element, ElementKind, Script, StaticAttribute, StrName, Template, Value},
+    visit::{self, Visit},
+    Position,
+};
+
+/// A scope variable declaration.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope<'a> {
+    /// A `<wxs>` module.
+    Script(&'a Script),
+    /// A `slot:` value.
+    SlotValue(&'a StaticAttribute),
+    /// A `let:` variable.
+    LetVar(&'a Attribute),
+    /// The `wx:for-item` .
+    ForItem {
+        list: &'a (Range<Position>, Value),
+        name: &'a (Range<Position>, StrName),
+    },
+    /// The `wx:for-index` .
+    ForIndex {
+        list: &'a (Range<Position>, Value),
+        name: &'a (Range<Position>, StrName),
+    },
+}
+
+impl<'a> Scope<'a> {
+    /// The variable name.
+    pub fn name(&self) -> &'a str {
+        match *self {
+            Self::Script(x) => x.module_name().name.as_str(),
+            Self::SlotValue(x) => x.value.name.as_str(),
+            Self::LetVar(x) => x.name.name.as_str(),
+            Self::ForItem { name, .. } | Self::ForIndex { name, .. } => name.1.name.as_str(),
+        }
+    }
+
+    /// The location of the variable name.
+    ///
+    /// The default `wx:for-item` and `wx:for-index` share the location of `wx:for` .
+    pub fn location(&self) -> &'a Range<Position> {
+        match *self {
+            Self::Script(x) => &x.module_name().location,
+            Self::SlotValue(x) => &x.value.location,
+            Self::LetVar(x) => &x.name.location,
+            Self::ForItem { name, .. } | Self::ForIndex { name, .. } => &name.1.location,
+        }
+    }
+
+    /// Whether the name is not written by itself, i.e. `slot:a` or the default `wx:for-item` and `wx:for-index` .
+    pub fn is_implicit(&self) -> bool {
+        match *self {
+            Self::Script(_) | Self::LetVar(_) => false,
+            Self::SlotValue(x) => x.value.location == x.name.location,
+            Self::ForItem { list, name } | Self::ForIndex { list, name } => name.0 == list.0,
+        }
+    }
+}
+
+pub trait ScopeVisit<'a>: Visit<'a> {
+    /// Called when a scope variable is declared.
+    fn declare_scope(&mut self, scope: Scope<'a>);
+
+    /// Called when the last `count` declared scope variables are out of range.
+    fn end_scopes(&mut self, count: usize);
+}
+
+/// Visit a template file, in which the `<wxs>` modules are declared.
+pub fn walk_template<'a, V: ScopeVisit<'a> + ?Sized>(v: &mut V, x: &'a Template) {
+    let scripts = &x.globals.scripts;
+    for script in scripts {
+        v.declare_scope(Scope::Script(script));
+    }
+    visit::walk_template(v, x);
+    v.end_scopes(scripts.len());
+}
+
+/// Visit an element, in which its scope variables are declared.
+///
+/// The `slot:` values are declared first.
+/// Each `let:` variable is declared after its value, so a `let:` value can only read the ones before it.
+/// The `wx:for` item and index are declared after the list.
+pub fn walk_element<'a, V: ScopeVisit<'a> + ?Sized>(v: &mut V, x: &'a Element) {
+    let mut count = 0;
+    if let Some(refs) = x.slot_value_refs() {
+        for attr in refs {
+            v.declare_scope(Scope::SlotValue(attr));
+            count += 1;
+        }
+    }
+    let mut let_vars = |v: &mut V, list: &'a [Attribute]| {
+        for attr in list {
+            v.visit_attribute(attr);
+            v.declare_scope(Scope::LetVar(attr));
+            count += 1;
+        }
+    };
+    match &x.kind {
+        ElementKind::Normal {
+            tag_name,
+            attributes,
+            class,
+            style,
+            change_attributes,
+            worklet_attributes,
+            children,
+            generics,
+            extra_attr,
+            let_vars: list,
+            common,
+        } => {
+            v.visit_tag_location(&x.tag_location);
+            let_vars(v, list);
+            v.visit_ident(tag_name);
+            for attr in attributes {
+                v.visit_normal_attribute(attr);
+            }
+            v.visit_class_attribute(class);
+            v.visit_style_attribute(style);
+            for attr in change_attributes {
+                v.visit_attribute(attr);
+            }
+            for attr in worklet_attributes {
+                v.visit_static_attribute(attr);
+            }
+            for attr in generics {
+                v.visit_static_attribute(attr);
+            }
+            for attr in extra_attr {
+                v.visit_static_attribute(attr);
+            }
+            v.visit_common_element_attributes(common);
+            for node in children {
+                v.visit_node(node);
+            }
+        }
+        ElementKind::Pure {
+            children,
+            let_vars: list,
+            slot,
+            slot_value_refs,
+        } => {
+            v.visit_tag_location(&x.tag_location);
+            let_vars(v, list);
+            if let Some((location, value)) = slot {
+                v.visit_location(location);
+                v.visit_value(value);
+            }
+            for attr in slot_value_refs {
+                v.visit_static_attribute(attr);
+            }
+            for node in children {
+                v.visit_node(node);
+            }
+        }
+        ElementKind::For {
+            list,
+            item_name,
+            index_name,
+            key,
+            children,
+        } => {
+            v.visit_tag_location(&x.tag_location);
+            v.visit_location(&list.0);
+            v.visit_value(&list.1);
+            v.declare_scope(Scope::ForItem {
+                list,
+                name: item_name,
+            });
+            v.declare_scope(Scope::ForIndex {
+                list,
+                name: index_name,
+            });
+            count += 2;
+            v.visit_location(&item_name.0);
+            v.visit_str_name(&item_name.1);
+            v.visit_location(&index_name.0);
+            v.visit_str_name(&index_name.1);
+            v.visit_location(&key.0);
+            v.visit_str_name(&key.1);
+            for node in children {
+                v.visit_node(node);
+            }
+        }
+        ElementKind::If { .. }
+        | ElementKind::TemplateRef { .. }
+        | ElementKind::Include { .. }
+        | ElementKind::Slot { .. } => visit::walk_element(v, x),
+    }
+    v.end_scopes(count);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::expr::Expression;
+
+    #[derive(Default)]
+    struct ScopeRefs<'a> {
+        scopes: Vec<&'a str>,
+        refs: Vec<String>,
+    }
+
+    impl<'a> Visit<'a> for ScopeRefs<'a> {
+        fn visit_template(&mut self, x: &'a Template) {
+            walk_template(self, x)
+        }
+
+        fn visit_element(&mut self, x: &'a Element) {
+            walk_element(self, x)
+        }
+
+        fn visit_expression(&mut self, x: &'a Expression) {
+            if let Expression::ScopeRef { index, .. } = x {
+                self.refs
+                    .push(format!("{}@{}", self.scopes[*index], self.scopes.len()));
+            }
+            visit::walk_expression(self, x);
+        }
+    }
+
+    impl<'a> ScopeVisit<'a> for ScopeRefs<'a> {
+        fn declare_scope(&mut self, scope: Scope<'a>) {
+            self.scopes.push(scope.name());
+        }
+
+        fn end_scopes(&mut self, count: usize) {
+            self.scopes.truncate(self.scopes.len() - count);
+        }
+    }
+
+    #[test]
+    fn scope_refs() {
+        let src = r#"
+            <wxs module="m" src="m" />
+            <template name="t">{{ m }}</template>
+            <view let:a="{{ m }}" let:b="{{ a }}" attr="{{ b }}">
+                <block wx:for="{{ a }}" wx:for-item="i">{{ i + index }}</block>
+                <view slot:s let:c="{{ s }}">{{ c }}</view>
+            </view>
+            <slot name="{{ m }}" />
+        "#;
+        let (template, _) = crate::parse::parse("TEST", src);
+        let mut v = ScopeRefs::default();
+        v.visit_template(&template);
+        assert_eq!(
+            v.refs,
+            ["m@1", "m@1", "a@2", "b@3", "a@3", "i@5", "index@5", "s@4", "c@5", "m@1",]
+        );
+        assert!(v.scopes.is_empty());
+    }
+}
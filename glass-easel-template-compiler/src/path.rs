@@ -46,3 +46,24 @@ pub(crate) fn resolve(base: &str, rel: &str) -> String {
     }
     slices.join("/")
 }
+
+pub(crate) fn relative(from: &str, to: &str) -> String {
+    let from_dir: Vec<_> = from.split('/').collect();
+    let from_dir = &from_dir[..from_dir.len() - 1];
+    let to: Vec<_> = to.split('/').collect();
+    let common = from_dir
+        .iter()
+        .zip(to[..to.len() - 1].iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut ret = String::new();
+    if common == from_dir.len() {
+        ret.push_str("./");
+    } else {
+        for _ in common..from_dir.len() {
+            ret.push_str("../");
+        }
+    }
+    ret.push_str(&to[common..].join("/"));
+    ret
+}
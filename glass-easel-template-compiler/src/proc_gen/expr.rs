@@ -2,10 +2,15 @@ use std::fmt::Write;
 
 use compact_str::CompactString;
 
-use super::{JsExprWriter, JsFunctionScopeWriter, JsIdent, ScopeVar, ScopeVarLvaluePath};
+use super::{
+    JsExprWriter, JsFunctionScopeWriter, JsIdent, JsTopScopeWriter, ScopeVar, ScopeVarLvaluePath,
+};
 use crate::{
-    escape::gen_lit_str,
-    parse::expr::{ArrayFieldKind, Expression, ObjectFieldKind},
+    escape::{gen_lit_str, gen_lit_template_str_part},
+    parse::{
+        expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
+        TemplateStructure,
+    },
     stringify::expr::ExpressionLevel,
     TmplError,
 };
@@ -434,6 +439,7 @@ impl Expression {
             write!(value, ")")?;
             return Ok(ret);
         }
+        w.source_location_to(value, self.location_start())?;
         let path_analysis_state: PathAnalysisState = match self {
             Expression::ScopeRef { index, .. } => {
                 let scope = &scopes[*index];
@@ -597,10 +603,38 @@ impl Expression {
                     spread_sub_pas_list,
                 )]))
             }
+            Expression::LitTemplateStr { parts, .. } => {
+                write!(value, "`")?;
+                for part in parts.iter() {
+                    match part {
+                        TemplateStrPart::Static { value: x, .. } => {
+                            write!(value, "{}", gen_lit_template_str_part(x))?;
+                        }
+                        TemplateStrPart::Dynamic { value: x, .. } => {
+                            write!(value, "${{")?;
+                            x.to_proc_gen_rec_and_end_path(
+                                w,
+                                scopes,
+                                ExpressionLevel::Cond,
+                                path_calc,
+                                value,
+                            )?;
+                            write!(value, "}}")?;
+                        }
+                    }
+                }
+                write!(value, "`")?;
+                PathAnalysisState::NotInPath
+            }
 
             Expression::StaticMember {
                 obj, field_name, ..
+            }
+            | Expression::OptionalStaticMember {
+                obj, field_name, ..
             } => {
+                // `X` already gives `undefined` members for `null` and `undefined` ,
+                // so optional chains share the code and the update paths
                 write!(value, "X(")?;
                 let mut pas =
                     obj.to_proc_gen_rec(w, scopes, ExpressionLevel::Cond, path_calc, value)?;
@@ -619,6 +653,9 @@ impl Expression {
             }
             Expression::DynamicMember {
                 obj, field_name, ..
+            }
+            | Expression::OptionalDynamicMember {
+                obj, field_name, ..
             } => {
                 let ident = {
                     let ident = w.gen_private_ident();
@@ -650,7 +687,8 @@ impl Expression {
                 }
                 pas
             }
-            Expression::FuncCall { func, args, .. } => {
+            Expression::FuncCall { func, args, .. }
+            | Expression::OptionalFuncCall { func, args, .. } => {
                 write!(value, "P(")?;
                 func.to_proc_gen_rec_and_end_path(
                     w,
@@ -1298,6 +1336,98 @@ impl ExpressionProcGen {
     }
 }
 
+/// The root of a data path found by the path analysis.
+pub(crate) enum DataPathRoot {
+    Data(CompactString),
+    Scope(usize),
+}
+
+/// A data path read by an expression, i.e. a root followed by static fields.
+pub(crate) struct DataPath {
+    pub(crate) root: DataPathRoot,
+    pub(crate) fields: Vec<CompactString>,
+}
+
+impl PathSliceList {
+    fn collect_data_paths(&self, ret: &mut Vec<DataPath>) {
+        let mut cur: Option<DataPath> = None;
+        let mut ended = false;
+        for path_slice in self.0.iter() {
+            match path_slice {
+                PathSlice::Ident(s) => {
+                    cur = Some(DataPath {
+                        root: DataPathRoot::Data(s.clone()),
+                        fields: vec![],
+                    })
+                }
+                PathSlice::ScopeIndex(i) => {
+                    cur = Some(DataPath {
+                        root: DataPathRoot::Scope(*i),
+                        fields: vec![],
+                    })
+                }
+                PathSlice::StaticMember(s) => {
+                    if let Some(cur) = cur.as_mut().filter(|_| !ended) {
+                        cur.fields.push(s.clone());
+                    }
+                }
+                PathSlice::IndirectValue(_) => {
+                    // the index is in the sub paths, so the path ends here
+                    ended = true;
+                }
+                PathSlice::CombineObj(v) => {
+                    for (_, pas, sub_p) in v.iter() {
+                        pas.collect_data_paths(sub_p, ret);
+                    }
+                }
+                PathSlice::CombineArr(v, spread) => {
+                    for (pas, sub_p) in v.iter().chain(spread.iter()) {
+                        pas.collect_data_paths(sub_p, ret);
+                    }
+                }
+                PathSlice::Condition(_, (true_pas, true_p), (false_pas, false_p)) => {
+                    true_pas.collect_data_paths(true_p, ret);
+                    false_pas.collect_data_paths(false_p, ret);
+                }
+            }
+        }
+        ret.extend(cur);
+    }
+}
+
+impl PathAnalysisState {
+    fn collect_data_paths(&self, sub_p: &[PathSliceList], ret: &mut Vec<DataPath>) {
+        for psl in sub_p {
+            psl.collect_data_paths(ret);
+        }
+        if let PathAnalysisState::InPath(psl) = self {
+            psl.collect_data_paths(ret);
+        }
+    }
+}
+
+impl Expression {
+    /// Get the data paths read by the expression, which are the ones checked against the update path tree.
+    ///
+    /// `scope_count` is the number of scopes visible to the expression.
+    pub(crate) fn data_paths(&self, scope_count: usize) -> Result<Vec<DataPath>, TmplError> {
+        let mut w = JsTopScopeWriter::new(String::new());
+        w.function_scope(|w| {
+            let scopes = (0..scope_count)
+                .map(|_| ScopeVar {
+                    var: w.gen_ident(),
+                    update_path_tree: Some(w.gen_ident()),
+                    lvalue_path: ScopeVarLvaluePath::Invalid,
+                })
+                .collect();
+            let epg = self.to_proc_gen_prepare(w, &scopes)?;
+            let mut ret = vec![];
+            epg.pas.collect_data_paths(&epg.sub_p, &mut ret);
+            Ok(ret)
+        })
+    }
+}
+
 fn proc_gen_expression_level(expr: &Expression) -> ExpressionLevel {
     match expr {
         Expression::ScopeRef { .. } => ExpressionLevel::Lit,
@@ -1311,9 +1441,13 @@ fn proc_gen_expression_level(expr: &Expression) -> ExpressionLevel {
         Expression::LitBool { .. } => ExpressionLevel::Lit,
         Expression::LitObj { .. } => ExpressionLevel::Member,
         Expression::LitArr { .. } => ExpressionLevel::Member,
+        Expression::LitTemplateStr { .. } => ExpressionLevel::Lit,
         Expression::StaticMember { .. } => ExpressionLevel::Member,
         Expression::DynamicMember { .. } => ExpressionLevel::Member,
         Expression::FuncCall { .. } => ExpressionLevel::Member,
+        Expression::OptionalStaticMember { .. } => ExpressionLevel::Member,
+        Expression::OptionalDynamicMember { .. } => ExpressionLevel::Member,
+        Expression::OptionalFuncCall { .. } => ExpressionLevel::Member,
         Expression::Reverse { .. } => ExpressionLevel::Unary,
         Expression::BitReverse { .. } => ExpressionLevel::Unary,
         Expression::Positive { .. } => ExpressionLevel::Unary,
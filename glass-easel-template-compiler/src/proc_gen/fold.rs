@@ -0,0 +1,266 @@
+//! Constant folding of template expressions before code generation.
+//!
+//! Sub-expressions that only involve primitive literals are evaluated at compile time,
+//! so `{{ 1 + 2 }}` is generated as `3` and `{{ true ? a : b }}` as `a`.
+//! When a whole value folds into a constant,
+//! it is turned into a static value if the generated code is known to behave the same.
+
+use std::ops::Range;
+
+use compact_str::CompactString;
+
+use crate::{
+    eval::{Evaluator, JsValue},
+    parse::{
+        expr::{Expression, TemplateStrPart},
+        tag::{ClassAttribute, Node, StyleAttribute, Value},
+        visit_mut::{self, VisitMut},
+        Position, Template, TemplateStructure,
+    },
+};
+
+/// Fold constant expressions in the template.
+///
+/// The binding map keys are collected again after folding,
+/// so the data fields folded away no longer have binding map updaters.
+pub(crate) fn fold_template(tmpl: &mut Template) {
+    Folder.visit_template(tmpl);
+    tmpl.reinit_scopes_and_binding_map_keys();
+}
+
+struct Folder;
+
+impl Folder {
+    fn fold_value(&mut self, x: &mut Value, to_static: impl FnOnce(&JsValue) -> Option<String>) {
+        let Value::Dynamic { expression, .. } = x else {
+            return;
+        };
+        self.visit_expression(expression);
+        let Some(v) = const_value(expression) else {
+            return;
+        };
+        if let Some(value) = to_static(&v) {
+            *x = Value::Static {
+                value: CompactString::from(value),
+                location: x.location(),
+            };
+        }
+    }
+}
+
+impl VisitMut for Folder {
+    fn visit_node(&mut self, x: &mut Node) {
+        match x {
+            Node::Text(x) => {
+                self.fold_value(x, |v| Some(v.to_string_without_undefined().into_owned()))
+            }
+            _ => visit_mut::walk_node(self, x),
+        }
+    }
+
+    fn visit_class_attribute(&mut self, x: &mut ClassAttribute) {
+        match x {
+            ClassAttribute::Multiple(list) => {
+                // a static class item is always added, and a falsy one is never added
+                list.retain_mut(|(_, _, value)| {
+                    let Some(value) = value else {
+                        return true;
+                    };
+                    let mut enabled = true;
+                    self.fold_value(value, |v| {
+                        enabled = v.to_boolean();
+                        enabled.then(String::new)
+                    });
+                    enabled
+                });
+            }
+            _ => visit_mut::walk_class_attribute(self, x),
+        }
+    }
+
+    fn visit_style_attribute(&mut self, x: &mut StyleAttribute) {
+        match x {
+            StyleAttribute::Multiple(list) => {
+                for (_, _, value) in list {
+                    self.fold_value(value, |v| {
+                        Some(v.to_string_without_undefined().into_owned())
+                    });
+                }
+            }
+            _ => visit_mut::walk_style_attribute(self, x),
+        }
+    }
+
+    fn visit_value(&mut self, x: &mut Value) {
+        // other places may pass the value as-is, so only strings are equivalent to static ones
+        self.fold_value(x, |v| match v {
+            JsValue::String(s) => Some(s.clone()),
+            _ => None,
+        });
+    }
+
+    fn visit_expression(&mut self, x: &mut Expression) {
+        visit_mut::walk_expression(self, x);
+        fold_expression(x);
+    }
+}
+
+fn is_const(x: &Expression) -> bool {
+    match x {
+        Expression::LitUndefined { .. }
+        | Expression::LitNull { .. }
+        | Expression::LitStr { .. }
+        | Expression::LitInt { .. }
+        | Expression::LitFloat { .. }
+        | Expression::LitBool { .. } => true,
+        Expression::Negative { value, .. } => matches!(
+            &**value,
+            Expression::LitInt { .. } | Expression::LitFloat { .. }
+        ),
+        _ => false,
+    }
+}
+
+fn const_value(x: &Expression) -> Option<JsValue> {
+    if !is_const(x) {
+        return None;
+    }
+    let data = JsValue::Undefined;
+    let v = Evaluator::new(&data, &[]).eval(x).ok()?;
+    Some(v.into_owned())
+}
+
+fn lit_expression(v: &JsValue, location: Range<Position>) -> Option<Expression> {
+    let ret = match v {
+        JsValue::Undefined => Expression::LitUndefined { location },
+        JsValue::Null => Expression::LitNull { location },
+        JsValue::Bool(value) => Expression::LitBool {
+            value: *value,
+            location,
+        },
+        JsValue::String(value) => Expression::LitStr {
+            value: value.as_str().into(),
+            location,
+        },
+        JsValue::Number(n) => {
+            if !n.is_finite() {
+                return None;
+            }
+            // negative numbers (including `-0`) are written as a negation of its absolute value
+            let abs = n.abs();
+            let lit = if abs.fract() == 0. && abs < 9007199254740992. {
+                Expression::LitInt {
+                    value: abs as i64,
+                    location: location.clone(),
+                }
+            } else {
+                Expression::LitFloat {
+                    value: abs,
+                    location: location.clone(),
+                }
+            };
+            if n.is_sign_negative() {
+                Expression::Negative {
+                    value: Box::new(lit),
+                    location,
+                }
+            } else {
+                lit
+            }
+        }
+        JsValue::Array(_) | JsValue::Object(_) => return None,
+    };
+    Some(ret)
+}
+
+fn fold_expression(x: &mut Expression) {
+    let take = |x: &mut Box<Expression>| {
+        let location = x.location();
+        std::mem::replace(&mut **x, Expression::LitUndefined { location })
+    };
+    let replacement = match x {
+        Expression::Cond {
+            cond,
+            true_br,
+            false_br,
+            ..
+        } => const_value(cond).map(|c| {
+            if c.to_boolean() {
+                take(true_br)
+            } else {
+                take(false_br)
+            }
+        }),
+        Expression::LogicAnd { left, right, .. } => const_value(left).map(|c| {
+            if c.to_boolean() {
+                take(right)
+            } else {
+                take(left)
+            }
+        }),
+        Expression::LogicOr { left, right, .. } => const_value(left).map(|c| {
+            if c.to_boolean() {
+                take(left)
+            } else {
+                take(right)
+            }
+        }),
+        Expression::NullishCoalescing { left, right, .. } => const_value(left).map(|c| match c {
+            JsValue::Undefined | JsValue::Null => take(right),
+            _ => take(left),
+        }),
+        _ => fold_const_operands(x),
+    };
+    if let Some(replacement) = replacement {
+        *x = replacement;
+    }
+}
+
+fn fold_const_operands(x: &Expression) -> Option<Expression> {
+    let operands: Vec<&Expression> = match x {
+        Expression::ToStringWithoutUndefined { value, .. }
+        | Expression::Reverse { value, .. }
+        | Expression::BitReverse { value, .. }
+        | Expression::Positive { value, .. }
+        | Expression::TypeOf { value, .. }
+        | Expression::Void { value, .. } => vec![&**value],
+        Expression::Negative { value, .. } => match &**value {
+            // keep the canonical form of negative numbers
+            Expression::LitInt { .. } | Expression::LitFloat { .. } => return None,
+            value => vec![value],
+        },
+        Expression::Multiply { left, right, .. }
+        | Expression::Divide { left, right, .. }
+        | Expression::Remainer { left, right, .. }
+        | Expression::Plus { left, right, .. }
+        | Expression::Minus { left, right, .. }
+        | Expression::LeftShift { left, right, .. }
+        | Expression::RightShift { left, right, .. }
+        | Expression::UnsignedRightShift { left, right, .. }
+        | Expression::Lt { left, right, .. }
+        | Expression::Gt { left, right, .. }
+        | Expression::Lte { left, right, .. }
+        | Expression::Gte { left, right, .. }
+        | Expression::Eq { left, right, .. }
+        | Expression::Ne { left, right, .. }
+        | Expression::EqFull { left, right, .. }
+        | Expression::NeFull { left, right, .. }
+        | Expression::BitAnd { left, right, .. }
+        | Expression::BitXor { left, right, .. }
+        | Expression::BitOr { left, right, .. } => vec![&**left, &**right],
+        Expression::LitTemplateStr { parts, .. } => parts
+            .iter()
+            .filter_map(|part| match part {
+                TemplateStrPart::Static { .. } => None,
+                TemplateStrPart::Dynamic { value, .. } => Some(value),
+            })
+            .collect(),
+        _ => return None,
+    };
+    if !operands.into_iter().all(is_const) {
+        return None;
+    }
+    let data = JsValue::Undefined;
+    let v = Evaluator::new(&data, &[]).eval(x).ok()?;
+    lit_expression(&v, x.location())
+}
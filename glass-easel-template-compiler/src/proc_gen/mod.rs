@@ -1,9 +1,15 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Write;
+use std::rc::Rc;
 
+use sourcemap::{SourceMap, SourceMapBuilder};
+
+use crate::parse::Position;
 use crate::TmplError;
 
-mod expr;
+pub(crate) mod expr;
+pub(crate) mod fold;
 mod tag;
 
 const VAR_NAME_CHARS: [char; 63] = [
@@ -19,6 +25,13 @@ const VAR_NAME_START_CHARS: [char; 52] = [
 ];
 const VAR_NAME_INDEX_PRESERVE: usize = 26; // 'A' ~ 'Z' are preserved
 
+// Source locations are written as `START line,col,path END` markers during generation,
+// and then stripped from the final output to build the source map.
+// Non-characters are used so that they will not conflict with any meaningful content.
+// String literals escape them, and a `START` in raw content is written as an empty marker `START END` .
+const SOURCE_MAP_MARKER_START: char = '\u{FDD0}';
+const SOURCE_MAP_MARKER_END: char = '\u{FDD1}';
+
 #[derive(Debug, Clone)]
 pub(crate) struct JsIdent {
     name: String,
@@ -71,6 +84,9 @@ pub(crate) struct JsTopScopeWriter<W: fmt::Write> {
     top_declares: Vec<String>,
     sub_strs: Vec<String>,
     block: JsBlockStat,
+    source_map: bool,
+    source_path: String,
+    static_routines: Rc<HashMap<String, JsIdent>>,
 }
 
 impl<'a, W: fmt::Write> JsTopScopeWriter<W> {
@@ -80,11 +96,54 @@ impl<'a, W: fmt::Write> JsTopScopeWriter<W> {
             top_declares: vec![],
             sub_strs: vec![],
             block: JsBlockStat::new(),
+            source_map: false,
+            source_path: String::new(),
+            static_routines: Rc::new(HashMap::new()),
         }
     }
 
+    /// Write source location markers so that a source map can be extracted from the result.
+    pub(crate) fn enable_source_map(&mut self) {
+        self.source_map = true;
+    }
+
     pub(crate) fn align<WW: fmt::Write>(&mut self, w: &JsFunctionScopeWriter<'a, WW>) {
         self.block.align(w.get_block());
+        self.source_map = w.top_scope.source_map;
+        self.source_path = w.top_scope.source_path.clone();
+        self.static_routines = w.top_scope.static_routines.clone();
+    }
+
+    fn write_source_location(&self, w: &mut String, pos: Position) -> Result<(), TmplError> {
+        if self.source_map && !self.source_path.contains(SOURCE_MAP_MARKER_END) {
+            write!(
+                w,
+                "{}{},{},{}{}",
+                SOURCE_MAP_MARKER_START,
+                pos.line,
+                pos.utf16_col,
+                self.source_path,
+                SOURCE_MAP_MARKER_END,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write raw content which may contain the source map markers.
+    fn write_raw_content(&self, w: &mut String, content: &str) -> Result<(), TmplError> {
+        if self.source_map && content.contains(SOURCE_MAP_MARKER_START) {
+            let mut escaped = String::with_capacity(content.len() + 3);
+            for c in content.chars() {
+                escaped.push(c);
+                if c == SOURCE_MAP_MARKER_START {
+                    escaped.push(SOURCE_MAP_MARKER_END);
+                }
+            }
+            write!(w, "{}", escaped)?;
+        } else {
+            write!(w, "{}", content)?;
+        }
+        Ok(())
     }
 
     pub(crate) fn finish(self) -> W {
@@ -241,13 +300,37 @@ impl<'a, W: fmt::Write> JsFunctionScopeWriter<'a, W> {
         }
     }
 
+    /// Mark the current output position as generated from `pos` in the source.
+    pub(crate) fn source_location(&mut self, pos: Position) -> Result<(), TmplError> {
+        self.top_scope.write_source_location(self.w, pos)
+    }
+
+    /// Like `source_location` , but write the marker to a separated string.
+    pub(crate) fn source_location_to(
+        &self,
+        w: &mut String,
+        pos: Position,
+    ) -> Result<(), TmplError> {
+        self.top_scope.write_source_location(w, pos)
+    }
+
+    /// Set the shared creation routines of static subtrees, keyed by the generated creation code.
+    pub(crate) fn set_static_routines(&mut self, routines: HashMap<String, JsIdent>) {
+        self.top_scope.static_routines = Rc::new(routines);
+    }
+
+    /// Get the shared creation routine of a static subtree.
+    pub(crate) fn static_routine(&self, key: &str) -> Option<&JsIdent> {
+        self.top_scope.static_routines.get(key)
+    }
+
     pub(crate) fn custom_stmt_str(&mut self, content: &str) -> Result<(), TmplError> {
         let block = self.get_block_mut();
         if block.need_stat_sep {
             block.need_stat_sep = false;
             write!(&mut self.w, ";")?;
         }
-        write!(&mut self.w, "{}", content)?;
+        self.top_scope.write_raw_content(self.w, content)?;
         Ok(())
     }
 
@@ -330,6 +413,32 @@ pub(crate) struct JsExprWriter<'a, W: fmt::Write> {
 }
 
 impl<'a, W: fmt::Write> JsExprWriter<'a, W> {
+    /// Set the source path of the source locations written later.
+    pub(crate) fn set_source_path(&mut self, path: &str) {
+        self.top_scope.source_path = path.to_string();
+    }
+
+    /// Write a piece of source code as it is, with the source location of each line marked.
+    pub(crate) fn source_content(
+        &mut self,
+        content: &str,
+        start: Position,
+    ) -> Result<(), TmplError> {
+        for (index, line) in content.split_inclusive('\n').enumerate() {
+            let pos = if index == 0 {
+                start
+            } else {
+                Position {
+                    line: start.line + index as u32,
+                    utf16_col: 0,
+                }
+            };
+            self.top_scope.write_source_location(self.w, pos)?;
+            self.top_scope.write_raw_content(self.w, line)?;
+        }
+        Ok(())
+    }
+
     fn get_block(&mut self) -> &mut JsBlockStat {
         if self.block.is_some() {
             self.block.as_mut().unwrap()
@@ -466,3 +575,64 @@ pub(crate) enum ScopeVarLvaluePath {
         mod_name: String,
     },
 }
+
+/// Strip the source location markers in the generated code.
+pub(crate) fn strip_source_map_markers(code: &str) -> String {
+    extract_source_map(code, |_| None).0
+}
+
+/// Strip the source location markers in the generated code and build the source map.
+///
+/// `source_content` provides the source content of a source path if available.
+pub(crate) fn extract_source_map<'s>(
+    code: &str,
+    source_content: impl Fn(&str) -> Option<&'s str>,
+) -> (String, SourceMap) {
+    let mut smb = SourceMapBuilder::new(None);
+    let mut source_ids: HashMap<&str, u32> = HashMap::new();
+    let mut ret = String::with_capacity(code.len());
+    let mut line = 0;
+    let mut utf16_col = 0;
+    let mut rest = code;
+    while let Some(index) = rest.find(SOURCE_MAP_MARKER_START) {
+        let (before, after) = rest.split_at(index);
+        for c in before.chars() {
+            if c == '\n' {
+                line += 1;
+                utf16_col = 0;
+            } else {
+                utf16_col += c.len_utf16() as u32;
+            }
+        }
+        ret.push_str(before);
+        let after = &after[SOURCE_MAP_MARKER_START.len_utf8()..];
+        let end = after.find(SOURCE_MAP_MARKER_END).unwrap_or(after.len());
+        let mut marker = after[..end].splitn(3, ',');
+        if end == 0 {
+            // an empty marker is an escaped `START` in the raw content
+            ret.push(SOURCE_MAP_MARKER_START);
+            utf16_col += SOURCE_MAP_MARKER_START.len_utf16() as u32;
+        } else if let (Some(src_line), Some(src_col), Some(path)) =
+            (marker.next(), marker.next(), marker.next())
+        {
+            let src_id = *source_ids.entry(path).or_insert_with(|| {
+                let id = smb.add_source(path);
+                smb.set_source_contents(id, source_content(path));
+                id
+            });
+            smb.add_raw(
+                line,
+                utf16_col,
+                src_line.parse().unwrap_or(0),
+                src_col.parse().unwrap_or(0),
+                Some(src_id),
+                None,
+            );
+        }
+        rest = after
+            .get(end + SOURCE_MAP_MARKER_END.len_utf8()..)
+            .unwrap_or("");
+    }
+    ret.push_str(rest);
+    (ret, smb.into_sourcemap())
+}
@@ -1,29 +1,35 @@
 use std::{collections::HashMap, fmt::Write, ops::Range};
 
 use super::{
-    JsExprWriter, JsFunctionScopeWriter, JsIdent, JsTopScopeWriter, ScopeVar, ScopeVarLvaluePath,
+    strip_source_map_markers, JsExprWriter, JsFunctionScopeWriter, JsIdent, JsTopScopeWriter,
+    ScopeVar, ScopeVarLvaluePath,
 };
 use crate::{
     binding_map::BindingMapCollector,
-    escape::{camel_to_dash, gen_lit_str},
+    escape::{camel_to_dash, gen_lit_str, gen_lit_str_with_quotes},
     parse::{
         tag::{
             Attribute, ClassAttribute, CommonElementAttributes, Element, ElementKind, EventBinding,
             Node, NormalAttribute, NormalAttributePrefix, Script, StaticAttribute, StyleAttribute,
             Value,
         },
+        visit::{self, Visit},
         Position, Template,
     },
     proc_gen::expr::ExpressionProcGen,
+    tree_shaking::TreeShakingReport,
     TmplError, TmplGroup,
 };
 
 impl Template {
+    /// Generate the gen object of a constant-folded template.
     pub(crate) fn to_proc_gen<W: std::fmt::Write>(
         &self,
         w: &mut JsExprWriter<W>,
         group: &TmplGroup,
+        shaking: Option<&TreeShakingReport>,
     ) -> Result<(), TmplError> {
+        w.set_source_path(&format!("{}.wxml", self.path));
         w.paren(|w| {
             w.function(|w| {
                 w.expr_stmt(|w| {
@@ -145,56 +151,78 @@ impl Template {
                         })
                     };
                 let scopes = &mut vec![];
-                let has_scripts = if self.globals.scripts.len() > 0 {
-                    for script in &self.globals.scripts {
-                        let ident = w.gen_ident();
-                        let lvalue_path = match script {
-                            Script::GlobalRef {
-                                tag_location: _,
-                                module_location: _,
-                                module_name: _,
-                                src_location: _,
-                                src,
-                            } => {
-                                let abs_path = crate::path::resolve(&self.path, &src.name);
-                                w.expr_stmt(|w| {
-                                    write!(w, r#"var {}=R[{}]()"#, ident, gen_lit_str(&abs_path))?;
-                                    Ok(())
-                                })?;
-                                ScopeVarLvaluePath::Script { abs_path }
-                            }
-                            Script::Inline {
-                                tag_location: _,
-                                module_location: _,
-                                module_name,
-                                content,
-                                content_location: _,
-                            } => {
-                                w.expr_stmt(|w| {
-                                    write!(
-                                        w,
-                                        "var {}=D('{}#{}',(require,exports,module)=>{{{}}})()",
-                                        ident, &self.path, module_name.name, content
-                                    )?;
-                                    Ok(())
-                                })?;
-                                ScopeVarLvaluePath::InlineScript {
-                                    path: self.path.clone(),
-                                    mod_name: module_name.name.to_string(),
-                                }
-                            }
-                        };
+                let mut has_scripts = false;
+                for script in &self.globals.scripts {
+                    let ident = w.gen_ident();
+                    let removed = shaking
+                        .map(|x| x.is_script_removed(&self.path, &script.module_name().name))
+                        .unwrap_or(false);
+                    // the scope is still needed to keep the indices of other scopes
+                    if removed {
                         scopes.push(ScopeVar {
                             var: ident,
                             update_path_tree: None,
-                            lvalue_path,
-                        })
+                            lvalue_path: ScopeVarLvaluePath::Invalid,
+                        });
+                        continue;
                     }
-                    true
-                } else {
-                    false
-                };
+                    has_scripts = true;
+                    let lvalue_path = match script {
+                        Script::GlobalRef {
+                            tag_location: _,
+                            module_location: _,
+                            module_name: _,
+                            src_location: _,
+                            src,
+                        } => {
+                            let abs_path = crate::path::resolve(&self.path, &src.name);
+                            w.expr_stmt(|w| {
+                                write!(w, r#"var {}=R[{}]()"#, ident, gen_lit_str(&abs_path))?;
+                                Ok(())
+                            })?;
+                            ScopeVarLvaluePath::Script { abs_path }
+                        }
+                        Script::Inline {
+                            tag_location: _,
+                            module_location: _,
+                            module_name,
+                            content,
+                            content_location,
+                        } => {
+                            w.expr_stmt(|w| {
+                                write!(
+                                    w,
+                                    "var {}=D({},(require,exports,module)=>{{",
+                                    ident,
+                                    gen_lit_str_with_quotes(
+                                        &format!("{}#{}", self.path, module_name.name),
+                                        true
+                                    ),
+                                )?;
+                                w.source_content(content, content_location.start)?;
+                                write!(w, "}})()")?;
+                                Ok(())
+                            })?;
+                            ScopeVarLvaluePath::InlineScript {
+                                path: self.path.clone(),
+                                mod_name: module_name.name.to_string(),
+                            }
+                        }
+                    };
+                    scopes.push(ScopeVar {
+                        var: ident,
+                        update_path_tree: None,
+                        lvalue_path,
+                    })
+                }
+                let static_routines = self.write_static_routines(w, group, shaking)?;
+                w.set_static_routines(static_routines);
                 for t in self.globals.sub_templates.iter() {
+                    if let Some(shaking) = shaking {
+                        if shaking.is_template_removed(&self.path, &t.name.name) {
+                            continue;
+                        }
+                    }
                     let bmc = BindingMapCollector::new();
                     write_template_item(&t.name.name, w, scopes, &bmc, &t.content, has_scripts)?;
                 }
@@ -221,6 +249,109 @@ impl Template {
     }
 }
 
+impl Template {
+    /// Write the shared creation routines of the static subtrees in the template scope.
+    ///
+    /// A routine is only written for a subtree that repeats and it saves bytes,
+    /// otherwise the subtree is still created inline.
+    fn write_static_routines<W: std::fmt::Write>(
+        &self,
+        w: &mut JsFunctionScopeWriter<W>,
+        group: &TmplGroup,
+        shaking: Option<&TreeShakingReport>,
+    ) -> Result<HashMap<String, JsIdent>, TmplError> {
+        struct StaticSubtrees<'a>(Vec<&'a Element>);
+        impl<'a> Visit<'a> for StaticSubtrees<'a> {
+            fn visit_element(&mut self, x: &'a Element) {
+                if x.has_static_children() {
+                    self.0.push(x);
+                } else {
+                    visit::walk_element(self, x);
+                }
+            }
+        }
+        let mut subtrees = StaticSubtrees(vec![]);
+        for t in self.globals.sub_templates.iter() {
+            if let Some(shaking) = shaking {
+                if shaking.is_template_removed(&self.path, &t.name.name) {
+                    continue;
+                }
+            }
+            for node in t.content.iter() {
+                subtrees.visit_node(node);
+            }
+        }
+        for node in self.content.iter() {
+            subtrees.visit_node(node);
+        }
+
+        // group the subtrees by the generated code (source locations are ignored)
+        let mut groups: Vec<(String, Vec<&Element>)> = vec![];
+        for elem in subtrees.0 {
+            let key = elem.static_children_key(w, group)?;
+            match groups.iter_mut().find(|(x, _)| *x == key) {
+                Some((_, list)) => list.push(elem),
+                None => groups.push((key, vec![elem])),
+            }
+        }
+
+        let mut ret = HashMap::new();
+        for (key, list) in groups {
+            let elem = list[0];
+            let children = match &elem.kind {
+                ElementKind::Normal { children, .. } => children,
+                _ => unreachable!(),
+            };
+            let has_elements = children.iter().any(|x| matches!(x, Node::Element(..)));
+            let args = Node::to_proc_gen_function_args(&mut children.iter(), false);
+            let n = list.len();
+            let prefix_len = if has_elements {
+                "var L=R.c,M=R.m,O=R.r;".len()
+            } else {
+                0
+            };
+            let inline_len = n * (args.len() + "()=>{if(C){}}".len() + key.len());
+            let shared_len = "var a=(R,T,E)=>{};".len()
+                + prefix_len
+                + key.len()
+                + n * "(C,T,E)=>{if(C)a(R,T,E)}".len();
+            if shared_len >= inline_len {
+                continue;
+            }
+            let mut writer = JsTopScopeWriter::new(String::new());
+            writer.align(w);
+            writer.function_scope(|w| {
+                if has_elements {
+                    w.expr_stmt(|w| {
+                        write!(w, "var L=R.c,M=R.m,O=R.r")?;
+                        Ok(())
+                    })?;
+                }
+                Node::to_proc_gen_static_content(
+                    children,
+                    w,
+                    &mut vec![],
+                    &BindingMapCollector::new(),
+                    group,
+                )
+            })?;
+            let body = writer.finish();
+            let ident = w.gen_ident();
+            w.expr_stmt(|w| {
+                write!(w, "var {}=", ident)?;
+                w.function_args("R,T,E", |w| {
+                    w.expr_stmt(|w| {
+                        write!(w, "{}", body)?;
+                        Ok(())
+                    })
+                })
+            })?;
+            ret.insert(key, ident);
+        }
+        Ok(ret)
+    }
+}
+
 impl Node {
     fn to_proc_gen_function_args<'a>(
         list_iter: &mut (impl IntoIterator<Item = &'a Node> + Clone),
@@ -356,6 +487,38 @@ impl Node {
         Ok(())
     }
 
+    fn is_static_subtree(&self) -> bool {
+        match self {
+            Node::Text(Value::Static { .. }) => true,
+            Node::Text(Value::Dynamic { .. }) => false,
+            Node::Element(elem) => elem.is_static_subtree(),
+            Node::Comment(..) | Node::UnknownMetaTag(..) => true,
+        }
+    }
+
+    fn to_proc_gen_static_content<W: std::fmt::Write>(
+        list: &[Self],
+        w: &mut JsFunctionScopeWriter<W>,
+        scopes: &mut Vec<ScopeVar>,
+        bmc: &BindingMapCollector,
+        group: &TmplGroup,
+    ) -> Result<(), TmplError> {
+        for c in list.iter() {
+            match c {
+                Node::Text(Value::Static { value, .. }) => {
+                    w.expr_stmt(|w| {
+                        write!(w, "T({})", gen_lit_str(&value))?;
+                        Ok(())
+                    })?;
+                }
+                Node::Element(elem) => elem.to_proc_gen_static(w, scopes, bmc, group)?,
+                Node::Text(Value::Dynamic { .. }) => unreachable!(),
+                Node::Comment(..) | Node::UnknownMetaTag(..) => {}
+            }
+        }
+        Ok(())
+    }
+
     fn to_proc_gen_define_children_content<W: std::fmt::Write>(
         list: &[Self],
         var_slot_names: &Option<Vec<String>>,
@@ -391,7 +554,8 @@ impl Node {
                     group,
                     cur_path,
                 )?;
-                for (_, var_update_path_tree) in var_slot_map.values() {
+                for slot_value_name in var_slot_names.iter() {
+                    let (_, var_update_path_tree) = &var_slot_map[slot_value_name];
                     w.expr_stmt(|w| {
                         write!(w, "{}=undefined", var_update_path_tree)?;
                         Ok(())
@@ -633,6 +797,378 @@ impl Element {
         Ok(())
     }
 
+    fn is_static_subtree(&self) -> bool {
+        match &self.kind {
+            ElementKind::Normal {
+                tag_name: _,
+                attributes,
+                class,
+                style,
+                change_attributes,
+                worklet_attributes: _,
+                children,
+                generics: _,
+                extra_attr: _,
+                let_vars,
+                common,
+            } => {
+                let is_static = |v: &Value| match v {
+                    Value::Static { .. } => true,
+                    Value::Dynamic { .. } => false,
+                };
+                let class_static = match class {
+                    ClassAttribute::None => true,
+                    ClassAttribute::String(_, v) => is_static(v),
+                    ClassAttribute::Multiple(x) => x
+                        .iter()
+                        .all(|(_, _, v)| v.as_ref().map(is_static).unwrap_or(true)),
+                };
+                let style_static = match style {
+                    StyleAttribute::None => true,
+                    StyleAttribute::String(_, v) => is_static(v),
+                    StyleAttribute::Multiple(x) => x.iter().all(|(_, _, v)| is_static(v)),
+                };
+                class_static
+                    && style_static
+                    && change_attributes.is_empty()
+                    && let_vars.is_empty()
+                    && attributes
+                        .iter()
+                        .all(|attr| attr.value.as_ref().map(is_static).unwrap_or(true))
+                    && common.id.as_ref().map(|x| is_static(&x.1)).unwrap_or(true)
+                    && common
+                        .slot
+                        .as_ref()
+                        .map(|x| is_static(&x.1))
+                        .unwrap_or(true)
+                    && common.slot_value_refs.is_empty()
+                    && common.event_bindings.is_empty()
+                    && common
+                        .data
+                        .iter()
+                        .chain(common.marks.iter())
+                        .all(|attr| attr.value.as_ref().map(is_static).unwrap_or(true))
+                    && children.iter().all(|x| x.is_static_subtree())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the children can be built by a shared creation routine.
+    fn has_static_children(&self) -> bool {
+        match &self.kind {
+            ElementKind::Normal { children, .. } => {
+                !children.is_empty() && children.iter().all(|x| x.is_static_subtree())
+            }
+            _ => false,
+        }
+    }
+
+    /// The creation code of the static children, used to find identical static subtrees.
+    fn static_children_key<W: std::fmt::Write>(
+        &self,
+        w: &JsFunctionScopeWriter<W>,
+        group: &TmplGroup,
+    ) -> Result<String, TmplError> {
+        let children = match &self.kind {
+            ElementKind::Normal { children, .. } => children,
+            _ => unreachable!(),
+        };
+        let mut writer = JsTopScopeWriter::new(String::new());
+        writer.align(w);
+        writer.function_scope(|w| {
+            Node::to_proc_gen_static_content(
+                children,
+                w,
+                &mut vec![],
+                &BindingMapCollector::new(),
+                group,
+            )
+        })?;
+        Ok(strip_source_map_markers(&writer.finish()))
+    }
+
+    fn to_proc_gen_static<W: std::fmt::Write>(
+        &self,
+        w: &mut JsFunctionScopeWriter<W>,
+        scopes: &mut Vec<ScopeVar>,
+        bmc: &BindingMapCollector,
+        group: &TmplGroup,
+    ) -> Result<(), TmplError> {
+        w.source_location(self.tag_location.start.0.start)?;
+        let (children, common) = match &self.kind {
+            ElementKind::Normal {
+                children, common, ..
+            } => (children, common),
+            _ => unreachable!(),
+        };
+        let slot_kind = SlotKind::new(&common.slot, w, scopes)?;
+        w.expr_stmt(|w| {
+            self.write_normal_tag_name(w)?;
+            self.write_normal_property_init(w, scopes, bmc, group, &slot_kind)?;
+            write!(w, ",")?;
+            let args = Node::to_proc_gen_function_args(&mut children.iter(), false);
+            w.function_args(args, |w| {
+                Node::to_proc_gen_static_content(children, w, scopes, bmc, group)
+            })?;
+            slot_kind.write_as_extra_argument(w)?;
+            write!(w, ")")?;
+            Ok(())
+        })
+    }
+
+    fn write_normal_tag_name<W: std::fmt::Write>(
+        &self,
+        w: &mut JsExprWriter<W>,
+    ) -> Result<(), TmplError> {
+        let (tag_name, generics) = match &self.kind {
+            ElementKind::Normal {
+                tag_name, generics, ..
+            } => (tag_name, generics),
+            _ => unreachable!(),
+        };
+        write!(w, "E({},{{", gen_lit_str(&tag_name.name))?;
+        for (i, attr) in generics.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "{}:{}",
+                gen_lit_str(&attr.name.name),
+                gen_lit_str(&attr.value.name)
+            )?;
+        }
+        write!(w, "}},")?;
+        Ok(())
+    }
+
+    fn write_normal_property_init<W: std::fmt::Write>(
+        &self,
+        w: &mut JsExprWriter<W>,
+        scopes: &mut Vec<ScopeVar>,
+        bmc: &BindingMapCollector,
+        group: &TmplGroup,
+        slot_kind: &SlotKind,
+    ) -> Result<(), TmplError> {
+        let (attributes, class, style, change_attributes, worklet_attributes, extra_attr, common) =
+            match &self.kind {
+                ElementKind::Normal {
+                    attributes,
+                    class,
+                    style,
+                    change_attributes,
+                    worklet_attributes,
+                    extra_attr,
+                    common,
+                    ..
+                } => (
+                    attributes,
+                    class,
+                    style,
+                    change_attributes,
+                    worklet_attributes,
+                    extra_attr,
+                    common,
+                ),
+                _ => unreachable!(),
+            };
+        w.function_args("N,C", |w| {
+            if group.dev() {
+                w.expr_stmt(|w| {
+                    write!(w, "R.devArgs(N).A=[")?;
+                    self.collect_active_attribute_names(|str| write!(w, "{},", str))?;
+                    write!(w, "]")?;
+                    Ok(())
+                })?;
+            }
+            if extra_attr.len() > 0 {
+                for attr in extra_attr.iter() {
+                    w.expr_stmt(|w| {
+                        write!(
+                            w,
+                            "R.a(N,{},{})",
+                            gen_lit_str(&attr.name.name),
+                            gen_lit_str(&attr.value.name)
+                        )?;
+                        Ok(())
+                    })?;
+                }
+            }
+            match class {
+                ClassAttribute::None => {}
+                ClassAttribute::String(_, value) => {
+                    write_attribute_value(w, "L", value, scopes, bmc)?;
+                }
+                ClassAttribute::Multiple(x) => {
+                    let mut p_list = Vec::with_capacity(x.len());
+                    for (_, name, value) in x.iter() {
+                        match value.as_ref() {
+                            Some(Value::Dynamic {
+                                expression,
+                                double_brace_location: _,
+                                binding_map_keys: _,
+                            }) => {
+                                let p = expression.to_proc_gen_prepare(w, scopes)?;
+                                p_list.push((name, Some(p)));
+                            }
+                            None | Some(Value::Static { .. }) => {
+                                p_list.push((name, None));
+                            }
+                        }
+                    }
+                    w.expr_stmt(|w| {
+                        write!(w, "R.e(N,[")?;
+                        for (i, (name, p)) in p_list.iter().enumerate() {
+                            if i > 0 {
+                                write!(w, ",")?;
+                            }
+                            if let Some(p) = p {
+                                write!(w, "C||K||")?;
+                                p.lvalue_state_expr(w, scopes, false)?;
+                                write!(w, "?")?;
+                                p.value_expr(w)?;
+                                write!(w, r#"?{}:"":null"#, gen_lit_str(&name.name))?;
+                            } else {
+                                write!(w, "{}", gen_lit_str(&name.name))?;
+                            }
+                        }
+                        write!(w, "])")?;
+                        Ok(())
+                    })?;
+                    for (i, (_, name, value)) in x.iter().enumerate() {
+                        if let Some(Value::Dynamic {
+                            expression,
+                            double_brace_location: _,
+                            binding_map_keys: Some(binding_map_keys),
+                        }) = value
+                        {
+                            if !binding_map_keys.is_empty(bmc) {
+                                binding_map_keys.to_proc_gen_write_map(w, bmc, |w| {
+                                    let p = expression.to_proc_gen_prepare(w, scopes)?;
+                                    w.expr_stmt(|w| {
+                                        write!(w, "R.ei(N,{},", i)?;
+                                        p.value_expr(w)?;
+                                        write!(w, r#"?{}:"")"#, gen_lit_str(&name.name))?;
+                                        Ok(())
+                                    })?;
+                                    w.expr_stmt(|w| {
+                                        write!(w, "E(N)")?;
+                                        Ok(())
+                                    })
+                                })?;
+                            }
+                        }
+                    }
+                }
+            }
+            match style {
+                StyleAttribute::None => {}
+                StyleAttribute::String(_, value) => {
+                    write_attribute_value(w, "R.y", value, scopes, bmc)?;
+                }
+                StyleAttribute::Multiple(x) => {
+                    let mut p_list = Vec::with_capacity(x.len());
+                    for (_, name, value) in x.iter() {
+                        match value {
+                            Value::Dynamic {
+                                expression,
+                                double_brace_location: _,
+                                binding_map_keys: _,
+                            } => {
+                                let p = expression.to_proc_gen_prepare(w, scopes)?;
+                                p_list.push((name, StaticStrOrProcGen::Dynamic(p)));
+                            }
+                            Value::Static { value, location: _ } => {
+                                p_list.push((name, StaticStrOrProcGen::Static(&value)));
+                            }
+                        }
+                    }
+                    w.expr_stmt(|w| {
+                        write!(w, "R.w(N,[")?;
+                        for (i, (name, p)) in p_list.iter().enumerate() {
+                            if i > 0 {
+                                write!(w, ",")?;
+                            }
+                            write!(w, "{},", gen_lit_str(&name.name))?;
+                            match p {
+                                StaticStrOrProcGen::Dynamic(p) => {
+                                    write!(w, "C||K||")?;
+                                    p.lvalue_state_expr(w, scopes, false)?;
+                                    write!(w, "?Y(")?;
+                                    p.value_expr(w)?;
+                                    write!(w, "):null")?;
+                                }
+                                StaticStrOrProcGen::Static(value) => {
+                                    write!(w, "{}", gen_lit_str(value))?;
+                                }
+                            }
+                        }
+                        write!(w, "])")?;
+                        Ok(())
+                    })?;
+                    for (i, (_, _, value)) in x.iter().enumerate() {
+                        if let Value::Dynamic {
+                            expression,
+                            double_brace_location: _,
+                            binding_map_keys: Some(binding_map_keys),
+                        } = value
+                        {
+                            if !binding_map_keys.is_empty(bmc) {
+                                binding_map_keys.to_proc_gen_write_map(w, bmc, |w| {
+                                    let p = expression.to_proc_gen_prepare(w, scopes)?;
+                                    w.expr_stmt(|w| {
+                                        write!(w, "R.wi(N,{},Y(", i)?;
+                                        p.value_expr(w)?;
+                                        write!(w, "))")?;
+                                        Ok(())
+                                    })?;
+                                    w.expr_stmt(|w| {
+                                        write!(w, "E(N)")?;
+                                        Ok(())
+                                    })
+                                })?;
+                            }
+                        }
+                    }
+                }
+            }
+            for attr in worklet_attributes.iter() {
+                attr.to_proc_gen_as_worklet_property(w, scopes, bmc)?;
+            }
+            for attr in change_attributes.iter() {
+                attr.to_proc_gen_as_change_property(w, scopes, bmc)?;
+            }
+            for attr in attributes.iter() {
+                attr.to_proc_gen_as_normal(w, scopes, bmc)?;
+            }
+            common.to_proc_gen_without_slot(w, scopes, bmc)?;
+            if let SlotKind::Dynamic(p) = &slot_kind {
+                if let Some((
+                    _,
+                    Value::Dynamic {
+                        binding_map_keys, ..
+                    },
+                )) = common.slot.as_ref()
+                {
+                    if let Some(binding_map_keys) = binding_map_keys {
+                        if !binding_map_keys.is_empty(bmc) {
+                            binding_map_keys.to_proc_gen_write_map(w, bmc, |w| {
+                                w.expr_stmt(|w| {
+                                    write!(w, "R.s(N,")?;
+                                    p.value_expr(w)?;
+                                    write!(w, ")")?;
+                                    Ok(())
+                                })
+                            })?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
     pub(crate) fn to_proc_gen<W: std::fmt::Write>(
         &self,
         w: &mut JsFunctionScopeWriter<W>,
@@ -641,24 +1177,53 @@ impl Element {
         group: &TmplGroup,
         cur_path: &str,
     ) -> Result<(), TmplError> {
+        w.source_location(self.tag_location.start.0.start)?;
         match &self.kind {
             ElementKind::Normal {
-                tag_name,
-                attributes,
-                class,
-                style,
-                change_attributes,
-                worklet_attributes,
                 children,
-                generics,
-                extra_attr,
                 let_vars,
                 common,
+                ..
             } => {
                 let let_vars = Self::write_let_vars(w, scopes, let_vars)?;
                 let slot_kind = SlotKind::new(&common.slot, w, scopes)?;
+                let static_routine = if self.has_static_children() {
+                    let key = self.static_children_key(w, group)?;
+                    Some(w.static_routine(&key).cloned())
+                } else {
+                    None
+                };
                 let (child_ident, var_slot_names) =
                     w.declare_var_on_top_scope_init(|w, ident| {
+                        match &static_routine {
+                            Some(Some(routine)) => {
+                                // the whole subtree is built by the shared routine and skipped on updates
+                                w.function_args("C,T,E", |w| {
+                                    w.expr_stmt(|w| {
+                                        write!(w, "if(C){}(R,T,E)", routine)?;
+                                        Ok(())
+                                    })
+                                })?;
+                                return Ok((ident, None));
+                            }
+                            Some(None) => {
+                                // the whole subtree is built on creation and skipped on updates
+                                let args =
+                                    Node::to_proc_gen_function_args(&mut children.iter(), false);
+                                w.function_args(args, |w| {
+                                    w.expr_stmt(|w| {
+                                        write!(w, "if(C)")?;
+                                        w.brace_block(|w| {
+                                            Node::to_proc_gen_static_content(
+                                                children, w, scopes, bmc, group,
+                                            )
+                                        })
+                                    })
+                                })?;
+                                return Ok((ident, None));
+                            }
+                            None => {}
+                        }
                         let var_slot_map = Node::to_proc_gen_define_children(
                             &mut children.iter(),
                             w,
@@ -680,229 +1245,8 @@ impl Element {
                         Ok((ident, var_slot_map))
                     })?;
                 w.expr_stmt(|w| {
-                    write!(w, "E({},{{", gen_lit_str(&tag_name.name),)?;
-                    if generics.len() > 0 {
-                        for (i, attr) in generics.iter().enumerate() {
-                            if i > 0 {
-                                write!(w, ",")?;
-                            }
-                            write!(
-                                w,
-                                "{}:{}",
-                                gen_lit_str(&attr.name.name),
-                                gen_lit_str(&attr.value.name)
-                            )?;
-                        }
-                    }
-                    write!(w, "}},")?;
-                    w.function_args("N,C", |w| {
-                        if group.dev() {
-                            w.expr_stmt(|w| {
-                                write!(w, "R.devArgs(N).A=[")?;
-                                self.collect_active_attribute_names(|str| write!(w, "{},", str))?;
-                                write!(w, "]")?;
-                                Ok(())
-                            })?;
-                        }
-                        if extra_attr.len() > 0 {
-                            for attr in extra_attr.iter() {
-                                w.expr_stmt(|w| {
-                                    write!(
-                                        w,
-                                        "R.a(N,{},{})",
-                                        gen_lit_str(&attr.name.name),
-                                        gen_lit_str(&attr.value.name)
-                                    )?;
-                                    Ok(())
-                                })?;
-                            }
-                        }
-                        match class {
-                            ClassAttribute::None => {}
-                            ClassAttribute::String(_, value) => {
-                                write_attribute_value(w, "L", value, scopes, bmc)?;
-                            }
-                            ClassAttribute::Multiple(x) => {
-                                let mut p_list = Vec::with_capacity(x.len());
-                                for (_, name, value) in x.iter() {
-                                    match value.as_ref() {
-                                        Some(Value::Dynamic {
-                                            expression,
-                                            double_brace_location: _,
-                                            binding_map_keys: _,
-                                        }) => {
-                                            let p = expression.to_proc_gen_prepare(w, scopes)?;
-                                            p_list.push((name, Some(p)));
-                                        }
-                                        None | Some(Value::Static { .. }) => {
-                                            p_list.push((name, None));
-                                        }
-                                    }
-                                }
-                                w.expr_stmt(|w| {
-                                    write!(w, "R.e(N,[")?;
-                                    for (i, (name, p)) in p_list.iter().enumerate() {
-                                        if i > 0 {
-                                            write!(w, ",")?;
-                                        }
-                                        if let Some(p) = p {
-                                            write!(w, "C||K||")?;
-                                            p.lvalue_state_expr(w, scopes, false)?;
-                                            write!(w, "?")?;
-                                            p.value_expr(w)?;
-                                            write!(w, r#"?{}:"":null"#, gen_lit_str(&name.name))?;
-                                        } else {
-                                            write!(w, "{}", gen_lit_str(&name.name))?;
-                                        }
-                                    }
-                                    write!(w, "])")?;
-                                    Ok(())
-                                })?;
-                                for (i, (_, name, value)) in x.iter().enumerate() {
-                                    if let Some(Value::Dynamic {
-                                        expression,
-                                        double_brace_location: _,
-                                        binding_map_keys: Some(binding_map_keys),
-                                    }) = value
-                                    {
-                                        if !binding_map_keys.is_empty(bmc) {
-                                            binding_map_keys.to_proc_gen_write_map(
-                                                w,
-                                                bmc,
-                                                |w| {
-                                                    let p = expression
-                                                        .to_proc_gen_prepare(w, scopes)?;
-                                                    w.expr_stmt(|w| {
-                                                        write!(w, "R.ei(N,{},", i)?;
-                                                        p.value_expr(w)?;
-                                                        write!(
-                                                            w,
-                                                            r#"?{}:"")"#,
-                                                            gen_lit_str(&name.name)
-                                                        )?;
-                                                        Ok(())
-                                                    })?;
-                                                    w.expr_stmt(|w| {
-                                                        write!(w, "E(N)")?;
-                                                        Ok(())
-                                                    })
-                                                },
-                                            )?;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        match style {
-                            StyleAttribute::None => {}
-                            StyleAttribute::String(_, value) => {
-                                write_attribute_value(w, "R.y", value, scopes, bmc)?;
-                            }
-                            StyleAttribute::Multiple(x) => {
-                                let mut p_list = Vec::with_capacity(x.len());
-                                for (_, name, value) in x.iter() {
-                                    match value {
-                                        Value::Dynamic {
-                                            expression,
-                                            double_brace_location: _,
-                                            binding_map_keys: _,
-                                        } => {
-                                            let p = expression.to_proc_gen_prepare(w, scopes)?;
-                                            p_list.push((name, StaticStrOrProcGen::Dynamic(p)));
-                                        }
-                                        Value::Static { value, location: _ } => {
-                                            p_list.push((name, StaticStrOrProcGen::Static(&value)));
-                                        }
-                                    }
-                                }
-                                w.expr_stmt(|w| {
-                                    write!(w, "R.w(N,[")?;
-                                    for (i, (name, p)) in p_list.iter().enumerate() {
-                                        if i > 0 {
-                                            write!(w, ",")?;
-                                        }
-                                        write!(w, "{},", gen_lit_str(&name.name))?;
-                                        match p {
-                                            StaticStrOrProcGen::Dynamic(p) => {
-                                                write!(w, "C||K||")?;
-                                                p.lvalue_state_expr(w, scopes, false)?;
-                                                write!(w, "?Y(")?;
-                                                p.value_expr(w)?;
-                                                write!(w, "):null")?;
-                                            }
-                                            StaticStrOrProcGen::Static(value) => {
-                                                write!(w, "{}", gen_lit_str(value))?;
-                                            }
-                                        }
-                                    }
-                                    write!(w, "])")?;
-                                    Ok(())
-                                })?;
-                                for (i, (_, _, value)) in x.iter().enumerate() {
-                                    if let Value::Dynamic {
-                                        expression,
-                                        double_brace_location: _,
-                                        binding_map_keys: Some(binding_map_keys),
-                                    } = value
-                                    {
-                                        if !binding_map_keys.is_empty(bmc) {
-                                            binding_map_keys.to_proc_gen_write_map(
-                                                w,
-                                                bmc,
-                                                |w| {
-                                                    let p = expression
-                                                        .to_proc_gen_prepare(w, scopes)?;
-                                                    w.expr_stmt(|w| {
-                                                        write!(w, "R.wi(N,{},Y(", i)?;
-                                                        p.value_expr(w)?;
-                                                        write!(w, "))")?;
-                                                        Ok(())
-                                                    })?;
-                                                    w.expr_stmt(|w| {
-                                                        write!(w, "E(N)")?;
-                                                        Ok(())
-                                                    })
-                                                },
-                                            )?;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        for attr in worklet_attributes.iter() {
-                            attr.to_proc_gen_as_worklet_property(w, scopes, bmc)?;
-                        }
-                        for attr in change_attributes.iter() {
-                            attr.to_proc_gen_as_change_property(w, scopes, bmc)?;
-                        }
-                        for attr in attributes.iter() {
-                            attr.to_proc_gen_as_normal(w, scopes, bmc)?;
-                        }
-                        common.to_proc_gen_without_slot(w, scopes, bmc)?;
-                        if let SlotKind::Dynamic(p) = &slot_kind {
-                            if let Some((
-                                _,
-                                Value::Dynamic {
-                                    binding_map_keys, ..
-                                },
-                            )) = common.slot.as_ref()
-                            {
-                                if let Some(binding_map_keys) = binding_map_keys {
-                                    if !binding_map_keys.is_empty(bmc) {
-                                        binding_map_keys.to_proc_gen_write_map(w, bmc, |w| {
-                                            w.expr_stmt(|w| {
-                                                write!(w, "R.s(N,")?;
-                                                p.value_expr(w)?;
-                                                write!(w, ")")?;
-                                                Ok(())
-                                            })
-                                        })?;
-                                    }
-                                }
-                            }
-                        }
-                        Ok(())
-                    })?;
+                    self.write_normal_tag_name(w)?;
+                    self.write_normal_property_init(w, scopes, bmc, group, &slot_kind)?;
                     write!(w, ",{}", child_ident)?;
                     if common.slot.is_some() || var_slot_names.is_some() {
                         write!(w, ",")?;
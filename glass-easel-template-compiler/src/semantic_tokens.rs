@@ -0,0 +1,603 @@
+//! Semantic token classification of templates.
+//!
+//! The tokens are collected from the template tree,
+//! so scope references and data fields, or event bindings and plain attributes, are distinguished.
+//! The source text is only used to find the tag names and the entities.
+
+use std::ops::Range;
+
+use crate::parse::{
+    expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
+    tag::{
+        Attribute, ClassAttribute, Comment, CommonElementAttributes, Element, ElementKind,
+        EventBinding, ImportElement, IncludeElement, NormalAttribute, NormalAttributePrefix,
+        Script, StaticAttribute, StyleAttribute, TagLocation, TemplateDefinition, UnknownMetaTag,
+        Value,
+    },
+    visit::{self, Visit},
+    visit_scope::{self, Scope, ScopeVisit},
+    LineIndex, Position, Template,
+};
+
+/// The type of a semantic token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SemanticTokenType {
+    /// A tag name, in start tags and end tags.
+    TagName,
+    /// An attribute prefix, e.g. the `bind` in `bind:tap` .
+    AttributePrefix,
+    /// An attribute name.
+    AttributeName,
+    /// A field of the template data.
+    DataField,
+    /// A scope variable, i.e. a `wx:for-item` , a `wx:for-index` , a `slot:` value or a `let:` variable.
+    ScopeRef,
+    /// A `<wxs>` module.
+    WxsModule,
+    /// A member field or an object key in expressions.
+    Property,
+    /// A string literal.
+    String,
+    /// A number literal.
+    Number,
+    /// A `true` `false` `null` or `undefined` literal.
+    Keyword,
+    /// An operator in expressions.
+    Operator,
+    /// A comment.
+    Comment,
+    /// An HTML entity, e.g. `&amp;` .
+    Entity,
+}
+
+impl SemanticTokenType {
+    /// The token types in the order of the LSP encoding, i.e. the `tokenTypes` of the legend.
+    pub const LEGEND: [&'static str; 13] = [
+        "tagName",
+        "attributePrefix",
+        "attributeName",
+        "dataField",
+        "scopeRef",
+        "wxsModule",
+        "property",
+        "string",
+        "number",
+        "keyword",
+        "operator",
+        "comment",
+        "entity",
+    ];
+
+    /// The index in the legend.
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+/// A classified token range.
+///
+/// A token never spans multiple lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SemanticToken {
+    pub location: Range<Position>,
+    pub kind: SemanticTokenType,
+    /// Whether the token declares a scope variable or a `<wxs>` module.
+    pub is_declaration: bool,
+}
+
+impl SemanticToken {
+    /// The token modifiers in the order of the LSP encoding, i.e. the `tokenModifiers` of the legend.
+    pub const MODIFIERS_LEGEND: [&'static str; 1] = ["declaration"];
+
+    /// Encode tokens in the LSP semantic tokens format.
+    ///
+    /// Each token is encoded as five integers: the line delta, the start column delta,
+    /// the length, the token type and the token modifier bits.
+    /// The tokens should be sorted.
+    pub fn encode(tokens: &[Self]) -> Vec<u32> {
+        let mut ret = Vec::with_capacity(tokens.len() * 5);
+        let mut prev = Position::default();
+        for token in tokens {
+            let start = token.location.start;
+            let delta_line = start.line - prev.line;
+            let delta_col = if delta_line == 0 {
+                start.utf16_col - prev.utf16_col
+            } else {
+                start.utf16_col
+            };
+            ret.push(delta_line);
+            ret.push(delta_col);
+            ret.push(token.location.end.utf16_col - start.utf16_col);
+            ret.push(token.kind.index());
+            ret.push(if token.is_declaration { 1 } else { 0 });
+            prev = start;
+        }
+        ret
+    }
+}
+
+struct Collector<'a> {
+    src: &'a str,
+    line_index: LineIndex,
+    /// The token types of the scope variables in range.
+    scopes: Vec<SemanticTokenType>,
+    list: Vec<SemanticToken>,
+}
+
+impl<'a> Collector<'a> {
+    fn add(&mut self, location: Range<Position>, kind: SemanticTokenType) {
+        self.list.push(SemanticToken {
+            location,
+            kind,
+            is_declaration: false,
+        });
+    }
+
+    fn declare(&mut self, location: Range<Position>, kind: SemanticTokenType) {
+        self.list.push(SemanticToken {
+            location,
+            kind,
+            is_declaration: true,
+        });
+    }
+
+    /// Add the tag name which starts at the position.
+    fn tag_name_at(&mut self, pos: Position) {
+        let start = self.line_index.byte_index(self.src, pos);
+        let len: u32 = self.src[start..]
+            .chars()
+            .take_while(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | ':' | '.'))
+            .map(|ch| ch.len_utf16() as u32)
+            .sum();
+        let end = Position {
+            line: pos.line,
+            utf16_col: pos.utf16_col + len,
+        };
+        self.add(pos..end, SemanticTokenType::TagName);
+    }
+
+    fn tag(&mut self, tag_location: &TagLocation) {
+        self.tag_name_at(tag_location.start.0.end);
+        if tag_location.end.is_some() {
+            // the end tag name follows the `/`
+            self.tag_name_at(tag_location.close.end);
+        }
+    }
+
+    /// Add the name of a `wx:` attribute, e.g. `wx:if` .
+    fn wx_attr_name(&mut self, location: &Range<Position>) {
+        let start = location.start;
+        let prefix_end = Position {
+            line: start.line,
+            utf16_col: start.utf16_col + 2,
+        };
+        let name_start = Position {
+            line: start.line,
+            utf16_col: start.utf16_col + 3,
+        };
+        self.add(start..prefix_end, SemanticTokenType::AttributePrefix);
+        self.add(name_start..location.end, SemanticTokenType::AttributeName);
+    }
+
+    fn prefix(&mut self, prefix_location: &Option<Range<Position>>) {
+        if let Some(location) = prefix_location {
+            self.add(location.clone(), SemanticTokenType::AttributePrefix);
+        }
+    }
+
+    fn entities(&mut self, location: &Range<Position>) {
+        let start = self.line_index.byte_index(self.src, location.start);
+        let end = self.line_index.byte_index(self.src, location.end);
+        let s = &self.src[start..end];
+        let mut i = 0;
+        while let Some(offset) = s[i..].find('&') {
+            let begin = i + offset;
+            let name_len = s[begin + 1..]
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '#'))
+                .unwrap_or(s.len() - begin - 1);
+            i = begin + 1 + name_len;
+            if name_len > 0 && s[i..].starts_with(';') {
+                i += 1;
+                let loc = self.line_index.position(self.src, start + begin)
+                    ..self.line_index.position(self.src, start + i);
+                self.add(loc, SemanticTokenType::Entity);
+            }
+        }
+    }
+}
+
+impl<'a, 't> Visit<'t> for Collector<'a> {
+    fn visit_template(&mut self, x: &'t Template) {
+        visit_scope::walk_template(self, x);
+    }
+
+    fn visit_import_element(&mut self, x: &'t ImportElement) {
+        self.tag(&x.tag_location);
+        self.add(x.src_location.clone(), SemanticTokenType::AttributeName);
+    }
+
+    fn visit_include_element(&mut self, x: &'t IncludeElement) {
+        self.tag(&x.tag_location);
+        self.add(x.src_location.clone(), SemanticTokenType::AttributeName);
+    }
+
+    fn visit_script(&mut self, x: &'t Script) {
+        self.tag(&x.tag_location());
+        self.add(x.module_location(), SemanticTokenType::AttributeName);
+        if let Script::GlobalRef { src_location, .. } = x {
+            self.add(src_location.clone(), SemanticTokenType::AttributeName);
+        }
+    }
+
+    fn visit_template_definition(&mut self, x: &'t TemplateDefinition) {
+        self.tag(&x.tag_location);
+        self.add(x.name_location.clone(), SemanticTokenType::AttributeName);
+        for node in x.content.iter() {
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_comment(&mut self, x: &'t Comment) {
+        self.add(x.location.clone(), SemanticTokenType::Comment);
+    }
+
+    fn visit_unknown_meta_tag(&mut self, _x: &'t UnknownMetaTag) {}
+
+    fn visit_element(&mut self, x: &'t Element) {
+        self.tag(&x.tag_location);
+        match &x.kind {
+            ElementKind::Normal { .. } => {}
+            ElementKind::Pure { slot, .. } => {
+                if let Some((location, _)) = slot {
+                    self.add(location.clone(), SemanticTokenType::AttributeName);
+                }
+            }
+            ElementKind::For {
+                list,
+                item_name,
+                index_name,
+                key,
+                ..
+            } => {
+                // a `<block>` is unwrapped, otherwise the tag name is added again by the child
+                self.wx_attr_name(&list.0);
+                // the default ones share the location of `wx:for`
+                for location in [&item_name.0, &index_name.0, &key.0] {
+                    if *location != list.0 {
+                        self.wx_attr_name(location);
+                    }
+                }
+            }
+            ElementKind::If {
+                branches,
+                else_branch,
+            } => {
+                for (location, _, _) in branches {
+                    self.wx_attr_name(location);
+                }
+                if let Some((location, _)) = else_branch {
+                    self.wx_attr_name(location);
+                }
+            }
+            ElementKind::TemplateRef { target, data } => {
+                self.add(target.0.clone(), SemanticTokenType::AttributeName);
+                self.add(data.0.clone(), SemanticTokenType::AttributeName);
+            }
+            ElementKind::Include { path } => {
+                self.add(path.0.clone(), SemanticTokenType::AttributeName);
+            }
+            ElementKind::Slot { name, .. } => {
+                self.add(name.0.clone(), SemanticTokenType::AttributeName);
+            }
+        }
+        visit_scope::walk_element(self, x);
+    }
+
+    fn visit_normal_attribute(&mut self, x: &'t NormalAttribute) {
+        if let NormalAttributePrefix::Model(location) = &x.prefix {
+            self.add(location.clone(), SemanticTokenType::AttributePrefix);
+        }
+        self.add(x.name.location.clone(), SemanticTokenType::AttributeName);
+        if let Some(value) = x.value.as_ref() {
+            self.visit_value(value);
+        }
+    }
+
+    fn visit_class_attribute(&mut self, x: &'t ClassAttribute) {
+        match x {
+            ClassAttribute::None => {}
+            ClassAttribute::String(location, value) => {
+                self.add(location.clone(), SemanticTokenType::AttributeName);
+                self.visit_value(value);
+            }
+            ClassAttribute::Multiple(list) => {
+                for (prefix_location, name, value) in list {
+                    self.add(prefix_location.clone(), SemanticTokenType::AttributePrefix);
+                    self.add(name.location.clone(), SemanticTokenType::AttributeName);
+                    if let Some(value) = value {
+                        self.visit_value(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_style_attribute(&mut self, x: &'t StyleAttribute) {
+        match x {
+            StyleAttribute::None => {}
+            StyleAttribute::String(location, value) => {
+                self.add(location.clone(), SemanticTokenType::AttributeName);
+                self.visit_value(value);
+            }
+            StyleAttribute::Multiple(list) => {
+                for (prefix_location, name, value) in list {
+                    self.add(prefix_location.clone(), SemanticTokenType::AttributePrefix);
+                    self.add(name.location.clone(), SemanticTokenType::AttributeName);
+                    self.visit_value(value);
+                }
+            }
+        }
+    }
+
+    fn visit_attribute(&mut self, x: &'t Attribute) {
+        self.prefix(&x.prefix_location);
+        self.add(x.name.location.clone(), SemanticTokenType::AttributeName);
+        if let Some(value) = x.value.as_ref() {
+            self.visit_value(value);
+        }
+    }
+
+    fn visit_static_attribute(&mut self, x: &'t StaticAttribute) {
+        self.prefix(&x.prefix_location);
+        self.add(x.name.location.clone(), SemanticTokenType::AttributeName);
+    }
+
+    fn visit_event_binding(&mut self, x: &'t EventBinding) {
+        self.add(
+            x.prefix_location.clone(),
+            SemanticTokenType::AttributePrefix,
+        );
+        self.add(x.name.location.clone(), SemanticTokenType::AttributeName);
+        if let Some(value) = x.value.as_ref() {
+            self.visit_value(value);
+        }
+    }
+
+    fn visit_common_element_attributes(&mut self, x: &'t CommonElementAttributes) {
+        for (location, _) in x.id.iter().chain(x.slot.iter()) {
+            self.add(location.clone(), SemanticTokenType::AttributeName);
+        }
+        visit::walk_common_element_attributes(self, x);
+    }
+
+    fn visit_value(&mut self, x: &'t Value) {
+        match x {
+            Value::Static { location, .. } => self.entities(location),
+            Value::Dynamic { expression, .. } => {
+                if let Expression::LitStr { .. } = &**expression {
+                    return self.visit_expression(expression);
+                }
+                // the static parts are plain text, while the others are wrapped in `{{ }}`
+                let _ = expression.for_each_static_or_dynamic_part::<()>(|part, _| {
+                    match part {
+                        Expression::LitStr { location, .. } => self.entities(location),
+                        Expression::ToStringWithoutUndefined { value, .. } => {
+                            self.visit_expression(value)
+                        }
+                        _ => self.visit_expression(part),
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, x: &'t Expression) {
+        use SemanticTokenType as T;
+        match x {
+            Expression::ScopeRef { location, index } => {
+                let kind = self.scopes.get(*index).cloned().unwrap_or(T::ScopeRef);
+                self.add(location.clone(), kind);
+            }
+            Expression::DataField { location, .. } => self.add(location.clone(), T::DataField),
+            Expression::ToStringWithoutUndefined { .. } => {}
+            Expression::LitUndefined { location }
+            | Expression::LitNull { location }
+            | Expression::LitBool { location, .. } => self.add(location.clone(), T::Keyword),
+            Expression::LitStr { location, .. } => self.add(location.clone(), T::String),
+            Expression::LitInt { location, .. } | Expression::LitFloat { location, .. } => {
+                self.add(location.clone(), T::Number)
+            }
+            Expression::LitObj { fields, .. } => {
+                for field in fields {
+                    match field {
+                        ObjectFieldKind::Named {
+                            location,
+                            colon_location,
+                            ..
+                        } => {
+                            // the key of `{ a }` is also the value
+                            if colon_location.is_some() {
+                                self.add(location.clone(), T::Property);
+                            }
+                        }
+                        ObjectFieldKind::Spread { location, .. } => {
+                            self.add(location.clone(), T::Operator)
+                        }
+                    }
+                }
+            }
+            Expression::LitArr { fields, .. } => {
+                for field in fields {
+                    if let ArrayFieldKind::Spread { location, .. } = field {
+                        self.add(location.clone(), T::Operator);
+                    }
+                }
+            }
+            Expression::LitTemplateStr {
+                parts,
+                backtick_location,
+            } => {
+                self.add(backtick_location.0.clone(), T::String);
+                for part in parts {
+                    if let TemplateStrPart::Static { location, .. } = part {
+                        self.add(location.clone(), T::String);
+                    }
+                }
+                self.add(backtick_location.1.clone(), T::String);
+            }
+            Expression::StaticMember { field_location, .. } => {
+                self.add(field_location.clone(), T::Property);
+            }
+            Expression::OptionalStaticMember {
+                question_dot_location,
+                field_location,
+                ..
+            } => {
+                self.add(question_dot_location.clone(), T::Operator);
+                self.add(field_location.clone(), T::Property);
+            }
+            Expression::OptionalDynamicMember {
+                question_dot_location,
+                ..
+            }
+            | Expression::OptionalFuncCall {
+                question_dot_location,
+                ..
+            } => self.add(question_dot_location.clone(), T::Operator),
+            Expression::DynamicMember { .. } | Expression::FuncCall { .. } => {}
+            Expression::Cond {
+                question_location,
+                colon_location,
+                ..
+            } => {
+                self.add(question_location.clone(), T::Operator);
+                self.add(colon_location.clone(), T::Operator);
+            }
+            Expression::Reverse { location, .. }
+            | Expression::BitReverse { location, .. }
+            | Expression::Positive { location, .. }
+            | Expression::Negative { location, .. }
+            | Expression::TypeOf { location, .. }
+            | Expression::Void { location, .. }
+            | Expression::Multiply { location, .. }
+            | Expression::Divide { location, .. }
+            | Expression::Remainer { location, .. }
+            | Expression::Plus { location, .. }
+            | Expression::Minus { location, .. }
+            | Expression::LeftShift { location, .. }
+            | Expression::RightShift { location, .. }
+            | Expression::UnsignedRightShift { location, .. }
+            | Expression::Lt { location, .. }
+            | Expression::Gt { location, .. }
+            | Expression::Lte { location, .. }
+            | Expression::Gte { location, .. }
+            | Expression::InstanceOf { location, .. }
+            | Expression::Eq { location, .. }
+            | Expression::Ne { location, .. }
+            | Expression::EqFull { location, .. }
+            | Expression::NeFull { location, .. }
+            | Expression::BitAnd { location, .. }
+            | Expression::BitXor { location, .. }
+            | Expression::BitOr { location, .. }
+            | Expression::LogicAnd { location, .. }
+            | Expression::LogicOr { location, .. }
+            | Expression::NullishCoalescing { location, .. } => {
+                self.add(location.clone(), T::Operator)
+            }
+        }
+        visit::walk_expression(self, x);
+    }
+}
+
+impl<'a, 't> ScopeVisit<'t> for Collector<'a> {
+    fn declare_scope(&mut self, scope: Scope<'t>) {
+        let kind = match scope {
+            Scope::Script(_) => SemanticTokenType::WxsModule,
+            _ => SemanticTokenType::ScopeRef,
+        };
+        // the default `wx:for-item` and `wx:for-index` are not in the source
+        let in_source = match scope {
+            Scope::ForItem { .. } | Scope::ForIndex { .. } => !scope.is_implicit(),
+            _ => true,
+        };
+        if in_source {
+            self.declare(scope.location().clone(), kind);
+        }
+        self.scopes.push(kind);
+    }
+
+    fn end_scopes(&mut self, count: usize) {
+        self.scopes.truncate(self.scopes.len() - count);
+    }
+}
+
+/// Split the tokens which span multiple lines.
+fn split_lines(list: Vec<SemanticToken>, src: &str) -> Vec<SemanticToken> {
+    let line_lens: Vec<u32> = src
+        .split('\n')
+        .map(|x| x.strip_suffix('\r').unwrap_or(x).encode_utf16().count() as u32)
+        .collect();
+    let mut ret = Vec::with_capacity(list.len());
+    for token in list {
+        let Range { start, end } = token.location.clone();
+        if start.line == end.line {
+            ret.push(token);
+            continue;
+        }
+        for line in start.line..=end.line {
+            let line_start = if line == start.line {
+                start.utf16_col
+            } else {
+                0
+            };
+            let line_end = if line == end.line {
+                end.utf16_col
+            } else {
+                line_lens.get(line as usize).cloned().unwrap_or(0)
+            };
+            ret.push(SemanticToken {
+                location: Position {
+                    line,
+                    utf16_col: line_start,
+                }..Position {
+                    line,
+                    utf16_col: line_end,
+                },
+                kind: token.kind,
+                is_declaration: token.is_declaration,
+            });
+        }
+    }
+    ret
+}
+
+/// Classify the tokens of a template, sorted by positions.
+pub(crate) fn semantic_tokens(tree: &Template, src: &str) -> Vec<SemanticToken> {
+    let mut c = Collector {
+        src,
+        line_index: LineIndex::new(src),
+        scopes: vec![],
+        list: vec![],
+    };
+    c.visit_template(tree);
+
+    // drop the empty, duplicated or overlapping ones, while a declaration wins over the name of its attribute
+    let mut list = split_lines(c.list, src);
+    list.sort_by_key(|x| (x.location.start, x.location.end, !x.is_declaration));
+    let mut ret: Vec<SemanticToken> = Vec::with_capacity(list.len());
+    for token in list {
+        if token.location.start >= token.location.end {
+            continue;
+        }
+        if let Some(prev) = ret.last() {
+            if token.location.start < prev.location.end {
+                continue;
+            }
+        }
+        ret.push(token);
+    }
+    ret
+}
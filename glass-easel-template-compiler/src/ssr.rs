@@ -0,0 +1,435 @@
+//! Render templates to HTML strings without a JavaScript engine
+
+use std::fmt::Write;
+
+use crate::escape::{camel_to_dash, escape_html_body, escape_html_quote};
+use crate::eval::{Evaluator, JsValue};
+use crate::parse::{
+    expr::Expression,
+    tag::{Attribute, ClassAttribute, Element, ElementKind, Node, Script, StyleAttribute, Value},
+    Template,
+};
+use crate::{TmplError, TmplGroup};
+
+const MAX_TEMPLATE_DEPTH: usize = 64;
+
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// The host environment of HTML rendering.
+///
+/// All methods have default implementations, so an empty struct can be used when nothing is needed.
+pub trait SsrHost {
+    /// Call a function exported by a script module, e.g. `{{ m.f(a) }}` .
+    ///
+    /// The `module` is the absolute path of a `<wxs src>` ,
+    /// or `{template path}#{module name}` for an inline `<wxs>` .
+    /// Returns `None` if not supported, and the call evaluates to `undefined` .
+    fn call_script(
+        &mut self,
+        module: &str,
+        func: &str,
+        args: Vec<serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        let _ = (module, func, args);
+        None
+    }
+
+    /// Get the HTML content of a `<slot>` .
+    ///
+    /// `values` contains the slot values, i.e. the attributes of the `<slot>` element.
+    /// Returns `None` if the slot is empty.
+    fn render_slot(
+        &mut self,
+        name: &str,
+        values: &serde_json::Map<String, serde_json::Value>,
+    ) -> Option<String> {
+        let _ = (name, values);
+        None
+    }
+}
+
+/// An `SsrHost` that does not support script calls or slot content.
+pub struct DefaultSsrHost;
+
+impl SsrHost for DefaultSsrHost {}
+
+/// Render the template in `path` to an HTML string.
+pub(crate) fn render(
+    group: &TmplGroup,
+    path: &str,
+    data: &serde_json::Value,
+    host: &mut dyn SsrHost,
+) -> Result<String, TmplError> {
+    let tmpl = group.get_tree(path)?;
+    let mut renderer = Renderer {
+        group,
+        host,
+        depth: 0,
+        out: String::new(),
+    };
+    let data = JsValue::from(data);
+    renderer.render_template_content(tmpl, &tmpl.content, &data)?;
+    Ok(renderer.out)
+}
+
+struct Renderer<'g, 'h> {
+    group: &'g TmplGroup,
+    host: &'h mut dyn SsrHost,
+    depth: usize,
+    out: String,
+}
+
+fn script_module_id(tmpl: &Template, script: &Script) -> String {
+    match script {
+        Script::Inline { module_name, .. } => format!("{}#{}", tmpl.path, module_name.name),
+        Script::GlobalRef { src, .. } => crate::path::resolve(&tmpl.path, &src.name),
+    }
+}
+
+impl<'g, 'h> Renderer<'g, 'h> {
+    fn render_template_content(
+        &mut self,
+        tmpl: &'g Template,
+        nodes: &'g [Node],
+        data: &JsValue,
+    ) -> Result<(), TmplError> {
+        if self.depth >= MAX_TEMPLATE_DEPTH {
+            return Err(TmplError {
+                message: format!(
+                    "too many nested templates or includes in {:?} (possibly recursive)",
+                    tmpl.path
+                ),
+            });
+        }
+        self.depth += 1;
+        // script modules are not representable, but their functions can be called through the host
+        let mut scopes = vec![JsValue::Undefined; tmpl.globals.scripts.len()];
+        self.render_nodes(tmpl, nodes, data, &mut scopes)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn eval(
+        &mut self,
+        tmpl: &Template,
+        expr: &Expression,
+        data: &JsValue,
+        scopes: &[JsValue],
+    ) -> Result<JsValue, TmplError> {
+        let host = &mut *self.host;
+        let mut evaluator = Evaluator::new(data, scopes);
+        evaluator.set_call_handler(|func: &Expression, args: Vec<JsValue>| {
+            let (index, field_name) = match func {
+                Expression::StaticMember {
+                    obj, field_name, ..
+                }
+                | Expression::OptionalStaticMember {
+                    obj, field_name, ..
+                } => match &**obj {
+                    Expression::ScopeRef { index, .. } => (*index, field_name),
+                    _ => return Ok(JsValue::Undefined),
+                },
+                _ => return Ok(JsValue::Undefined),
+            };
+            let Some(script) = tmpl.globals.scripts.get(index) else {
+                return Ok(JsValue::Undefined);
+            };
+            let args = args.iter().map(|x| x.to_json()).collect();
+            let ret = host.call_script(&script_module_id(tmpl, script), field_name, args);
+            Ok(ret
+                .as_ref()
+                .map(JsValue::from)
+                .unwrap_or(JsValue::Undefined))
+        });
+        match evaluator.eval(expr) {
+            Ok(x) => Ok(x.into_owned()),
+            Err(err) => Err(TmplError {
+                message: format!(
+                    "{} (in {:?} at {}:{})",
+                    err.message,
+                    tmpl.path,
+                    err.location.start.line + 1,
+                    err.location.start.utf16_col + 1,
+                ),
+            }),
+        }
+    }
+
+    fn eval_value(
+        &mut self,
+        tmpl: &Template,
+        value: &Value,
+        data: &JsValue,
+        scopes: &[JsValue],
+    ) -> Result<JsValue, TmplError> {
+        match value {
+            Value::Static { value, .. } => Ok(JsValue::String(value.to_string())),
+            Value::Dynamic { expression, .. } => self.eval(tmpl, expression, data, scopes),
+        }
+    }
+
+    fn render_nodes(
+        &mut self,
+        tmpl: &'g Template,
+        nodes: &'g [Node],
+        data: &JsValue,
+        scopes: &mut Vec<JsValue>,
+    ) -> Result<(), TmplError> {
+        for node in nodes {
+            match node {
+                Node::Text(value) => {
+                    let v = self.eval_value(tmpl, value, data, scopes)?;
+                    let s = v.to_string_without_undefined();
+                    self.out.push_str(&escape_html_body(&s));
+                }
+                Node::Element(elem) => self.render_element(tmpl, elem, data, scopes)?,
+                Node::Comment(..) | Node::UnknownMetaTag(..) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn push_element_scopes(
+        &mut self,
+        tmpl: &Template,
+        elem: &Element,
+        data: &JsValue,
+        scopes: &mut Vec<JsValue>,
+    ) -> Result<(), TmplError> {
+        // slot values are provided by the component, which does not exist here
+        if let Some(refs) = elem.slot_value_refs() {
+            for _ in refs {
+                scopes.push(JsValue::Undefined);
+            }
+        }
+        if let Some(let_vars) = elem.let_var_refs() {
+            for attr in let_vars {
+                let v = match attr.value.as_ref() {
+                    Some(value) => self.eval_value(tmpl, value, data, scopes)?,
+                    None => JsValue::Undefined,
+                };
+                scopes.push(v);
+            }
+        }
+        Ok(())
+    }
+
+    fn render_element(
+        &mut self,
+        tmpl: &'g Template,
+        elem: &'g Element,
+        data: &JsValue,
+        scopes: &mut Vec<JsValue>,
+    ) -> Result<(), TmplError> {
+        let prev_scope_len = scopes.len();
+        match &elem.kind {
+            ElementKind::Normal {
+                tag_name,
+                attributes,
+                class,
+                style,
+                children,
+                extra_attr,
+                common,
+                ..
+            } => {
+                self.push_element_scopes(tmpl, elem, data, scopes)?;
+                let mut attrs: Vec<(String, JsValue)> = vec![];
+                if let Some((_, value)) = common.id.as_ref() {
+                    let v = self.eval_value(tmpl, value, data, scopes)?;
+                    attrs.push((
+                        "id".to_string(),
+                        JsValue::String(v.to_string_without_undefined().into_owned()),
+                    ));
+                }
+                if let Some((_, value)) = common.slot.as_ref() {
+                    let v = self.eval_value(tmpl, value, data, scopes)?;
+                    attrs.push((
+                        "slot".to_string(),
+                        JsValue::String(v.to_string_without_undefined().into_owned()),
+                    ));
+                }
+                let class = match class {
+                    ClassAttribute::None => String::new(),
+                    ClassAttribute::String(_, value) => {
+                        let v = self.eval_value(tmpl, value, data, scopes)?;
+                        let s = v.to_string_without_undefined();
+                        s.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+                    }
+                    ClassAttribute::Multiple(list) => {
+                        let mut names = vec![];
+                        for (_, name, value) in list {
+                            let enabled = match value {
+                                Some(Value::Dynamic { expression, .. }) => {
+                                    self.eval(tmpl, expression, data, scopes)?.to_boolean()
+                                }
+                                Some(Value::Static { .. }) | None => true,
+                            };
+                            if enabled {
+                                names.push(name.name.as_str());
+                            }
+                        }
+                        names.join(" ")
+                    }
+                };
+                if !class.is_empty() {
+                    attrs.push(("class".to_string(), JsValue::String(class)));
+                }
+                let style = match style {
+                    StyleAttribute::None => String::new(),
+                    StyleAttribute::String(_, value) => {
+                        let v = self.eval_value(tmpl, value, data, scopes)?;
+                        v.to_string_without_undefined().into_owned()
+                    }
+                    StyleAttribute::Multiple(list) => {
+                        let mut s = String::new();
+                        for (_, name, value) in list {
+                            let v = self.eval_value(tmpl, value, data, scopes)?;
+                            write!(s, "{}:{};", name.name, v.to_string_without_undefined())?;
+                        }
+                        s
+                    }
+                };
+                if !style.is_empty() {
+                    attrs.push(("style".to_string(), JsValue::String(style)));
+                }
+                for attr in attributes {
+                    let v = match attr.value.as_ref() {
+                        Some(value) => self.eval_value(tmpl, value, data, scopes)?,
+                        None => JsValue::Bool(true),
+                    };
+                    attrs.push((attr.name.name.to_string(), v));
+                }
+                for attr in extra_attr {
+                    attrs.push((
+                        attr.name.name.to_string(),
+                        JsValue::String(attr.value.name.to_string()),
+                    ));
+                }
+                for attr in common.data.iter() {
+                    let Attribute { name, value, .. } = attr;
+                    let v = match value.as_ref() {
+                        Some(value) => self.eval_value(tmpl, value, data, scopes)?,
+                        None => JsValue::Bool(true),
+                    };
+                    attrs.push((format!("data-{}", camel_to_dash(&name.name)), v));
+                }
+
+                // write the tag like setting attributes in DOM
+                let tag_name = tag_name.name.as_str();
+                write!(self.out, "<{}", tag_name)?;
+                for (name, v) in attrs {
+                    match v {
+                        JsValue::Bool(false) => {}
+                        JsValue::Bool(true) | JsValue::Undefined | JsValue::Null => {
+                            write!(self.out, " {}", name)?;
+                        }
+                        v => {
+                            let s = v.to_js_string();
+                            write!(self.out, r#" {}="{}""#, name, escape_html_quote(&s))?;
+                        }
+                    }
+                }
+                write!(self.out, ">")?;
+                if !VOID_ELEMENTS.contains(&tag_name) {
+                    self.render_nodes(tmpl, children, data, scopes)?;
+                    write!(self.out, "</{}>", tag_name)?;
+                }
+            }
+            ElementKind::Pure { children, .. } => {
+                self.push_element_scopes(tmpl, elem, data, scopes)?;
+                self.render_nodes(tmpl, children, data, scopes)?;
+            }
+            ElementKind::For { list, children, .. } => {
+                let list = self.eval_value(tmpl, &list.1, data, scopes)?;
+                let items: Vec<(JsValue, JsValue)> = match list {
+                    JsValue::Array(arr) => arr
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, x)| (x, JsValue::Number(i as f64)))
+                        .collect(),
+                    JsValue::Object(map) => map
+                        .into_iter()
+                        .map(|(k, x)| (x, JsValue::String(k)))
+                        .collect(),
+                    JsValue::String(s) => s
+                        .encode_utf16()
+                        .enumerate()
+                        .map(|(i, c)| {
+                            let item = JsValue::String(String::from_utf16_lossy(&[c]));
+                            (item, JsValue::Number(i as f64))
+                        })
+                        .collect(),
+                    JsValue::Number(n) => {
+                        let len = if n.fract() == 0. && (0. ..4294967296.).contains(&n) {
+                            n as usize
+                        } else {
+                            0
+                        };
+                        (0..len)
+                            .map(|i| (JsValue::Number(i as f64), JsValue::Number(i as f64)))
+                            .collect()
+                    }
+                    _ => vec![],
+                };
+                for (item, index) in items {
+                    scopes.push(item);
+                    scopes.push(index);
+                    self.render_nodes(tmpl, children, data, scopes)?;
+                    scopes.truncate(prev_scope_len);
+                }
+            }
+            ElementKind::If {
+                branches,
+                else_branch,
+            } => {
+                let mut matched = None;
+                for (_, cond, children) in branches {
+                    if self.eval_value(tmpl, cond, data, scopes)?.to_boolean() {
+                        matched = Some(children);
+                        break;
+                    }
+                }
+                if let Some(children) = matched.or(else_branch.as_ref().map(|(_, x)| x)) {
+                    self.render_nodes(tmpl, children, data, scopes)?;
+                }
+            }
+            ElementKind::TemplateRef { target, data: d } => {
+                let target = self.eval_value(tmpl, &target.1, data, scopes)?;
+                if target.to_boolean() {
+                    let name = target.to_js_string();
+                    if let Some((t, def)) = self.group.find_sub_template(tmpl, &name) {
+                        let d = self.eval_value(tmpl, &d.1, data, scopes)?;
+                        self.render_template_content(t, &def.content, &d)?;
+                    }
+                }
+            }
+            ElementKind::Include { path } => {
+                let p = crate::path::resolve(&tmpl.path, &path.1.name);
+                if let Ok(t) = self.group.get_tree(&p) {
+                    self.render_template_content(t, &t.content, data)?;
+                }
+            }
+            ElementKind::Slot { name, values, .. } => {
+                let name = self.eval_value(tmpl, &name.1, data, scopes)?;
+                let mut map = serde_json::Map::new();
+                for attr in values {
+                    let v = match attr.value.as_ref() {
+                        Some(value) => self.eval_value(tmpl, value, data, scopes)?,
+                        None => JsValue::String(String::new()),
+                    };
+                    map.insert(attr.name.name.to_string(), v.to_json());
+                }
+                let name = name.to_string_without_undefined();
+                if let Some(html) = self.host.render_slot(&name, &map) {
+                    self.out.push_str(&html);
+                }
+            }
+        }
+        scopes.truncate(prev_scope_len);
+        Ok(())
+    }
+}
@@ -2,8 +2,8 @@ use std::fmt::{Result as FmtResult, Write as FmtWrite};
 
 use super::stringifier::*;
 use crate::{
-    escape::gen_lit_str_with_quotes,
-    parse::expr::{ArrayFieldKind, Expression, ObjectFieldKind},
+    escape::{gen_lit_str_with_quotes, gen_lit_template_str_part},
+    parse::expr::{ArrayFieldKind, Expression, ObjectFieldKind, TemplateStrPart},
 };
 
 #[repr(u8)]
@@ -39,9 +39,13 @@ impl ExpressionLevel {
             Expression::LitBool { .. } => ExpressionLevel::Lit,
             Expression::LitObj { .. } => ExpressionLevel::Lit,
             Expression::LitArr { .. } => ExpressionLevel::Lit,
+            Expression::LitTemplateStr { .. } => ExpressionLevel::Lit,
             Expression::StaticMember { .. } => ExpressionLevel::Member,
             Expression::DynamicMember { .. } => ExpressionLevel::Member,
             Expression::FuncCall { .. } => ExpressionLevel::Member,
+            Expression::OptionalStaticMember { .. } => ExpressionLevel::Member,
+            Expression::OptionalDynamicMember { .. } => ExpressionLevel::Member,
+            Expression::OptionalFuncCall { .. } => ExpressionLevel::Member,
             Expression::Reverse { .. } => ExpressionLevel::Unary,
             Expression::BitReverse { .. } => ExpressionLevel::Unary,
             Expression::Positive { .. } => ExpressionLevel::Unary,
@@ -272,6 +276,59 @@ pub(super) fn expression_strigify_write<W: FmtWrite>(
             )?;
         }
 
+        Expression::LitTemplateStr {
+            parts,
+            backtick_location,
+        } => {
+            stringifier.write_token_state(
+                "`",
+                None,
+                &backtick_location.0,
+                StringifierLineState::NoSpaceAfter,
+            )?;
+            for part in parts.iter() {
+                match part {
+                    TemplateStrPart::Static { value, location } => {
+                        stringifier.write_token_state(
+                            &gen_lit_template_str_part(value),
+                            None,
+                            location,
+                            StringifierLineState::NoSpaceAround,
+                        )?;
+                    }
+                    TemplateStrPart::Dynamic {
+                        value,
+                        brace_location,
+                    } => {
+                        stringifier.write_token_state(
+                            "${",
+                            None,
+                            &brace_location.0,
+                            StringifierLineState::NoSpaceAround,
+                        )?;
+                        expression_strigify_write(
+                            value,
+                            stringifier,
+                            ExpressionLevel::Cond,
+                            filter,
+                        )?;
+                        stringifier.write_token_state(
+                            "}",
+                            None,
+                            &brace_location.1,
+                            StringifierLineState::NoSpaceAround,
+                        )?;
+                    }
+                }
+            }
+            stringifier.write_token_state(
+                "`",
+                None,
+                &backtick_location.1,
+                StringifierLineState::NoSpaceBefore,
+            )?;
+        }
+
         Expression::StaticMember {
             obj,
             field_name,
@@ -337,6 +394,85 @@ pub(super) fn expression_strigify_write<W: FmtWrite>(
                 StringifierLineState::ParenEnd,
             )?;
         }
+        Expression::OptionalStaticMember {
+            obj,
+            field_name,
+            question_dot_location,
+            field_location,
+        } => {
+            expression_strigify_write(obj, stringifier, ExpressionLevel::Member, filter)?;
+            stringifier.write_token_state(
+                "?.",
+                None,
+                question_dot_location,
+                StringifierLineState::NoSpaceAround,
+            )?;
+            stringifier.write_token_state(
+                &field_name,
+                Some(&field_name),
+                field_location,
+                StringifierLineState::Normal,
+            )?;
+        }
+        Expression::OptionalDynamicMember {
+            obj,
+            field_name,
+            question_dot_location,
+            bracket_location,
+        } => {
+            expression_strigify_write(obj, stringifier, ExpressionLevel::Member, filter)?;
+            stringifier.write_token_state(
+                "?.",
+                None,
+                question_dot_location,
+                StringifierLineState::NoSpaceAround,
+            )?;
+            stringifier.write_token_state(
+                "[",
+                None,
+                &bracket_location.0,
+                StringifierLineState::ParenCall,
+            )?;
+            expression_strigify_write(&field_name, stringifier, ExpressionLevel::Cond, filter)?;
+            stringifier.write_token_state(
+                "]",
+                None,
+                &bracket_location.1,
+                StringifierLineState::ParenEnd,
+            )?;
+        }
+        Expression::OptionalFuncCall {
+            func,
+            args,
+            question_dot_location,
+            paren_location,
+        } => {
+            expression_strigify_write(func, stringifier, ExpressionLevel::Member, filter)?;
+            stringifier.write_token_state(
+                "?.",
+                None,
+                question_dot_location,
+                StringifierLineState::NoSpaceAround,
+            )?;
+            stringifier.write_token_state(
+                "(",
+                None,
+                &paren_location.0,
+                StringifierLineState::ParenCall,
+            )?;
+            for (index, arg) in args.iter().enumerate() {
+                if index > 0 {
+                    stringifier.write_str_state(",", StringifierLineState::NoSpaceBefore)?;
+                }
+                expression_strigify_write(&arg, stringifier, ExpressionLevel::Cond, filter)?;
+            }
+            stringifier.write_token_state(
+                ")",
+                None,
+                &paren_location.1,
+                StringifierLineState::ParenEnd,
+            )?;
+        }
 
         Expression::Reverse { value, location } => {
             stringifier.write_token_state(
@@ -23,6 +23,7 @@ pub struct Stringifier<'s, W: FmtWrite> {
     smb: Option<SourceMapBuilder>,
     source_path: &'s str,
     options: StringifyOptions,
+    location_override: Option<Range<Position>>,
 }
 
 impl<'s, W: FmtWrite> Stringifier<'s, W> {
@@ -47,6 +48,7 @@ impl<'s, W: FmtWrite> Stringifier<'s, W> {
             smb,
             source_path,
             options,
+            location_override: None,
         }
     }
 
@@ -156,6 +158,32 @@ impl<'s, 't, W: FmtWrite> StringifierBlock<'s, 't, W> {
         ret
     }
 
+    /// Write content of another source inside a new empty scope space.
+    ///
+    /// All tokens written inside are mapped to `location` in the source map,
+    /// unless an outer call has already set a location.
+    pub(super) fn foreign_scope_space(
+        &mut self,
+        location: &Range<Position>,
+        f: impl FnOnce(&mut StringifierBlock<'s, '_, W>) -> FmtResult,
+    ) -> FmtResult {
+        let prev = self.top.location_override.clone();
+        if prev.is_none() {
+            self.top.location_override = Some(location.clone());
+        }
+        let mut scope_names = vec![];
+        let ret = {
+            let mut b = StringifierBlock {
+                top: self.top,
+                indent_level: self.indent_level,
+                scope_names: &mut scope_names,
+            };
+            f(&mut b)
+        };
+        self.top.location_override = prev;
+        ret
+    }
+
     pub(super) fn write_sub_block(
         &mut self,
         f: impl FnOnce(&mut StringifierBlock<'s, '_, W>) -> FmtResult,
@@ -284,6 +312,10 @@ impl<'s, 't, 'u, W: FmtWrite> StringifierLine<'s, 't, 'u, W> {
         location: &Range<Position>,
     ) -> FmtResult {
         let top = &mut self.block.top;
+        let (location, source_text) = match top.location_override.as_ref() {
+            Some(x) => (x, None),
+            None => (location, source_text),
+        };
         if let Some(smb) = top.smb.as_mut() {
             smb.add(
                 top.line,
@@ -477,6 +509,7 @@ pub trait StringifyItem: StringifyLine + Sized {
             smb: None,
             source_path: stringifier.source_path,
             options: stringifier.options,
+            location_override: None,
         };
         top.block(|block| block.write_line(|stringifier| stringifier.inline(self)))
             .ok()?;
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
 use std::fmt::{Result as FmtResult, Write as FmtWrite};
 
 use sourcemap::SourceMap;
 
 use crate::{
-    escape::dash_to_camel,
+    escape::{dash_to_camel, gen_lit_str_with_quotes},
+    group::TmplGroup,
     parse::{expr::*, tag::*, Position, Template, TemplateStructure},
     stringify::{
         expr::{expression_strigify_write, ExpressionLevel},
@@ -15,6 +17,7 @@ trait ConvertedExprWriteBlock {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertCtx<'_>,
     ) -> FmtResult;
 }
 
@@ -27,15 +30,96 @@ trait ConvertedExprWriteInline {
 
 const PRESERVED_VAR_NAMES: [&'static str; 3] = ["component", "data", "methods"];
 
+/// The helpers used by the converted expressions.
+///
+/// The generic slots and the event details of a tag can be declared
+/// by merging into `_TagGenerics_` and `_TagEvents_` in the TS env.
+/// The events of the built-in components are already declared in `_TagEvents_` ,
+/// and the events available on all tags (e.g. `tap` ) are declared in `_CommonEvents_` .
+/// The common fields of event objects can be merged into `_EventFields_` .
 pub(crate) const fn tmpl_converted_expr_runtime_string() -> &'static str {
     r#"
 type _ForIndex_<T> = T extends any[] ? number : T extends { [key: string]: any } ? string : any;
 type _ForItem_<T> = T extends (infer T)[] ? T : T extends { [key: string]: infer V } ? V : any;
 type _ForKey_<T, N extends string> = N extends "*this" ? _ForItem_<T> : _ForItem_<T> extends { [k: string]: any } ? _ForItem_<T>[N] : unknown;
+interface _TagGenerics_ { [tag: string]: { [name: string]: any } }
+type _Generic_<T extends string, N extends string> = T extends keyof _TagGenerics_ ? N extends keyof _TagGenerics_[T] ? _TagGenerics_[T][N] : never : any;
+interface _TapDetail_ { x: number; y: number }
+interface _CommonEvents_ { tap: _TapDetail_; canceltap: _TapDetail_; longpress: _TapDetail_; longtap: _TapDetail_; touchstart: { [k: string]: unknown }; touchmove: { [k: string]: unknown }; touchend: { [k: string]: unknown }; touchcancel: { [k: string]: unknown } }
+interface _InputDetail_ { value: string; cursor: number; keyCode: number }
+interface _TagEvents_ {
+  input: { input: _InputDetail_; focus: { value: string; height: number }; blur: { value: string }; confirm: { value: string }; keyboardheightchange: { height: number; duration: number } };
+  textarea: { input: _InputDetail_; focus: { value: string; height: number }; blur: { value: string }; confirm: { value: string }; linechange: { height: number; heightRpx: number; lineCount: number } };
+  "scroll-view": { scroll: { scrollLeft: number; scrollTop: number; scrollHeight: number; scrollWidth: number; deltaX: number; deltaY: number }; scrolltoupper: { direction: string }; scrolltolower: { direction: string } };
+  swiper: { change: { current: number; source: string }; animationfinish: { current: number; source: string } };
+  image: { load: { width: number; height: number }; error: { errMsg: string } };
+  switch: { change: { value: boolean } };
+  slider: { change: { value: number }; changing: { value: number } };
+  "checkbox-group": { change: { value: string[] } };
+  "radio-group": { change: { value: string } };
+  form: { submit: { value: { [name: string]: any } }; reset: {} };
+}
+interface _EventFields_ { type: string; timeStamp: number; target: { id: string; dataset: { [k: string]: any } } }
+type _CommonEventDetail_<E extends string> = E extends keyof _CommonEvents_ ? _CommonEvents_[E] : any;
+type _EventDetail_<T extends string, E extends string> = T extends keyof _TagEvents_ ? E extends keyof _TagEvents_[T] ? _TagEvents_[T][E] : _CommonEventDetail_<E> : _CommonEventDetail_<E>;
+type _Event_<D, S, M> = _EventFields_ & { detail: D; currentTarget: { id: string; dataset: S }; mark: M & { [k: string]: any } };
+type _EventHandler_<T extends string, E extends string, S, M> = (e: _Event_<_EventDetail_<T, E>, S, M>) => any;
 "#
 }
 
+/// The templates that can be reached while converting a template file.
+struct ConvertCtx<'a> {
+    group: &'a TmplGroup,
+    tree: &'a Template,
+    stack: Vec<(&'a str, Option<&'a str>)>,
+    checkers: HashMap<(&'a str, Option<&'a str>), String>,
+    mark_level: usize,
+}
+
+impl<'a> ConvertCtx<'a> {
+    /// Get the data checker of a sub-template or an included template,
+    /// and whether the template should be inlined here.
+    ///
+    /// A template is only inlined at the first use, together with its checker,
+    /// which is a function typed with the data of that use.
+    /// Other uses call the checker with their data,
+    /// so the output size is linear to the group size.
+    /// The checker is declared with `var` so that the uses in other blocks can call it.
+    fn template_checker(
+        &mut self,
+        tree: &'a Template,
+        name: Option<&'a str>,
+    ) -> Option<(String, bool)> {
+        let key = (tree.path.as_str(), name);
+        if let Some(checker) = self.checkers.get(&key) {
+            return Some((checker.clone(), false));
+        }
+        if self.stack.contains(&key) {
+            return None;
+        }
+        let checker = format!("_tmpl_{}_", self.checkers.len());
+        self.checkers.insert(key, checker.clone());
+        Some((checker, true))
+    }
+
+    fn enter<R>(
+        &mut self,
+        tree: &'a Template,
+        name: Option<&'a str>,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let prev = self.tree;
+        self.tree = tree;
+        self.stack.push((&tree.path, name));
+        let ret = f(self);
+        self.stack.pop();
+        self.tree = prev;
+        ret
+    }
+}
+
 pub(crate) fn generate_tmpl_converted_expr(
+    group: &TmplGroup,
     tree: &Template,
     ts_env: &str,
     runtime: &str,
@@ -47,37 +131,18 @@ pub(crate) fn generate_tmpl_converted_expr(
         minimize: true,
         ..Default::default()
     };
+    let mut ctx = ConvertCtx {
+        group,
+        tree,
+        stack: vec![],
+        checkers: HashMap::new(),
+        mark_level: 0,
+    };
     let mut w = Stringifier::new(ret, &tree.path, None, options);
     w.block(|w| {
         w.write_line(|w| w.write_str(ts_env))?;
         w.write_line(|w| w.write_str(runtime))?;
-        for script in tree.globals.scripts.iter() {
-            let location = &script.module_name().location;
-            let scope_name = &script.module_name().name;
-            w.add_scope_with_ts_keyword_escape(scope_name, &PRESERVED_VAR_NAMES);
-            w.write_line(|w| {
-                let pos = location.start;
-                write_token_series(["const "], &(pos..pos), w)?;
-                w.write_token_state(
-                    scope_name,
-                    Some(scope_name),
-                    &location,
-                    StringifierLineState::Normal,
-                )?;
-                write_token_series(
-                    [
-                        "=", "{", "}", "as", "{", "[", "k", ":", "string", "]", ":", "any", "}",
-                        ";",
-                    ],
-                    &(pos..pos),
-                    w,
-                )
-            })?;
-        }
-
-        // TODO tree.globals.imports
-        // TODO tree.globals.includes
-
+        write_scripts(tree, w)?;
         for tmpl in tree.globals.sub_templates.iter() {
             wrap_brace_block(w, &tmpl.tag_location, |w| {
                 let pos = tmpl.tag_location.start.0.start;
@@ -88,10 +153,12 @@ pub(crate) fn generate_tmpl_converted_expr(
                         w,
                     )
                 })?;
-                tmpl.content.converted_expr_write(w)
+                ctx.enter(tree, Some(&tmpl.name.name), |ctx| {
+                    tmpl.content.converted_expr_write(w, ctx)
+                })
             })?;
         }
-        tree.content.converted_expr_write(w)
+        ctx.enter(tree, None, |ctx| tree.content.converted_expr_write(w, ctx))
     })
     .unwrap();
 
@@ -99,13 +166,43 @@ pub(crate) fn generate_tmpl_converted_expr(
     (s, sm.unwrap())
 }
 
+fn write_scripts<'s, 't, W: FmtWrite>(
+    tree: &Template,
+    w: &mut StringifierBlock<'s, 't, W>,
+) -> FmtResult {
+    for script in tree.globals.scripts.iter() {
+        let location = &script.module_name().location;
+        let scope_name = &script.module_name().name;
+        w.add_scope_with_ts_keyword_escape(scope_name, &PRESERVED_VAR_NAMES);
+        w.write_line(|w| {
+            let pos = location.start;
+            write_token_series(["const "], &(pos..pos), w)?;
+            w.write_token_state(
+                scope_name,
+                Some(scope_name),
+                &location,
+                StringifierLineState::Normal,
+            )?;
+            write_token_series(
+                [
+                    "=", "{", "}", "as", "{", "[", "k", ":", "string", "]", ":", "any", "}", ";",
+                ],
+                &(pos..pos),
+                w,
+            )
+        })?;
+    }
+    Ok(())
+}
+
 impl ConvertedExprWriteBlock for Vec<Node> {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertCtx<'_>,
     ) -> FmtResult {
         for node in self {
-            node.converted_expr_write(w)?;
+            node.converted_expr_write(w, ctx)?;
         }
         Ok(())
     }
@@ -115,10 +212,11 @@ impl ConvertedExprWriteBlock for Node {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertCtx<'_>,
     ) -> FmtResult {
         w.new_scope_space(|w| match self {
             Self::Text(x) => write_dynamic_value(x, w),
-            Self::Element(x) => x.converted_expr_write(w),
+            Self::Element(x) => x.converted_expr_write(w, ctx),
             Self::Comment(_) | Self::UnknownMetaTag(_) => Ok(()),
         })
     }
@@ -188,6 +286,18 @@ fn write_let_vars<'s, 't, W: FmtWrite>(
     Ok(())
 }
 
+/// Check whether the expression is a data path that `model:` can update.
+fn is_model_target(expression: &Expression, script_count: usize) -> bool {
+    match expression {
+        Expression::DataField { .. } => true,
+        Expression::ScopeRef { index, .. } => *index >= script_count,
+        Expression::StaticMember { obj, .. } | Expression::DynamicMember { obj, .. } => {
+            is_model_target(obj, script_count)
+        }
+        _ => false,
+    }
+}
+
 fn write_dynamic_value<'s, 't, W: FmtWrite>(
     x: &Value,
     w: &mut StringifierBlock<'s, 't, W>,
@@ -234,14 +344,22 @@ fn write_slot_value_refs<'s, 't, W: FmtWrite>(
 fn write_event_method<'s, 't, W: FmtWrite>(
     name: &Ident,
     value: &Option<Value>,
+    handler_type: &str,
     w: &mut StringifierBlock<'s, 't, W>,
 ) -> FmtResult {
-    // IDEA impl event typing
     if let Some(value) = value.as_ref() {
+        // each check is in its own block, since the handler types are different
         w.write_line(|w| {
             let pos = name.location.start;
-            write_token_series(["var ", "_event_", ":", "Function", "="], &(pos..pos), w)?;
+            write_token_series(["{", "const ", "_event_", ":"], &(pos..pos), w)?;
             if let Value::Static { value, location } = value {
+                w.write_token_state(
+                    handler_type,
+                    None,
+                    &name.location,
+                    StringifierLineState::Normal,
+                )?;
+                w.write_token_state("=", None, &(pos..pos), StringifierLineState::Normal)?;
                 w.write_token_state("methods", None, &(pos..pos), StringifierLineState::Normal)?;
                 w.write_token_state(".", None, &(pos..pos), StringifierLineState::Normal)?;
                 w.write_token_state(
@@ -251,17 +369,52 @@ fn write_event_method<'s, 't, W: FmtWrite>(
                     StringifierLineState::Normal,
                 )?;
             } else {
+                write_token_series(["Function", "="], &(pos..pos), w)?;
                 value.converted_expr_write(w)?;
             }
-            write_token_series([";"], &(pos..pos), w)
+            write_token_series([";", "}"], &(pos..pos), w)
         })?;
     }
     Ok(())
 }
 
+fn attribute_end(attr: &Attribute) -> Position {
+    match attr.value.as_ref() {
+        Some(value) => value.location_end(),
+        None => attr.name.location.end,
+    }
+}
+
+/// Write an object literal with the attribute values, e.g. `{"a":data.b}` .
+fn write_attribute_object<'s, 't, 'u, W: FmtWrite>(
+    list: &[Attribute],
+    w: &mut StringifierLine<'s, 't, 'u, W>,
+) -> FmtResult {
+    for (i, attr) in list.iter().enumerate() {
+        if i > 0 {
+            w.write_token_state(",", None, &attr.name.location, StringifierLineState::Normal)?;
+        }
+        w.write_token_state(
+            &gen_lit_str_with_quotes(&attr.name.name, false),
+            Some(&attr.name.name),
+            &attr.name.location,
+            StringifierLineState::Normal,
+        )?;
+        let pos = attr.name.location.end;
+        w.write_token_state(":", None, &(pos..pos), StringifierLineState::Normal)?;
+        match attr.value.as_ref() {
+            Some(value) => value.converted_expr_write(w)?,
+            None => w.write_token_state("true", None, &(pos..pos), StringifierLineState::Normal)?,
+        }
+    }
+    Ok(())
+}
+
 fn write_common<'s, 't, W: FmtWrite>(
     common: &CommonElementAttributes,
+    tag_name: &str,
     w: &mut StringifierBlock<'s, 't, W>,
+    ctx: &mut ConvertCtx<'_>,
 ) -> FmtResult {
     let CommonElementAttributes {
         id,
@@ -277,21 +430,83 @@ fn write_common<'s, 't, W: FmtWrite>(
     if let Some(x) = slot {
         write_dynamic_value(&x.1, w)?;
     }
-    for item in data.iter().chain(marks.iter()) {
-        if let Some(value) = item.value.as_ref() {
-            write_dynamic_value(value, w)?;
-        }
+
+    // the `dataset` of the current target
+    if let Some(first) = data.first() {
+        w.write_line(|w| {
+            let pos = first.name.location.start;
+            write_token_series(["const ", "_dataset_", "=", "{"], &(pos..pos), w)?;
+            write_attribute_object(data, w)?;
+            let pos = attribute_end(data.last().unwrap());
+            write_token_series(["}", ";"], &(pos..pos), w)
+        })?;
+    }
+
+    // the `mark` contains the ones of the ancestors
+    if let Some(first) = marks.first() {
+        let level = ctx.mark_level;
+        w.write_line(|w| {
+            let pos = first.name.location.start;
+            write_token_series(["const "], &(pos..pos), w)?;
+            w.write_token_state(
+                &format!("_mark_{}_", level + 1),
+                None,
+                &(pos..pos),
+                StringifierLineState::Normal,
+            )?;
+            write_token_series(["=", "{"], &(pos..pos), w)?;
+            if level > 0 {
+                w.write_token_state("...", None, &(pos..pos), StringifierLineState::Normal)?;
+                w.write_token_state(
+                    &format!("_mark_{}_", level),
+                    None,
+                    &(pos..pos),
+                    StringifierLineState::Normal,
+                )?;
+                w.write_token_state(",", None, &(pos..pos), StringifierLineState::Normal)?;
+            }
+            write_attribute_object(marks, w)?;
+            let pos = attribute_end(marks.last().unwrap());
+            write_token_series(["}", ";"], &(pos..pos), w)
+        })?;
+        ctx.mark_level += 1;
     }
+
     for ev in event_bindings.iter() {
-        write_event_method(&ev.name, &ev.value, w)?;
+        let handler_type = event_handler_type(tag_name, &ev.name.name, common, ctx);
+        write_event_method(&ev.name, &ev.value, &handler_type, w)?;
     }
     Ok(())
 }
 
+/// The type of an event handler, which should be written after the `_dataset_` and `_mark_` .
+fn event_handler_type(
+    tag_name: &str,
+    event_name: &str,
+    common: &CommonElementAttributes,
+    ctx: &ConvertCtx<'_>,
+) -> String {
+    format!(
+        "_EventHandler_<{},{},{},{}>",
+        gen_lit_str_with_quotes(tag_name, false),
+        gen_lit_str_with_quotes(event_name, false),
+        if common.data.is_empty() {
+            "{}"
+        } else {
+            "typeof _dataset_"
+        },
+        match ctx.mark_level {
+            0 => "{}".to_string(),
+            x => format!("typeof _mark_{}_", x),
+        },
+    )
+}
+
 impl ConvertedExprWriteBlock for Element {
     fn converted_expr_write<'s, 't, W: FmtWrite>(
         &self,
         w: &mut StringifierBlock<'s, 't, W>,
+        ctx: &mut ConvertCtx<'_>,
     ) -> FmtResult {
         match &self.kind {
             ElementKind::Normal {
@@ -302,11 +517,12 @@ impl ConvertedExprWriteBlock for Element {
                 change_attributes,
                 worklet_attributes,
                 children,
-                generics: _,
-                extra_attr: _,
+                generics,
+                extra_attr,
                 let_vars,
                 common,
             } => {
+                let mark_level = ctx.mark_level;
                 wrap_brace_block(w, &self.tag_location, |w| {
                     w.write_line(|w| {
                         write_token_series(
@@ -406,6 +622,102 @@ impl ConvertedExprWriteBlock for Element {
                         })?;
                     }
 
+                    // model attributes should also accept the property value
+                    let script_count = ctx.tree.globals.scripts.len();
+                    for attr in attributes {
+                        let (NormalAttributePrefix::Model(_), Some(value)) =
+                            (&attr.prefix, &attr.value)
+                        else {
+                            continue;
+                        };
+                        let Value::Dynamic { expression, .. } = value else {
+                            continue;
+                        };
+                        if !is_model_target(expression, script_count) {
+                            continue;
+                        }
+                        let name = &attr.name;
+                        w.write_line(|w| {
+                            let pos = name.location.start;
+                            write_token_series(["{", "let ", "_model_", "="], &(pos..pos), w)?;
+                            value.converted_expr_write(w)?;
+                            write_token_series(
+                                [";", "_model_", "=", "_tag_", "."],
+                                &(pos..pos),
+                                w,
+                            )?;
+                            w.write_token_state(
+                                &dash_to_camel(&name.name),
+                                Some(&name.name),
+                                &name.location,
+                                StringifierLineState::Normal,
+                            )?;
+                            let pos = name.location.end;
+                            write_token_series([";", "}"], &(pos..pos), w)
+                        })?;
+                    }
+
+                    // generics, each in its own block since the generic types are different
+                    for attr in generics {
+                        let name = &attr.name;
+                        w.write_line(|w| {
+                            let pos = name.location.start;
+                            write_token_series(
+                                ["{", "const ", "_generic_", ":", "_Generic_", "<"],
+                                &(pos..pos),
+                                w,
+                            )?;
+                            w.write_token_state(
+                                &gen_lit_str_with_quotes(&tag_name.name, false),
+                                None,
+                                &(pos..pos),
+                                StringifierLineState::Normal,
+                            )?;
+                            w.write_token_state(
+                                ",",
+                                None,
+                                &(pos..pos),
+                                StringifierLineState::Normal,
+                            )?;
+                            w.write_token_state(
+                                &gen_lit_str_with_quotes(&name.name, false),
+                                Some(&name.name),
+                                &name.location,
+                                StringifierLineState::Normal,
+                            )?;
+                            let pos = name.location.end;
+                            write_token_series([">", "=", "tags", "["], &(pos..pos), w)?;
+                            w.write_token_state(
+                                &gen_lit_str_with_quotes(&attr.value.name, false),
+                                Some(&attr.value.name),
+                                &attr.value.location,
+                                StringifierLineState::Normal,
+                            )?;
+                            let pos = attr.value.location.end;
+                            write_token_series(["]", ";", "}"], &(pos..pos), w)
+                        })?;
+                    }
+
+                    // extra attributes
+                    for attr in extra_attr {
+                        let name = &attr.name;
+                        w.write_line(|w| {
+                            write_token_series(
+                                ["var ", "_extra_attr_", ":", "string", "="],
+                                &name.location,
+                                w,
+                            )?;
+                            w.write_token_state(
+                                &gen_lit_str_with_quotes(&attr.value.name, false),
+                                Some(&attr.value.name),
+                                &attr.value.location,
+                                StringifierLineState::Normal,
+                            )?;
+                            let pos = attr.value.location.end;
+                            write_token_series([";"], &(pos..pos), w)
+                        })?;
+                    }
+
                     // class
                     match class {
                         ClassAttribute::None => {}
@@ -472,19 +784,23 @@ impl ConvertedExprWriteBlock for Element {
                         }
                     }
 
+                    write_common(common, &tag_name.name, w, ctx)?;
+
                     // worklet
                     for attr in worklet_attributes {
                         let v = Value::Static {
                             value: attr.value.name.clone(),
                             location: attr.value.location(),
                         };
-                        write_event_method(&attr.name, &Some(v), w)?;
+                        let handler_type =
+                            event_handler_type(&tag_name.name, &attr.name.name, common, ctx);
+                        write_event_method(&attr.name, &Some(v), &handler_type, w)?;
                     }
 
                     // children
-                    write_common(common, w)?;
-                    children.converted_expr_write(w)
+                    children.converted_expr_write(w, ctx)
                 })?;
+                ctx.mark_level = mark_level;
             }
             ElementKind::Pure {
                 children,
@@ -498,7 +814,7 @@ impl ConvertedExprWriteBlock for Element {
                     if let Some(x) = slot {
                         write_dynamic_value(&x.1, w)?;
                     }
-                    children.converted_expr_write(w)
+                    children.converted_expr_write(w, ctx)
                 })?;
             }
             ElementKind::For {
@@ -607,7 +923,7 @@ impl ConvertedExprWriteBlock for Element {
                             write_token_series([">", ";"], &(key.0.end..key.0.end), w)
                         })?;
                     }
-                    children.converted_expr_write(w)
+                    children.converted_expr_write(w, ctx)
                 })?;
             }
             ElementKind::If {
@@ -616,24 +932,110 @@ impl ConvertedExprWriteBlock for Element {
             } => {
                 for (_loc, cond, children) in branches {
                     write_dynamic_value(cond, w)?;
-                    children.converted_expr_write(w)?;
+                    children.converted_expr_write(w, ctx)?;
                 }
                 if let Some((_loc, children)) = else_branch {
-                    children.converted_expr_write(w)?;
+                    children.converted_expr_write(w, ctx)?;
                 }
             }
-            ElementKind::TemplateRef { target: _, data } => {
-                // IDEA impl template typing
-                write_dynamic_value(&data.1, w)?;
+            ElementKind::TemplateRef { target, data } => {
+                // check the passed data against the target template
+                let target_tmpl = match (&target.1, &data.1) {
+                    (Value::Static { value: name, .. }, Value::Dynamic { .. }) => {
+                        ctx.group.find_sub_template(ctx.tree, name)
+                    }
+                    _ => None,
+                };
+                let Some((tree, tmpl)) = target_tmpl else {
+                    return write_dynamic_value(&data.1, w);
+                };
+                let Some((checker, inline)) = ctx.template_checker(tree, Some(&tmpl.name.name))
+                else {
+                    return write_dynamic_value(&data.1, w);
+                };
+                w.write_line(|w| w.write_token("{", None, &target.0))?;
+                w.write_sub_block(|w| {
+                    w.write_line(|w| {
+                        write_token_series(["const ", "_data_", "="], &data.0, w)?;
+                        data.1.converted_expr_write(w)?;
+                        write_token_series([";"], &data.0, w)
+                    })?;
+                    if inline {
+                        w.foreign_scope_space(&target.0, |w| {
+                            let pos = target.0.start;
+                            w.write_line(|w| {
+                                write_token_series(
+                                    [
+                                        "var ", &checker, "=", "(", "data", ":", "typeof ",
+                                        "_data_", ")", "=>", "{", "}", ";",
+                                    ],
+                                    &(pos..pos),
+                                    w,
+                                )
+                            })?;
+                            w.write_line(|w| {
+                                write_token_series(
+                                    ["{", "const ", "data", "=", "_data_", ";"],
+                                    &(pos..pos),
+                                    w,
+                                )
+                            })?;
+                            w.write_sub_block(|w| {
+                                write_scripts(tree, w)?;
+                                ctx.enter(tree, Some(&tmpl.name.name), |ctx| {
+                                    tmpl.content.converted_expr_write(w, ctx)
+                                })
+                            })?;
+                            w.write_line(|w| write_token_series(["}"], &(pos..pos), w))
+                        })
+                    } else {
+                        w.write_line(|w| {
+                            write_token_series([&checker, "(", "_data_", ")", ";"], &data.0, w)
+                        })
+                    }
+                })?;
+                w.write_line(|w| w.write_token("}", None, &target.0))?;
             }
-            ElementKind::Include { path: _ } => {
-                // IDEA impl include typing
+            ElementKind::Include { path } => {
+                // the included content shares the same data
+                let p = crate::path::resolve(&ctx.tree.path, &path.1.name);
+                let Ok(tree) = ctx.group.get_tree(&p) else {
+                    return Ok(());
+                };
+                let Some((checker, inline)) = ctx.template_checker(tree, None) else {
+                    return Ok(());
+                };
+                if !inline {
+                    return w.write_line(|w| {
+                        write_token_series([&checker, "(", "data", ")", ";"], &path.0, w)
+                    });
+                }
+                w.write_line(|w| w.write_token("{", None, &path.0))?;
+                w.write_sub_block(|w| {
+                    w.foreign_scope_space(&path.0, |w| {
+                        let pos = path.0.start;
+                        w.write_line(|w| {
+                            write_token_series(
+                                [
+                                    "var ", &checker, "=", "(", "_data_", ":", "typeof ", "data",
+                                    ")", "=>", "{", "}", ";",
+                                ],
+                                &(pos..pos),
+                                w,
+                            )
+                        })?;
+                        write_scripts(tree, w)?;
+                        ctx.enter(tree, None, |ctx| tree.content.converted_expr_write(w, ctx))
+                    })
+                })?;
+                w.write_line(|w| w.write_token("}", None, &path.0))?;
             }
             ElementKind::Slot {
                 name: _,
                 values,
                 common,
             } => {
+                let mark_level = ctx.mark_level;
                 wrap_brace_block(w, &self.tag_location, |w| {
                     write_slot_value_refs(&common.slot_value_refs, w)?;
                     for attr in values {
@@ -642,8 +1044,9 @@ impl ConvertedExprWriteBlock for Element {
                             write_dynamic_value(value, w)?;
                         }
                     }
-                    write_common(common, w)
+                    write_common(common, "slot", w, ctx)
                 })?;
+                ctx.mark_level = mark_level;
             }
         }
         Ok(())
@@ -876,7 +1279,7 @@ mod test {
     fn convert(src: &str) -> (String, SourceMap) {
         let mut group = crate::TmplGroup::new();
         group.add_tmpl("TEST", src);
-        generate_tmpl_converted_expr(group.get_tree("TEST").unwrap(), "", "")
+        generate_tmpl_converted_expr(&group, group.get_tree("TEST").unwrap(), "", "")
     }
 
     fn find_token(sm: &SourceMap, line: u32, col: u32) -> Option<(u32, u32)> {
@@ -1005,6 +1408,32 @@ mod test {
         assert_eq!(find_token(&sm, 0, 17), Some((0, 8)));
     }
 
+    #[test]
+    fn expr_optional_chain() {
+        let src = r#"{{ obj?.a?.[b]?.(c) }}"#;
+        let expect = r#"data.obj?.a?.[data.b]?.(data.c);"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 5), Some((0, 3)));
+        assert_eq!(find_token(&sm, 0, 8), Some((0, 6)));
+        assert_eq!(find_token(&sm, 0, 10), Some((0, 8)));
+    }
+
+    #[test]
+    fn expr_template_str() {
+        let src = r#"{{ `a${ b }c` }}"#;
+        let expect = r#"`a${data.b}c`;"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 0), Some((0, 3)));
+        assert_eq!(find_token(&sm, 0, 1), Some((0, 4)));
+        assert_eq!(find_token(&sm, 0, 2), Some((0, 5)));
+        assert_eq!(find_token(&sm, 0, 9), Some((0, 8)));
+        assert_eq!(find_token(&sm, 0, 10), Some((0, 10)));
+        assert_eq!(find_token(&sm, 0, 11), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 12), Some((0, 12)));
+    }
+
     #[test]
     fn expr_object_shortcut() {
         let src = r#"{{ a: 1, b: 2 }}"#;
@@ -1086,47 +1515,48 @@ mod test {
     #[test]
     fn element_data() {
         let src = r#"<view data:a="{{ a }}" />"#;
-        let expect = r#"{const _tag_=tags['view'];data.a;}"#;
+        let expect = r#"{const _tag_=tags['view'];const _dataset_={"a":data.a};}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
-        assert_eq!(find_token(&sm, 0, 26), Some((0, 17)));
-        assert_eq!(find_token(&sm, 0, 31), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 43), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 47), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 52), Some((0, 17)));
     }
 
     #[test]
     fn element_mark() {
         let src = r#"<view mark:a="{{ a }}" />"#;
-        let expect = r#"{const _tag_=tags['view'];data.a;}"#;
+        let expect = r#"{const _tag_=tags['view'];const _mark_1_={"a":data.a};}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
-        assert_eq!(find_token(&sm, 0, 26), Some((0, 17)));
-        assert_eq!(find_token(&sm, 0, 31), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 46), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 51), Some((0, 17)));
     }
 
     #[test]
     fn element_event() {
         let src = r#"<view bind:a="b" />"#;
-        let expect = r#"{const _tag_=tags['view'];var _event_:Function=methods.b;}"#;
+        let expect = r#"{const _tag_=tags['view'];{const _event_:_EventHandler_<"view","a",{},{}>=methods.b;}}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
         assert_eq!(find_token(&sm, 0, 26), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 30), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 38), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 47), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 55), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 33), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 41), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 74), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 82), Some((0, 14)));
     }
 
     #[test]
     fn element_worklet() {
         let src = r#"<view worklet:a="b" />"#;
-        let expect = r#"{const _tag_=tags['view'];var _event_:Function=methods.b;}"#;
+        let expect = r#"{const _tag_=tags['view'];{const _event_:_EventHandler_<"view","a",{},{}>=methods.b;}}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
         assert_eq!(find_token(&sm, 0, 26), Some((0, 14)));
-        assert_eq!(find_token(&sm, 0, 30), Some((0, 14)));
-        assert_eq!(find_token(&sm, 0, 38), Some((0, 14)));
-        assert_eq!(find_token(&sm, 0, 47), Some((0, 14)));
-        assert_eq!(find_token(&sm, 0, 55), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 33), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 41), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 74), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 82), Some((0, 17)));
     }
 
     #[test]
@@ -1221,34 +1651,34 @@ mod test {
     #[test]
     fn slot_tag_data() {
         let src = r#"<slot data:a="{{ a }}" />"#;
-        let expect = r#"{data.a;}"#;
+        let expect = r#"{const _dataset_={"a":data.a};}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
-        assert_eq!(find_token(&sm, 0, 1), Some((0, 17)));
-        assert_eq!(find_token(&sm, 0, 6), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 22), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 27), Some((0, 17)));
     }
 
     #[test]
     fn slot_tag_mark() {
         let src = r#"<slot mark:a="{{ a }}" />"#;
-        let expect = r#"{data.a;}"#;
+        let expect = r#"{const _mark_1_={"a":data.a};}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
-        assert_eq!(find_token(&sm, 0, 1), Some((0, 17)));
-        assert_eq!(find_token(&sm, 0, 6), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 21), Some((0, 17)));
+        assert_eq!(find_token(&sm, 0, 26), Some((0, 17)));
     }
 
     #[test]
     fn slot_tag_event() {
         let src = r#"<slot bind:a="b" />"#;
-        let expect = r#"{var _event_:Function=methods.b;}"#;
+        let expect = r#"{{const _event_:_EventHandler_<"slot","a",{},{}>=methods.b;}}"#;
         let (out, sm) = convert(src);
         assert_eq!(out, expect);
         assert_eq!(find_token(&sm, 0, 1), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 5), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 13), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 22), Some((0, 11)));
-        assert_eq!(find_token(&sm, 0, 30), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 8), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 16), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 49), Some((0, 11)));
+        assert_eq!(find_token(&sm, 0, 57), Some((0, 14)));
     }
 
     #[test]
@@ -1309,4 +1739,94 @@ mod test {
         assert_eq!(find_token(&sm, 0, 18), Some((0, 22)));
         assert_eq!(find_token(&sm, 0, 23), Some((0, 22)));
     }
+
+    fn convert_group(list: &[(&str, &str)]) -> (String, SourceMap) {
+        let mut group = crate::TmplGroup::new();
+        for (path, src) in list {
+            group.add_tmpl(path, src);
+        }
+        generate_tmpl_converted_expr(&group, group.get_tree(list[0].0).unwrap(), "", "")
+    }
+
+    #[test]
+    fn imported_template_ref() {
+        let src = r#"<import src="b" /><template is="x" data="{{ c: d }}" />"#;
+        let expect = r#"{const _data_=({c:data.d});var _tmpl_0_=(data:typeof _data_)=>{};{const data=_data_;data.c;}}"#;
+        let (out, sm) = convert_group(&[
+            ("TEST", src),
+            ("b", r#"<template name="x">{{ c }}</template>"#),
+        ]);
+        assert_eq!(out, expect);
+        // expressions in the imported template map to the `is` attribute
+        assert_eq!(find_token(&sm, 0, 84), Some((0, 28)));
+        assert_eq!(find_token(&sm, 0, 18), Some((0, 47)));
+    }
+
+    #[test]
+    fn recursive_template_ref() {
+        let src = r#"<template name="x"><template is="x" data="{{ a }}" /></template><template is="x" data="{{ b }}" />"#;
+        let expect = r#"{const data:any=0;({a});}{const _data_=({b});var _tmpl_0_=(data:typeof _data_)=>{};{const data=_data_;{const _data_=({a});_tmpl_0_(_data_);}}}"#;
+        let (out, _) = convert(src);
+        assert_eq!(out, expect);
+    }
+
+    #[test]
+    fn template_ref_checked_at_each_use() {
+        let src = r#"<template name="x">{{ c }}</template><template is="x" data="{{ a }}" /><template is="x" data="{{ b }}" />"#;
+        let expect = r#"{const data:any=0;data.c;}{const _data_=({a});var _tmpl_0_=(data:typeof _data_)=>{};{const data=_data_;data.c;}}{const _data_=({b});_tmpl_0_(_data_);}"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        // a mismatched data is reported at the `data` attribute
+        assert_eq!(find_token(&sm, 0, 141), Some((0, 88)));
+    }
+
+    #[test]
+    fn included_content_checked_at_each_use() {
+        let src = r#"<include src="b" /><block wx:for="{{ list }}"><include src="b" /></block>"#;
+        let expect = r#"{var _tmpl_0_=(_data_:typeof data)=>{};data.c;}{const _for_=data.list;const item=0 as unknown as _ForItem_<typeof _for_>;const index=0 as unknown as _ForIndex_<typeof _for_>;_tmpl_0_(data);}"#;
+        let (out, _) = convert_group(&[("TEST", src), ("b", r#"{{ c }}"#)]);
+        assert_eq!(out, expect);
+    }
+
+    #[test]
+    fn included_content() {
+        let src = r#"<wxs module="m">exports.a = 1</wxs><include src="b" />"#;
+        let expect = r#"const m={}as{[k:string]:any};{var _tmpl_0_=(_data_:typeof data)=>{};const m={}as{[k:string]:any};m?.a+data.c;}"#;
+        let (out, sm) = convert_group(&[
+            ("TEST", src),
+            ("b", r#"<wxs module="m">exports.a = 1</wxs>{{ m.a + c }}"#),
+        ]);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 62), Some((0, 44)));
+    }
+
+    #[test]
+    fn model_attributes() {
+        let src = r#"<view model:a-b="{{ c.d }}" model:e="{{ f + 1 }}" />"#;
+        let expect = r#"{const _tag_=tags['view'];_tag_.aB=data.c?.d;_tag_.e=data.f+1;{let _model_=data.c?.d;_model_=_tag_.aB;}}"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 99), Some((0, 12)));
+    }
+
+    #[test]
+    fn generics_and_extra_attributes() {
+        let src = r#"<list generic:item="card" generic:header="title" extra-attr:x="y" />"#;
+        let expect = r#"{const _tag_=tags['list'];{const _generic_:_Generic_<"list","item">=tags["card"];}{const _generic_:_Generic_<"list","header">=tags["title"];}var _extra_attr_:string="y";}"#;
+        let (out, sm) = convert(src);
+        assert_eq!(out, expect);
+        assert_eq!(find_token(&sm, 0, 60), Some((0, 14)));
+        assert_eq!(find_token(&sm, 0, 74), Some((0, 20)));
+        assert_eq!(find_token(&sm, 0, 117), Some((0, 34)));
+        assert_eq!(find_token(&sm, 0, 131), Some((0, 42)));
+        assert_eq!(find_token(&sm, 0, 163), Some((0, 60)));
+    }
+
+    #[test]
+    fn typed_event_handlers() {
+        let src = r#"<view mark:a="{{ 1 }}"><view data:b="c" mark:d="e" capture-catch:tap="f" /><view mut-bind:tap="g" /></view>"#;
+        let expect = r#"{const _tag_=tags['view'];const _mark_1_={"a":1};{const _tag_=tags['view'];const _dataset_={"b":"c"};const _mark_2_={..._mark_1_,"d":"e"};{const _event_:_EventHandler_<"view","tap",typeof _dataset_,typeof _mark_2_>=methods.f;}}{const _tag_=tags['view'];{const _event_:_EventHandler_<"view","tap",{},typeof _mark_1_>=methods.g;}}}"#;
+        let (out, _) = convert(src);
+        assert_eq!(out, expect);
+    }
 }
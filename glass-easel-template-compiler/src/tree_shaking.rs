@@ -0,0 +1,200 @@
+//! Find out the sub-templates, script modules and binding map keys that can be removed from the output.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{
+    parse::{
+        expr::Expression,
+        tag::{Element, ElementKind, Node, Value},
+        visit::{self, Visit},
+    },
+    TmplGroup,
+};
+
+/// A sub-template, a script module or a binding map key in a template file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TreeShakingItem {
+    /// The template path.
+    pub path: String,
+    /// The template name, the script module name or the data field name.
+    pub name: String,
+}
+
+/// The items removed by tree-shaking.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeShakingReport {
+    /// The `<template name>` definitions which are never reachable.
+    pub templates: Vec<TreeShakingItem>,
+    /// The `<wxs>` modules which are never referenced.
+    pub scripts: Vec<TreeShakingItem>,
+    /// The binding map keys which are only referenced in constant-folded expressions.
+    ///
+    /// These are always left out of the generated code, even if tree-shaking is disabled.
+    pub binding_map_keys: Vec<TreeShakingItem>,
+}
+
+impl TreeShakingReport {
+    pub(crate) fn is_template_removed(&self, path: &str, name: &str) -> bool {
+        self.templates
+            .iter()
+            .any(|x| x.path == path && x.name == name)
+    }
+
+    pub(crate) fn is_script_removed(&self, path: &str, name: &str) -> bool {
+        self.scripts
+            .iter()
+            .any(|x| x.path == path && x.name == name)
+    }
+}
+
+impl fmt::Display for TreeShakingReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for x in self.templates.iter() {
+            writeln!(f, "removed template {:?} in {:?}", x.name, x.path)?;
+        }
+        for x in self.scripts.iter() {
+            writeln!(f, "removed wxs module {:?} in {:?}", x.name, x.path)?;
+        }
+        for x in self.binding_map_keys.iter() {
+            writeln!(f, "removed binding map key {:?} in {:?}", x.name, x.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct TemplateRefs<'a> {
+    names: Vec<&'a str>,
+    has_dynamic: bool,
+}
+
+impl<'a> Visit<'a> for TemplateRefs<'a> {
+    fn visit_element(&mut self, x: &'a Element) {
+        if let ElementKind::TemplateRef { target, .. } = &x.kind {
+            match &target.1 {
+                Value::Static { value, .. } => self.names.push(value.as_str()),
+                Value::Dynamic { .. } => self.has_dynamic = true,
+            }
+        }
+        visit::walk_element(self, x);
+    }
+}
+
+struct ScriptRefs {
+    used: Vec<bool>,
+}
+
+impl<'a> Visit<'a> for ScriptRefs {
+    fn visit_expression(&mut self, x: &'a Expression) {
+        if let Expression::ScopeRef { index, .. } = x {
+            // script modules always occupy the smallest scope indices
+            if let Some(used) = self.used.get_mut(*index) {
+                *used = true;
+            }
+        }
+        visit::walk_expression(self, x);
+    }
+}
+
+/// Collect the items that are not used by any template in the group.
+///
+/// The content of each template file is always used, since it may be a page or an include target.
+/// A `<template is>` with a dynamic name keeps all the sub-templates it can see.
+/// A binding map key is removed when constant folding removes all its references.
+pub(crate) fn analyze(group: &TmplGroup) -> TreeShakingReport {
+    let mut reachable: HashSet<(String, String)> = HashSet::new();
+    let mut stack: Vec<(&str, &[Node])> = group
+        .list_template_trees()
+        .map(|(path, tree)| (path, tree.content.as_slice()))
+        .collect();
+    while let Some((path, content)) = stack.pop() {
+        let tree = match group.get_tree(path) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let mut refs = TemplateRefs::default();
+        for node in content {
+            refs.visit_node(node);
+        }
+        let mut targets: Vec<(&str, &str)> = vec![];
+        for name in refs.names {
+            if let Some((t, _)) = group.find_sub_template(tree, name) {
+                targets.push((t.path.as_str(), name));
+            }
+        }
+        if refs.has_dynamic {
+            for t in group.sub_template_sources(tree) {
+                for def in t.globals.sub_templates.iter() {
+                    targets.push((t.path.as_str(), def.name.name.as_str()));
+                }
+            }
+        }
+
+        for (p, name) in targets {
+            if !reachable.insert((p.to_string(), name.to_string())) {
+                continue;
+            }
+            let t = match group.get_tree(p) {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            for def in t.globals.sub_templates.iter() {
+                if def.name.name.as_str() == name {
+                    stack.push((t.path.as_str(), def.content.as_slice()));
+                }
+            }
+        }
+    }
+
+    let mut report = TreeShakingReport::default();
+    for (path, tree) in group.list_template_trees() {
+        let mut scripts = ScriptRefs {
+            used: vec![false; tree.globals.scripts.len()],
+        };
+        for node in tree.content.iter() {
+            scripts.visit_node(node);
+        }
+        for def in tree.globals.sub_templates.iter() {
+            let name = def.name.name.as_str();
+            if reachable.contains(&(path.to_string(), name.to_string())) {
+                for node in def.content.iter() {
+                    scripts.visit_node(node);
+                }
+            } else {
+                report.templates.push(TreeShakingItem {
+                    path: path.to_string(),
+                    name: name.to_string(),
+                });
+            }
+        }
+        for (script, used) in tree.globals.scripts.iter().zip(scripts.used) {
+            if !used {
+                report.scripts.push(TreeShakingItem {
+                    path: path.to_string(),
+                    name: script.module_name().name.to_string(),
+                });
+            }
+        }
+
+        // binding map keys only exist in the content of the template file
+        let Ok(folded) = group.get_folded_tree(path) else {
+            continue;
+        };
+        let kept: HashSet<&str> = folded
+            .globals
+            .binding_map_collector
+            .list_fields()
+            .map(|(key, _)| key)
+            .collect();
+        for (key, _) in tree.globals.binding_map_collector.list_fields() {
+            if !kept.contains(key) {
+                report.binding_map_keys.push(TreeShakingItem {
+                    path: path.to_string(),
+                    name: key.to_string(),
+                });
+            }
+        }
+    }
+    report
+}
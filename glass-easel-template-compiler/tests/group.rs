@@ -1,3 +1,4 @@
+use glass_easel_template_compiler::stringify::SourceMap;
 use glass_easel_template_compiler::*;
 
 #[test]
@@ -34,3 +35,948 @@ fn basic_import() {
         vec!["a".to_string()]
     );
 }
+
+fn apply_edit(src: &str, start: usize, end: usize, new_text: &str) -> (parse::TextEdit, String) {
+    let pos = |index: usize| {
+        let prefix = &src[..index];
+        let line_start = prefix.rfind('\n').map(|x| x + 1).unwrap_or(0);
+        parse::Position {
+            line: prefix.matches('\n').count() as u32,
+            utf16_col: prefix[line_start..].encode_utf16().count() as u32,
+        }
+    };
+    let edit = parse::TextEdit {
+        range: pos(start)..pos(end),
+        new_text: new_text.to_string(),
+    };
+    let new_src = format!("{}{}{}", &src[..start], new_text, &src[end..]);
+    (edit, new_src)
+}
+
+fn check_same_tree(group: &TmplGroup, expected: &TmplGroup) {
+    let tree = group.get_tree("a").unwrap();
+    let expected = expected.get_tree("a").unwrap();
+    assert_eq!(
+        format!("{:?}", tree.content),
+        format!("{:?}", expected.content)
+    );
+    assert_eq!(
+        format!(
+            "{:?} {:?} {:?} {:?}",
+            tree.globals.imports,
+            tree.globals.includes,
+            tree.globals.sub_templates,
+            tree.globals.scripts,
+        ),
+        format!(
+            "{:?} {:?} {:?} {:?}",
+            expected.globals.imports,
+            expected.globals.includes,
+            expected.globals.sub_templates,
+            expected.globals.scripts,
+        ),
+    );
+}
+
+fn check_update(src: &str, edits: &[(&str, &str)]) {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", src);
+    let mut src = src.to_string();
+    for (from, to) in edits {
+        let start = src.find(from).unwrap();
+        let (edit, new_src) = apply_edit(&src, start, start + from.len(), to);
+        src = new_src;
+        let warnings = group.update_tmpl("a", &[edit]).unwrap();
+        let mut expected = TmplGroup::new();
+        let expected_warnings = expected.add_tmpl("a", &src);
+        assert_eq!(warnings, expected_warnings);
+        check_same_tree(&group, &expected);
+        assert_eq!(group.stringify_tmpl("a"), expected.stringify_tmpl("a"));
+    }
+}
+
+#[test]
+fn update_tmpl() {
+    check_update(
+        r#"<view><text>{{ a }}</text></view><view>{{ b }}</view>"#,
+        &[
+            ("{{ a }}", "{{ a + c }}\n{{ d }}"),
+            ("<text>", "<text\n  id=\"{{ e }}\">"),
+            ("{{ d }}", "{{ "),
+            ("{{ ", "{{ f }}"),
+            ("</view><view>", "</view>\n<view>"),
+        ],
+    );
+    check_update(
+        r#"<view wx:if="{{ a }}" /><view> <text>{{ b }}</text> </view><view wx:else />"#,
+        &[
+            ("<text>", "<text wx:elif=\"{{ c }}\">"),
+            ("<view> <text", "<view wx:elif=\"{{ d }}\"> <text"),
+            ("{{ b }}", "{{ b }}<view></view>"),
+        ],
+    );
+    check_update(
+        r#"<view><block let:x="{{ y }}" let:y="{{ 1 }}"><text>{{ x }}</text></block></view>"#,
+        &[
+            ("{{ x }}", "{{ y }}"),
+            ("<text>", "<text let:z=\"{{ w }}\" let:w=\"{{ 2 }}\">"),
+            ("<text", "<block><text"),
+        ],
+    );
+    check_update(
+        r#"<template name="t"><view>{{ a }}</view></template><view><text /></view>"#,
+        &[
+            ("{{ a }}", "{{ a.b }}"),
+            ("<text />", "<text>{{ c }}</text>"),
+            ("<view>{{", "<view><template is=\"t\" />{{"),
+            ("<text>", "<text><wxs module=\"m\">1</wxs>"),
+        ],
+    );
+}
+
+#[test]
+fn update_tmpl_every_position() {
+    const SRC: &str = r#"<view class="a {{ b }}">
+  <text wx:if="{{ c }}">{{ d }}</text>
+  <!-- comment -->
+  <text wx:else>中文 {{ e[0] }}</text>
+  <block wx:for="{{ list }}" wx:key="id"><view data:x="{{ item }}" bind:tap="f" /></block>
+  <view><view let:g="{{ h }}">{{ g }}</view></view>
+</view>
+<template name="t"><slot name="{{ i }}" /></template>"#;
+    for (index, _) in SRC.char_indices() {
+        for new_text in ["", "x", "\n", "<", ">"] {
+            let end = if new_text.is_empty() {
+                index + SRC[index..].chars().next().unwrap().len_utf8()
+            } else {
+                index
+            };
+            let (edit, new_src) = apply_edit(SRC, index, end, new_text);
+            let mut group = TmplGroup::new();
+            group.add_tmpl("a", SRC);
+            let warnings = group.update_tmpl("a", &[edit]).unwrap();
+            let mut expected = TmplGroup::new();
+            let expected_warnings = expected.add_tmpl("a", &new_src);
+            assert_eq!(warnings, expected_warnings, "{}", new_src);
+            check_same_tree(&group, &expected);
+        }
+    }
+}
+
+fn validate_results(group: &TmplGroup) -> Vec<(String, parse::ParseErrorKind, u32, u32)> {
+    group
+        .validate()
+        .into_iter()
+        .map(|x| {
+            (
+                x.path,
+                x.kind,
+                x.location.start.utf16_col,
+                x.location.end.utf16_col,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn validate_missing_refs() {
+    use parse::ParseErrorKind::*;
+    const SRC_A: &str = r#"<template name="t" /><template name="u" />"#;
+    const SRC_B: &str = r#"<import src="a" /><import src="c" /><include src="./d" /><wxs module="m" src="/s" /><wxs module="n" src="/x" /><template is="t" /><template is="v" /><template is="{{ w }}" />"#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC_A);
+    group.add_tmpl("b", SRC_B);
+    group.add_script("s", "");
+    assert_eq!(
+        validate_results(&group),
+        vec![
+            ("b".to_string(), TemplateFileNotFound, 31, 32),
+            ("b".to_string(), TemplateFileNotFound, 50, 53),
+            ("b".to_string(), ScriptFileNotFound, 105, 107),
+            ("b".to_string(), TemplateNameNotFound, 144, 145),
+        ],
+    );
+}
+
+#[test]
+fn validate_shadowed_template_names() {
+    use parse::ParseErrorKind::*;
+    const SRC_A: &str = r#"<template name="t" />"#;
+    const SRC_B: &str = r#"<template name="t" /><template name="u" />"#;
+    const SRC_C: &str =
+        r#"<import src="a" /><import src="b" /><template name="u" /><template is="t" />"#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC_A);
+    group.add_tmpl("b", SRC_B);
+    group.add_tmpl("c", SRC_C);
+    assert_eq!(
+        validate_results(&group),
+        vec![
+            ("c".to_string(), ShadowedTemplateName, 31, 32),
+            ("c".to_string(), ShadowedTemplateName, 52, 53),
+        ],
+    );
+}
+
+#[test]
+fn validate_circular_includes() {
+    use parse::ParseErrorKind::*;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", r#"<include src="b" />"#);
+    group.add_tmpl("b", r#"<view><include src="c" /></view><import src="a" />"#);
+    group.add_tmpl("c", r#"<include src="a" />"#);
+    group.add_tmpl("d", r#"<include src="d" /><include src="a" />"#);
+    group.add_tmpl("e", r#"<include src="b" />"#);
+    assert_eq!(
+        validate_results(&group),
+        vec![
+            ("c".to_string(), CircularInclude, 14, 15),
+            ("d".to_string(), CircularInclude, 14, 15),
+        ],
+    );
+}
+
+#[test]
+fn dependency_graph() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "pages/index",
+        r#"<import src="/components/header" /><include src="../c" />"#,
+    );
+    group.add_tmpl(
+        "components/header",
+        r#"<wxs module="m" src="../utils" /><include src="/c" />"#,
+    );
+    group.add_tmpl("c", r#"<include src="d" /><import src="missing" />"#);
+    group.add_tmpl("d", r#"<include src="c" />"#);
+    group.add_tmpl("e", r#"<wxs module="m" src="utils" />"#);
+    group.add_script("utils", "");
+    assert_eq!(
+        group.transitive_dependencies("pages/index").unwrap(),
+        vec!["c", "components/header", "d", "missing"],
+    );
+    assert_eq!(
+        group.transitive_dependencies("c").unwrap(),
+        vec!["c", "d", "missing"],
+    );
+    assert!(group.transitive_dependencies("f").is_err());
+    assert_eq!(
+        group.transitive_script_dependencies("pages/index").unwrap(),
+        vec!["utils"],
+    );
+    assert_eq!(
+        group.transitive_script_dependencies("d").unwrap(),
+        Vec::<String>::new(),
+    );
+    assert_eq!(group.dependents("components/header"), vec!["pages/index"],);
+    assert_eq!(
+        group.dependents("d"),
+        vec!["c", "components/header", "d", "pages/index"],
+    );
+    assert_eq!(group.dependents("missing"), group.dependents("d"));
+    assert_eq!(group.dependents("pages/index"), Vec::<String>::new());
+    assert_eq!(
+        group.script_dependents("utils"),
+        vec!["components/header", "e", "pages/index"],
+    );
+    assert_eq!(
+        group.topological_order(),
+        vec!["d", "c", "components/header", "e", "pages/index"],
+    );
+}
+
+#[test]
+fn stable_gen_object_groups() {
+    const TMPLS: [(&str, &str); 4] = [
+        (
+            "a",
+            r#"<div title="{{ z }}" data:y="{{ y }}">{{ x + w + v }}</div>"#,
+        ),
+        (
+            "b",
+            r#"<import src="a" /><wxs module="m" src="s1" /><view slot:p slot:q slot:r>{{ p + q + r }}</view>"#,
+        ),
+        (
+            "c/d",
+            r#"<include src="../b" /><text>{{ m + n + o }}</text>"#,
+        ),
+        (
+            "e",
+            r#"<wxs module="m" src="s2" /><template name="t">{{ k + l }}</template>"#,
+        ),
+    ];
+    const SCRIPTS: [(&str, &str); 3] =
+        [("s1", "exports.a = 1"), ("s2", "exports.b = 2"), ("s3", "")];
+    let gen = |order: &[usize]| {
+        let mut group = TmplGroup::new();
+        for i in order {
+            if let Some((path, src)) = TMPLS.get(*i) {
+                group.add_tmpl(path, src);
+            }
+            if let Some((path, src)) = SCRIPTS.get(*i) {
+                group.add_script(path, src);
+            }
+        }
+        (
+            group.get_tmpl_gen_object_groups().unwrap(),
+            group.get_wx_gen_object_groups().unwrap(),
+            group.export_all_scripts().unwrap(),
+        )
+    };
+    let expected = gen(&[0, 1, 2, 3]);
+    for _ in 0..8 {
+        assert_eq!(gen(&[3, 2, 1, 0]), expected);
+        assert_eq!(gen(&[1, 3, 0, 2]), expected);
+    }
+    let mut group = TmplGroup::new();
+    group.add_tmpl(TMPLS[0].0, TMPLS[0].1);
+    let out = group.get_tmpl_gen_object(TMPLS[0].0).unwrap();
+    assert!(out.contains(r#"A=A||{"v":new Array(1),"w":new Array(1),"x":new Array(1),"y":new Array(1),"z":new Array(1)}"#));
+}
+
+#[test]
+fn module_output() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "pages/index",
+        r#"<import src="/components/header" /><include src="../c" /><include src="/c" /><wxs module="u" src="/utils/a" /><template is="h" data="{{ u.a }}" />"#,
+    );
+    group.add_tmpl(
+        "components/header",
+        r#"<template name="h"><include src="/c" /></template>"#,
+    );
+    group.add_tmpl(
+        "c",
+        r#"<import src="missing" /><include src="components/header" />"#,
+    );
+    group.add_script(
+        "utils/a",
+        r#"exports.a = require("./b").b + require('/utils/b').b"#,
+    );
+    group.add_script("utils/b", "exports.b = 1");
+    let modules = group.get_all_modules(ModuleFormat::EsModule).unwrap();
+    assert_eq!(
+        modules
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            RUNTIME_MODULE_NAME,
+            "utils/a.wxs.js",
+            "utils/b.wxs.js",
+            "c.wxml.js",
+            "components/header.wxml.js",
+            "pages/index.wxml.js",
+        ],
+    );
+    assert!(modules[0].1.ends_with("export{X,Y,Z,P,Q,D}"));
+    assert_eq!(
+        modules[1].1,
+        r#"import {D} from "../glass-easel-runtime.js";import "./b.wxs.js";export default D("utils/a",(require,exports,module)=>{exports.a = require("./b").b + require('/utils/b').b})"#,
+    );
+    assert!(modules[5].1.starts_with(
+        r#"import {X,Y,Z,P,Q,D} from "../glass-easel-runtime.js";import $A from "../utils/a.wxs.js";import $B from "../components/header.wxml.js";import $C from "../c.wxml.js";var R={"utils/a":$A};var G={get "components/header"(){return $B},get "c"(){return $C}};export default "#
+    ));
+
+    let modules = group.get_all_modules(ModuleFormat::CommonJs).unwrap();
+    assert!(modules[0].1.ends_with("module.exports={X,Y,Z,P,Q,D}"));
+    assert_eq!(
+        group
+            .get_script_module("utils/b", ModuleFormat::CommonJs)
+            .unwrap(),
+        r#"var {D}=require("../glass-easel-runtime.js");module.exports=D("utils/b",(require,exports,module)=>{exports.b = 1})"#,
+    );
+    assert!(group
+        .get_tmpl_module("c", ModuleFormat::CommonJs)
+        .unwrap()
+        .starts_with(
+            r#"var {X,Y,Z,P,Q,D}=require("./glass-easel-runtime.js");var R={};var G={get "components/header"(){return require("./components/header.wxml.js")}};module.exports="#
+        ));
+    assert!(group
+        .get_tmpl_module("missing", ModuleFormat::CommonJs)
+        .is_err());
+}
+
+#[test]
+fn gen_object_source_map() {
+    fn find_src(code: &str, sm: &SourceMap, pat: &str) -> Option<(String, u32, u32)> {
+        let before = &code[..code.find(pat)?];
+        let line = before.matches('\n').count() as u32;
+        let col = before[before.rfind('\n').map(|x| x + 1).unwrap_or(0)..]
+            .encode_utf16()
+            .count() as u32;
+        let token = sm.lookup_token(line, col)?;
+        Some((
+            token.get_source()?.to_string(),
+            token.get_src_line(),
+            token.get_src_col(),
+        ))
+    }
+
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        r#"<wxs module="m">exports.f = function (x) {
+  return x + 1
+}</wxs>
+<view>
+  {{ m.f(hello) }}
+</view>"#,
+    );
+    group.add_script("s", "exports.s = 1\nexports.t = 2");
+
+    let (code, sm) = group.get_tmpl_gen_object_with_source_map("a").unwrap();
+    assert_eq!(code, group.get_tmpl_gen_object("a").unwrap());
+    assert_eq!(
+        find_src(&code, &sm, "D.hello"),
+        Some(("a.wxml".to_string(), 4, 9)),
+    );
+    assert_eq!(
+        find_src(&code, &sm, "exports.f"),
+        Some(("a.wxml".to_string(), 0, 16)),
+    );
+    assert_eq!(
+        find_src(&code, &sm, "  return x + 1"),
+        Some(("a.wxml".to_string(), 1, 0)),
+    );
+    assert_eq!(
+        sm.get_source_contents(0).map(|x| x.starts_with("<wxs")),
+        Some(true),
+    );
+
+    let (code, sm) = group.get_tmpl_gen_object_groups_with_source_map().unwrap();
+    assert_eq!(code, group.get_tmpl_gen_object_groups().unwrap());
+    assert_eq!(
+        find_src(&code, &sm, "exports.t"),
+        Some(("s.wxs".to_string(), 1, 0)),
+    );
+    assert_eq!(
+        find_src(&code, &sm, "D.hello"),
+        Some(("a.wxml".to_string(), 4, 9)),
+    );
+    let (code, _) = group.get_wx_gen_object_groups_with_source_map().unwrap();
+    assert_eq!(code, group.get_wx_gen_object_groups().unwrap());
+}
+
+#[test]
+fn source_map_marker_chars_in_source() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        "<wxs module=\"m\">exports.s = '\u{FDD0}'</wxs>\n<view>\u{FDD0}{{ m.s + '\u{FDD1}' }}{{ a }}</view>",
+    );
+    let (code, _) = group.get_tmpl_gen_object_with_source_map("a").unwrap();
+    assert_eq!(code, group.get_tmpl_gen_object("a").unwrap());
+    assert!(code.contains("exports.s = '\u{FDD0}'"));
+    assert!(code.contains("\\uFDD0"));
+    assert!(code.contains("\\uFDD1"));
+    assert!(code.contains("D.a"));
+}
+
+#[test]
+fn static_subtree_hoisting() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        r#"<view class="a"><text>hello</text><!-- c --><image src="x.png" /></view><view>{{ b }}</view><view><text bind:tap="f">t</text></view><view class="b"><text>hello</text><image src="x.png" /></view>"#,
+    );
+    let code = group.get_tmpl_gen_object("a").unwrap();
+    assert!(code.contains(
+        r#"var a=(R,T,E)=>{var L=R.c,M=R.m,O=R.r;E("text",{},(N,C)=>{},(C,T)=>{T("hello")});E("image",{},(N,C)=>{if(C)O(N,"src","x.png")},(C)=>{})};"#
+    ));
+    assert!(!code.contains(r#"(R,T,E)=>{T("t")}"#));
+    assert_eq!(code.matches(r#"T("hello")"#).count(), 1);
+    assert_eq!(code.matches(r#"=(C,T,E)=>{if(C)a(R,T,E)}"#).count(), 2);
+    assert!(code.contains(r#"=(C,T,E)=>{E("text",{},(N,C)=>{if(C)R.v(N,"tap","f",!1,!1,!1,!1)},"#));
+    assert!(code.contains(r#"=(C,T)=>{if(C){T("t")}}"#));
+    assert!(!code.contains(r#"C?T("hello"):T()"#));
+
+    let (sm_code, _) = group.get_tmpl_gen_object_with_source_map("a").unwrap();
+    assert_eq!(sm_code, code);
+}
+
+#[test]
+fn constant_folding() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        r#"<view id="a{{ 1 + 2 }}" hidden="{{ !false }}" class:x="{{ 0 }}" class:y="{{ 'a' && 1 }}" style:width="{{ 3 }}px"><text>{{ 'a' + 'b' }}</text><text>{{ 1 - 3 }}{{ 0.1 + 0.2 }}</text><text>{{ true ? x : y }}{{ 1 / 0 }}</text></view>"#,
+    );
+    let code = group.get_tmpl_gen_object("a").unwrap();
+    assert!(code.contains(r#"if(C)R.i(N,"a3")"#));
+    assert!(code.contains(r#"O(N,"hidden",true)"#));
+    assert!(code.contains(r#"R.e(N,["y"])"#));
+    assert!(code.contains(r#"R.w(N,["width","3px"])"#));
+    assert!(code.contains(r#"=(C,T)=>{if(C){T("ab")}}"#));
+    assert!(code.contains(r#"=(C,T)=>{if(C){T("-20.30000000000000004")}}"#));
+    assert!(code.contains(r#"T(Y(Y(D.x)+Y(1/0))"#));
+
+    // the folded tree is updated with the template
+    group.add_tmpl("a", r#"<text>{{ 2 * 3 }}</text>"#);
+    let code = group.get_tmpl_gen_object("a").unwrap();
+    assert!(code.contains(r#"=(C,T)=>{if(C){T("6")}}"#));
+    assert!(!code.contains(r#"T("ab")"#));
+}
+
+#[test]
+fn tree_shaking() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "lib",
+        r#"
+            <wxs module="m">exports.f = function () { return 1 }</wxs>
+            <wxs module="n">exports.f = function () { return 2 }</wxs>
+            <template name="a"><template is="b" /></template>
+            <template name="b">{{ m.f() }}</template>
+            <template name="c">{{ n.f() }}</template>
+        "#,
+    );
+    group.add_tmpl("dyn-lib", r#"<template name="d" /><template name="e" />"#);
+    group.add_tmpl(
+        "page",
+        r#"<import src="lib" /><template is="a" /><view hidden="{{ true ? a : b }}" />"#,
+    );
+    group.add_tmpl(
+        "other",
+        r#"<import src="dyn-lib" /><template is="{{ t }}" />"#,
+    );
+    let item = |path: &str, name: &str| TreeShakingItem {
+        path: path.to_string(),
+        name: name.to_string(),
+    };
+    assert_eq!(
+        group.tree_shaking_report(),
+        TreeShakingReport {
+            templates: vec![item("lib", "c")],
+            scripts: vec![item("lib", "n")],
+            binding_map_keys: vec![item("page", "b")],
+        },
+    );
+    let code = group.get_tmpl_gen_object("page").unwrap();
+    assert!(code.contains(r#"A=A||{"a":new Array(1)}"#));
+
+    let code = group.get_tmpl_gen_object("lib").unwrap();
+    assert!(code.contains(r#"H["c"]="#));
+    assert!(code.contains("'lib#n'"));
+    group.set_tree_shaking(true);
+    let code = group.get_tmpl_gen_object("lib").unwrap();
+    assert!(code.contains(r#"H["a"]="#));
+    assert!(code.contains(r#"H["b"]="#));
+    assert!(!code.contains(r#"H["c"]="#));
+    assert!(code.contains("'lib#m'"));
+    assert!(!code.contains("'lib#n'"));
+
+    // the report is updated when the trees change
+    group.remove_tmpl("other");
+    assert!(group
+        .tree_shaking_report()
+        .templates
+        .contains(&item("dyn-lib", "d")));
+}
+
+#[test]
+fn tmpl_group_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<TmplGroup>();
+}
+
+#[test]
+fn data_dependency_report() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "a",
+        r#"
+            <wxs module="m">exports.f = function (x) { return x }</wxs>
+            <view class="{{ a.b }}" hidden="{{ m.f(c[d]) }}" />
+            <block wx:for="{{ list.items }}">
+                <view let:x="{{ item.v }}" let:y="{{ e.f }}">{{ x }}{{ y.g }}</view>
+            </block>
+            <template name="t">{{ label }}</template>
+        "#,
+    );
+    let report = group.data_dependency_report("a").unwrap();
+    assert_eq!(
+        report.data_paths,
+        ["a.b", "c", "d", "e.f", "e.f.g", "list.items"]
+    );
+    assert!(!report.binding_map_disabled);
+    assert_eq!(report.full_update_fields, ["e", "list"]);
+    let bindings: Vec<_> = report
+        .bindings
+        .iter()
+        .map(|x| {
+            (
+                x.sub_template.as_deref(),
+                x.name.as_str(),
+                x.data_paths.join(","),
+                x.binding_map,
+            )
+        })
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            (None, "attr:hidden", "c,d".to_string(), true),
+            (None, "class", "a.b".to_string(), true),
+            (None, "wx:for", "list.items".to_string(), false),
+            (None, "let:x", "list.items".to_string(), false),
+            (None, "let:y", "e.f".to_string(), false),
+            (None, "text", "e.f.g,list.items".to_string(), false),
+            (Some("t"), "text", "label".to_string(), false),
+        ],
+    );
+    assert_eq!(report.bindings[0].location.start.line, 2);
+
+    group.add_tmpl("b", r#"<include src="a" />{{ a }}"#);
+    let report = group.data_dependency_report("b").unwrap();
+    assert!(report.binding_map_disabled);
+    assert_eq!(report.includes, ["a"]);
+    assert!(!report.bindings[0].binding_map);
+}
+
+#[test]
+fn find_definition() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("lib/t", r#"<template name="t">{{ v }}</template>"#);
+    group.add_tmpl("lib/i", r#"<view />"#);
+    group.add_tmpl(
+        "a",
+        r#"<import src="lib/t" /><wxs module="m">exports.a = 1</wxs>
+<block wx:for="{{ list }}" wx:for-item="it"><view let:x="{{ it }}" slot:s>{{ m.a + x + s }}</view></block>
+<template is="t" data="{{ v: 1 }}" /><include src="./lib/i" />{{ it }}"#,
+    );
+    let find = |line, utf16_col| {
+        group
+            .find_definition("a", parse::Position { line, utf16_col })
+            .unwrap()
+            .map(|x| {
+                (
+                    x.path,
+                    x.location.start.line,
+                    x.location.start.utf16_col,
+                    x.location.end.utf16_col,
+                )
+            })
+    };
+    let a = |line, start, end| Some(("a".to_string(), line, start, end));
+    // the `wx:for-item` used in `let:`
+    assert_eq!(find(1, 61), a(1, 40, 42));
+    // the `<wxs>` module, the `let:` variable and the `slot:` value
+    assert_eq!(find(1, 77), a(0, 35, 36));
+    assert_eq!(find(1, 83), a(1, 54, 55));
+    assert_eq!(find(1, 87), a(1, 72, 73));
+    // the cursor just after a name
+    assert_eq!(find(1, 88), a(1, 72, 73));
+    // a declaration itself
+    assert_eq!(find(1, 54), a(1, 54, 55));
+    // the imported sub-template and the files
+    assert_eq!(find(2, 14), Some(("lib/t".to_string(), 0, 16, 17)));
+    assert_eq!(find(0, 15), Some(("lib/t".to_string(), 0, 0, 0)));
+    assert_eq!(find(2, 52), Some(("lib/i".to_string(), 0, 0, 0)));
+    // data fields have no definition
+    assert_eq!(find(2, 66), None);
+    assert_eq!(find(1, 20), None);
+}
+
+#[test]
+fn find_references_and_rename() {
+    let sources = [
+        ("lib/t", r#"<template name="t">{{ v }}</template>"#),
+        (
+            "a",
+            r#"<import src="lib/t" /><template is="t" /><view wx:for="{{ list }}">{{ item }}</view>"#,
+        ),
+        ("b", r#"<import src="lib/t" /><template is="t" />"#),
+        (
+            "c",
+            r#"<template name="t" /><template is="t" /><import src="lib/t" />"#,
+        ),
+        (
+            "d",
+            r#"<block wx:for="{{ list }}" wx:for-item="it"><view let:x="{{ it }}">{{ x + y }}<comp slot:s>{{ s }}</comp></view></block>"#,
+        ),
+    ];
+    let mut group = TmplGroup::new();
+    for (path, src) in sources {
+        group.add_tmpl(path, src);
+    }
+    let find = |group: &TmplGroup, path, utf16_col| {
+        group
+            .find_references(path, parse::Position { line: 0, utf16_col })
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.path, x.location.start.utf16_col))
+            .collect::<Vec<_>>()
+    };
+    // apply the edits to the sources
+    let rename = |group: &TmplGroup, path, utf16_col, name| {
+        let edits = group.rename_symbol(path, parse::Position { line: 0, utf16_col }, name)?;
+        let ret = edits
+            .into_iter()
+            .map(|(path, edits)| {
+                let (_, src) = sources.iter().find(|x| x.0 == path).unwrap();
+                let mut src = src.to_string();
+                for edit in edits {
+                    let start = edit.range.start.utf16_col as usize;
+                    let end = edit.range.end.utf16_col as usize;
+                    src.replace_range(start..end, &edit.new_text);
+                }
+                (path, src)
+            })
+            .collect::<Vec<_>>();
+        Ok::<_, TmplError>(ret)
+    };
+    let at = |path: &str, col| (path.to_string(), col);
+
+    // the imported sub-template, but not the one shadowed in `c`
+    let t_refs = vec![at("a", 36), at("b", 36), at("lib/t", 16)];
+    assert_eq!(find(&group, "a", 36), t_refs);
+    assert_eq!(find(&group, "lib/t", 16), t_refs);
+    assert_eq!(find(&group, "c", 16), vec![at("c", 16), at("c", 35)]);
+    assert_eq!(
+        find(&group, "a", 15),
+        vec![at("a", 13), at("b", 13), at("c", 53)]
+    );
+    // the default `wx:for-item` has no declaration in the source
+    assert_eq!(find(&group, "a", 71), vec![at("a", 70)]);
+    assert_eq!(find(&group, "d", 61), vec![at("d", 40), at("d", 60)]);
+
+    assert_eq!(
+        rename(&group, "a", 71, "it").unwrap(),
+        vec![(
+            "a".to_string(),
+            r#"<import src="lib/t" /><template is="t" /><view wx:for-item="it" wx:for="{{ list }}">{{ it }}</view>"#.to_string(),
+        )]
+    );
+    assert_eq!(
+        rename(&group, "d", 94, "v").unwrap(),
+        vec![(
+            "d".to_string(),
+            r#"<block wx:for="{{ list }}" wx:for-item="it"><view let:x="{{ it }}">{{ x + y }}<comp slot:s="v">{{ v }}</comp></view></block>"#.to_string(),
+        )]
+    );
+    assert_eq!(
+        rename(&group, "lib/t", 16, "u").unwrap(),
+        vec![
+            ("a".to_string(), r#"<import src="lib/t" /><template is="u" /><view wx:for="{{ list }}">{{ item }}</view>"#.to_string()),
+            ("b".to_string(), r#"<import src="lib/t" /><template is="u" />"#.to_string()),
+            ("lib/t".to_string(), r#"<template name="u">{{ v }}</template>"#.to_string()),
+        ]
+    );
+
+    // collisions with other scopes, data fields and sub-templates
+    assert!(rename(&group, "d", 61, "x").is_err());
+    assert!(rename(&group, "d", 61, "y").is_err());
+    assert!(rename(&group, "d", 61, "z").is_ok());
+    assert!(rename(&group, "d", 61, "1a").is_err());
+    assert!(rename(&group, "a", 36, "u").is_ok());
+    group.add_tmpl(
+        "b",
+        r#"<import src="lib/t" /><template is="t" /><template name="u" />"#,
+    );
+    assert!(rename(&group, "a", 36, "u").is_err());
+    // files cannot be renamed
+    assert!(rename(&group, "a", 15, "u").is_err());
+}
+
+#[test]
+fn completions() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("lib/t", r#"<template name="t1" /><template name="t2" />"#);
+    let complete = |group: &mut TmplGroup, src: &str| {
+        group.add_tmpl("a", src);
+        let lines: Vec<_> = src.split('\n').collect();
+        let pos = parse::Position {
+            line: lines.len() as u32 - 1,
+            utf16_col: lines.last().unwrap().encode_utf16().count() as u32,
+        };
+        group
+            .completions("a", pos)
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.label, x.kind))
+            .collect::<Vec<_>>()
+    };
+    let var = |name: &str| (name.to_string(), CompletionKind::Variable);
+
+    // scopes in incomplete expressions, the inner ones first
+    assert_eq!(
+        complete(
+            &mut group,
+            r#"<wxs module="m">var a = "<view>"; exports.a = 1</wxs><view wx:for="{{ list }}">{{ it"#
+        ),
+        vec![
+            var("index"),
+            var("item"),
+            ("m".to_string(), CompletionKind::Module)
+        ],
+    );
+    assert_eq!(
+        complete(&mut group, r#"<view wx:for="{{ list }}" class="{{ it"#),
+        vec![var("index"), var("item")],
+    );
+    assert_eq!(
+        complete(
+            &mut group,
+            r#"<view slot:s let:a="{{ 1 }}">
+<block wx:if="{{ c }}"><view wx:for="{{ a }}" wx:for-item="a" wx:for-index="i">{{ a }}</view></block>
+<view slot:x />{{ "#
+        ),
+        vec![var("a"), var("s")],
+    );
+    assert_eq!(
+        complete(
+            &mut group,
+            r#"<view slot:s><view wx:for="{{ list }}" wx:for-index="s">{{ "#
+        ),
+        vec![var("s"), var("item")],
+    );
+    assert_eq!(complete(&mut group, r#"<view>{{ "}}" + "#), vec![],);
+    assert_eq!(complete(&mut group, r#"<view class="{{ a }}"#), vec![]);
+    assert_eq!(complete(&mut group, r#"<!-- {{ "#), vec![]);
+
+    // sub-templates
+    assert_eq!(
+        complete(
+            &mut group,
+            r#"<import src="lib/t" /><template name="own" /><template is=""#
+        ),
+        vec![
+            ("own".to_string(), CompletionKind::Template),
+            ("t1".to_string(), CompletionKind::Template),
+            ("t2".to_string(), CompletionKind::Template),
+        ],
+    );
+
+    // attribute prefixes
+    let prefixes = complete(&mut group, "<view bi");
+    assert!(prefixes.contains(&("bind:".to_string(), CompletionKind::AttributePrefix)));
+    assert!(prefixes.contains(&(
+        "capture-mut-bind:".to_string(),
+        CompletionKind::AttributePrefix
+    )));
+    assert_eq!(complete(&mut group, r#"<view class="a" "#), prefixes);
+    assert_eq!(complete(&mut group, "<vi"), vec![]);
+
+    // group paths
+    group.add_tmpl("sub/b", "");
+    assert_eq!(
+        complete(&mut group, r#"<import src=""#),
+        vec![
+            ("./lib/t".to_string(), CompletionKind::Path),
+            ("./sub/b".to_string(), CompletionKind::Path),
+        ],
+    );
+
+    // positions before the end of a complete source
+    group.add_tmpl(
+        "a",
+        r#"<wxs module="m">exports.a = 1</wxs><view  class="{{ x }}" /><template is="" /><template name="own" />"#,
+    );
+    let complete_at = |group: &TmplGroup, col: u32| {
+        group
+            .completions(
+                "a",
+                parse::Position {
+                    line: 0,
+                    utf16_col: col,
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.label, x.kind))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(complete_at(&group, 42), prefixes);
+    assert_eq!(complete_at(&group, 40), vec![]);
+    assert_eq!(
+        complete_at(&group, 53),
+        vec![("m".to_string(), CompletionKind::Module)]
+    );
+    assert_eq!(complete_at(&group, 58), prefixes);
+    assert_eq!(
+        complete_at(&group, 74),
+        vec![("own".to_string(), CompletionKind::Template)]
+    );
+
+    // the source is still used after the tree is modified
+    group
+        .set_inline_script_content("a", "m", "exports.b = 2")
+        .unwrap();
+    assert_eq!(
+        complete_at(&group, 53),
+        vec![("m".to_string(), CompletionKind::Module)]
+    );
+}
+
+#[test]
+fn semantic_tokens() {
+    let src = r#"<wxs module="m">exports.a = 1</wxs><!-- a
+b -->
+<view wx:for="{{ list }}" wx:for-item="it" bind:tap="f" class="{{ m.a + it.v }}">&lt;{{ s ? 'x' : 1 }}</view>
+<comp let:x="{{ [...l] }}" slot:y>{{ x || y }}</comp>"#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", src);
+    let lines: Vec<_> = src.split('\n').collect();
+    let tokens = group.semantic_tokens("a").unwrap();
+    let list: Vec<_> = tokens
+        .iter()
+        .map(|x| {
+            let line = lines[x.location.start.line as usize];
+            let s = &line[x.location.start.utf16_col as usize..x.location.end.utf16_col as usize];
+            (s, x.kind, x.is_declaration)
+        })
+        .collect();
+    use SemanticTokenType as T;
+    assert_eq!(
+        list,
+        vec![
+            ("wxs", T::TagName, false),
+            ("module", T::AttributeName, false),
+            ("m", T::WxsModule, true),
+            ("wxs", T::TagName, false),
+            ("<!-- a", T::Comment, false),
+            ("b -->", T::Comment, false),
+            ("view", T::TagName, false),
+            ("wx", T::AttributePrefix, false),
+            ("for", T::AttributeName, false),
+            ("list", T::DataField, false),
+            ("wx", T::AttributePrefix, false),
+            ("for-item", T::AttributeName, false),
+            ("it", T::ScopeRef, true),
+            ("bind", T::AttributePrefix, false),
+            ("tap", T::AttributeName, false),
+            ("class", T::AttributeName, false),
+            ("m", T::WxsModule, false),
+            ("a", T::Property, false),
+            ("+", T::Operator, false),
+            ("it", T::ScopeRef, false),
+            ("v", T::Property, false),
+            ("&lt;", T::Entity, false),
+            ("s", T::DataField, false),
+            ("?", T::Operator, false),
+            ("'x'", T::String, false),
+            (":", T::Operator, false),
+            ("1", T::Number, false),
+            ("view", T::TagName, false),
+            ("comp", T::TagName, false),
+            ("let", T::AttributePrefix, false),
+            ("x", T::ScopeRef, true),
+            ("...", T::Operator, false),
+            ("l", T::DataField, false),
+            ("slot", T::AttributePrefix, false),
+            ("y", T::ScopeRef, true),
+            ("x", T::ScopeRef, false),
+            ("||", T::Operator, false),
+            ("y", T::ScopeRef, false),
+            ("comp", T::TagName, false),
+        ]
+    );
+
+    let data = SemanticToken::encode(&tokens);
+    assert_eq!(data.len(), tokens.len() * 5);
+    // `wxs` , `module` , `m`
+    assert_eq!(&data[..15], &[0, 1, 3, 0, 0, 0, 4, 6, 2, 0, 0, 8, 1, 5, 1]);
+    // the second line of the comment
+    assert_eq!(&data[25..30], &[1, 0, 5, 11, 0]);
+}
@@ -0,0 +1,168 @@
+#![cfg(feature = "serde")]
+
+use glass_easel_template_compiler::instr::*;
+use glass_easel_template_compiler::*;
+
+fn compile(src: &str) -> InstrTemplate {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", src);
+    group.get_tmpl_instructions("a").unwrap()
+}
+
+fn data_path(name: &str, fields: &[&str]) -> InstrPath {
+    InstrPath {
+        root: InstrPathRoot::Data(name.to_string()),
+        fields: fields.iter().map(|x| x.to_string()).collect(),
+    }
+}
+
+#[test]
+fn elements_and_props() {
+    let tmpl = compile(
+        r#"<view id="v" class="{{ c }}" data-a-b="{{ x.y[z].w }}" bind:tap="f" model:value="{{ v }}" hidden>{{ 1 + 2 }}</view>"#,
+    );
+    assert_eq!(tmpl.version, INSTR_VERSION);
+    let Instr::Element {
+        tag,
+        props,
+        children,
+        ..
+    } = &tmpl.content[0]
+    else {
+        panic!()
+    };
+    assert_eq!(tag, "view");
+    assert_eq!(children, &[Instr::Text(InstrValue::Static("3".into()))]);
+    assert_eq!(
+        props[0],
+        InstrProp::Class(InstrValue::Dynamic {
+            expr: InstrExpr::Data("c".into()),
+            deps: vec![data_path("c", &[])],
+        })
+    );
+    let InstrProp::Attr { name, model, .. } = &props[1] else {
+        panic!()
+    };
+    assert_eq!((name.as_str(), *model), ("value", true));
+    let InstrProp::Attr { name, value, model } = &props[2] else {
+        panic!()
+    };
+    assert_eq!((name.as_str(), *model), ("hidden", false));
+    assert!(matches!(
+        value,
+        InstrValue::Dynamic {
+            expr: InstrExpr::Bool(true),
+            deps,
+        } if deps.is_empty()
+    ));
+    let InstrProp::Data {
+        name,
+        value: InstrValue::Dynamic { deps, .. },
+        hyphen: true,
+    } = &props[3]
+    else {
+        panic!()
+    };
+    assert_eq!(name, "aB");
+    assert_eq!(deps, &[data_path("x", &["y"]), data_path("z", &[])]);
+    assert!(
+        matches!(&props[4], InstrProp::Event { name, value: InstrValue::Static(v), .. } if name == "tap" && v == "f")
+    );
+    assert_eq!(props[5], InstrProp::Id(InstrValue::Static("v".into())));
+}
+
+#[test]
+fn control_flow_and_scopes() {
+    let tmpl = compile(
+        r#"
+            <wxs module="m">exports.f = function (x) { return x }</wxs>
+            <block wx:for="{{ list }}" wx:key="id">
+                <view wx:if="{{ m.f(item.v) }}">{{ index }}</view>
+                <view wx:else />
+            </block>
+            <slot name="s" value="{{ a }}" />
+            <template is="t" data="{{ ...o }}" />
+            <include src="b" />
+        "#,
+    );
+    assert_eq!(
+        tmpl.scripts,
+        vec![Some(InstrScript::Inline {
+            id: "a#m".into(),
+            code: "exports.f = function (x) { return x }".into(),
+        })]
+    );
+    let Instr::For {
+        list: InstrValue::Dynamic { deps, .. },
+        key,
+        children,
+    } = &tmpl.content[0]
+    else {
+        panic!()
+    };
+    assert_eq!(deps, &[data_path("list", &[])]);
+    assert_eq!(key.as_deref(), Some("id"));
+    let Instr::If { branches } = &children[0] else {
+        panic!()
+    };
+    assert_eq!(branches.len(), 2);
+    assert!(branches[1].cond.is_none());
+    // the script module is not a dependency, but the item scope is
+    let Some(InstrValue::Dynamic { expr, deps }) = &branches[0].cond else {
+        panic!()
+    };
+    assert!(matches!(expr, InstrExpr::Call(..)));
+    assert_eq!(
+        deps,
+        &[InstrPath {
+            root: InstrPathRoot::Scope(1),
+            fields: vec!["v".into()],
+        }]
+    );
+    assert!(
+        matches!(&tmpl.content[1], Instr::Slot { name: InstrValue::Static(n), values, .. } if n == "s" && values[0].0 == "value")
+    );
+    assert!(
+        matches!(&tmpl.content[2], Instr::TemplateRef { target: InstrValue::Static(t), .. } if t == "t")
+    );
+    assert_eq!(tmpl.content[3], Instr::Include { path: "b".into() });
+}
+
+#[test]
+fn serialize_to_json() {
+    let tmpl = compile(r#"<import src="lib" /><template name="x"><text>{{ a }}</text></template>"#);
+    let json = serde_json::to_value(&tmpl).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "version": 1,
+            "path": "a",
+            "imports": ["lib"],
+            "scripts": [],
+            "sub_templates": [{
+                "name": "x",
+                "content": [{
+                    "Element": {
+                        "tag": "text",
+                        "generics": [],
+                        "slot": null,
+                        "slot_value_refs": [],
+                        "let_vars": [],
+                        "props": [],
+                        "children": [{
+                            "Text": {
+                                "Dynamic": {
+                                    "expr": { "Data": "a" },
+                                    "deps": [{ "root": { "Data": "a" }, "fields": [] }],
+                                },
+                            },
+                        }],
+                    },
+                }],
+            }],
+            "content": [],
+        }),
+    );
+    let back: InstrTemplate = serde_json::from_value(json).unwrap();
+    assert_eq!(back, tmpl);
+}
@@ -0,0 +1,351 @@
+use std::io::{BufReader, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use glass_easel_template_compiler::lsp::{read_message, write_message, Server};
+use serde_json::{json, Value};
+
+fn workspace(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!(
+        "glass-easel-lsp-test-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&root);
+    for (path, content) in files {
+        let p = root.join(path);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, content).unwrap();
+    }
+    root
+}
+
+fn uri(root: &Path, path: &str) -> String {
+    format!("file://{}/{}", root.to_str().unwrap(), path)
+}
+
+fn script(messages: &[Value]) -> Vec<u8> {
+    let mut ret = vec![];
+    for msg in messages {
+        write_message(&mut ret, msg).unwrap();
+    }
+    ret
+}
+
+fn parse_output(output: &[u8]) -> Vec<Value> {
+    let mut r = BufReader::new(output);
+    let mut ret = vec![];
+    while let Some(buf) = read_message(&mut r).unwrap() {
+        ret.push(serde_json::from_slice(&buf).unwrap());
+    }
+    ret
+}
+
+fn initialize(root: &Path) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "initialize",
+        "params": { "rootUri": format!("file://{}", root.to_str().unwrap()), "capabilities": {} },
+    })
+}
+
+#[test]
+fn document_sync_and_diagnostics() {
+    let root = workspace(
+        "sync",
+        &[
+            ("a.wxml", "<view />"),
+            ("sub/b.wxml", "<import src=\"../a\" />"),
+        ],
+    );
+    let a = uri(&root, "a.wxml");
+    let input = script(&[
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }),
+        initialize(&root),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": a, "languageId": "wxml", "version": 1, "text": "<view>" } },
+        }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": { "uri": a, "version": 2 },
+                "contentChanges": [{
+                    "range": { "start": { "line": 0, "character": 6 }, "end": { "line": 0, "character": 6 } },
+                    "text": "</view>",
+                }],
+            },
+        }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/unknown", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didClose",
+            "params": { "textDocument": { "uri": a } },
+        }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]);
+    let mut server = Server::new();
+    let mut output = vec![];
+    let code = server.run(&mut Cursor::new(input), &mut output).unwrap();
+    assert_eq!(code, 0);
+    assert!(server.group().contains_template("a"));
+    assert!(server.group().contains_template("sub/b"));
+    assert_eq!(
+        server
+            .group()
+            .direct_dependencies("sub/b")
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec!["a".to_string()]
+    );
+
+    let out = parse_output(&output);
+    assert_eq!(out.len(), 7);
+    assert_eq!(out[0]["error"]["code"], -32002);
+    assert_eq!(out[1]["id"], 0);
+    assert_eq!(
+        out[1]["result"]["capabilities"]["textDocumentSync"]["change"],
+        2
+    );
+    assert_eq!(out[2]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(out[2]["params"]["uri"], a);
+    let diag = &out[2]["params"]["diagnostics"][0];
+    assert_eq!(diag["severity"], 2);
+    assert_eq!(diag["source"], "glass-easel");
+    assert!(diag["code"].is_u64());
+    assert_eq!(diag["range"]["start"], json!({ "line": 0, "character": 1 }));
+    assert_eq!(out[3]["params"]["diagnostics"], json!([]));
+    assert_eq!(out[4]["id"], 2);
+    assert_eq!(out[4]["error"]["code"], -32601);
+    assert_eq!(out[5]["params"]["diagnostics"], json!([]));
+    assert_eq!(out[6], json!({ "jsonrpc": "2.0", "id": 3, "result": null }));
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn stdio_binary() {
+    let root = workspace("stdio", &[("a.wxml", "<view />")]);
+    let input = script(&[
+        initialize(&root),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri(&root, "a.wxml"), "languageId": "wxml", "version": 1, "text": "{{ a" } },
+        }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_glass-easel-wxml-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    // exit without shutdown
+    assert_eq!(output.status.code(), Some(1));
+    let out = parse_output(&output.stdout);
+    assert_eq!(out.len(), 2);
+    assert_eq!(
+        out[0]["result"]["serverInfo"]["name"],
+        "glass-easel-wxml-lsp"
+    );
+    assert_eq!(out[1]["params"]["diagnostics"][0]["severity"], 1);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+fn request(server: &mut Server, id: u64, method: &str, params: Value) -> Value {
+    let mut out = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": params,
+    }));
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0]["id"], id);
+    out.pop().unwrap()
+}
+
+#[test]
+fn definition() {
+    let root = workspace(
+        "definition",
+        &[
+            ("lib/t.wxml", r#"<template name="t">{{ v }}</template>"#),
+            ("a.wxml", r#"<import src="lib/t" /><template is="t" />"#),
+        ],
+    );
+    let mut server = Server::new();
+    request(
+        &mut server,
+        0,
+        "initialize",
+        initialize(&root)["params"].clone(),
+    );
+    let pos = |line, character| {
+        json!({
+            "textDocument": { "uri": uri(&root, "a.wxml") },
+            "position": { "line": line, "character": character },
+        })
+    };
+    let ret = request(&mut server, 1, "textDocument/definition", pos(0, 36));
+    assert_eq!(
+        ret["result"],
+        json!({
+            "uri": uri(&root, "lib/t.wxml"),
+            "range": {
+                "start": { "line": 0, "character": 16 },
+                "end": { "line": 0, "character": 17 },
+            },
+        })
+    );
+    let ret = request(&mut server, 2, "textDocument/definition", pos(0, 2));
+    assert_eq!(ret["result"], Value::Null);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn references_and_rename() {
+    let root = workspace(
+        "rename",
+        &[
+            ("lib/t.wxml", r#"<template name="t">{{ v }}</template>"#),
+            ("a.wxml", r#"<import src="lib/t" /><template is="t" />"#),
+        ],
+    );
+    let mut server = Server::new();
+    request(
+        &mut server,
+        0,
+        "initialize",
+        initialize(&root)["params"].clone(),
+    );
+    let pos = |character, extra: Value| {
+        let mut ret = json!({
+            "textDocument": { "uri": uri(&root, "a.wxml") },
+            "position": { "line": 0, "character": character },
+        });
+        ret.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        ret
+    };
+    let range = |start, end| {
+        json!({
+            "start": { "line": 0, "character": start },
+            "end": { "line": 0, "character": end },
+        })
+    };
+    let ret = request(
+        &mut server,
+        1,
+        "textDocument/references",
+        pos(36, json!({ "context": { "includeDeclaration": true } })),
+    );
+    assert_eq!(
+        ret["result"],
+        json!([
+            { "uri": uri(&root, "a.wxml"), "range": range(36, 37) },
+            { "uri": uri(&root, "lib/t.wxml"), "range": range(16, 17) },
+        ])
+    );
+    let ret = request(
+        &mut server,
+        2,
+        "textDocument/references",
+        pos(36, json!({ "context": { "includeDeclaration": false } })),
+    );
+    assert_eq!(
+        ret["result"],
+        json!([{ "uri": uri(&root, "a.wxml"), "range": range(36, 37) }])
+    );
+    let ret = request(
+        &mut server,
+        3,
+        "textDocument/rename",
+        pos(36, json!({ "newName": "u" })),
+    );
+    assert_eq!(
+        ret["result"]["changes"][uri(&root, "lib/t.wxml")],
+        json!([{ "range": range(16, 17), "newText": "u" }])
+    );
+    assert_eq!(
+        ret["result"]["changes"][uri(&root, "a.wxml")],
+        json!([{ "range": range(36, 37), "newText": "u" }])
+    );
+    let ret = request(
+        &mut server,
+        4,
+        "textDocument/rename",
+        pos(36, json!({ "newName": "a-b" })),
+    );
+    assert_eq!(ret["error"]["code"], -32803);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn completion() {
+    let root = workspace("completion", &[("a.wxml", "<view />")]);
+    let a = uri(&root, "a.wxml");
+    let mut server = Server::new();
+    request(
+        &mut server,
+        0,
+        "initialize",
+        initialize(&root)["params"].clone(),
+    );
+    server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": a, "languageId": "wxml", "version": 1, "text": "<view wx:for=\"{{ list }}\">{{ " } },
+    }));
+    let ret = request(
+        &mut server,
+        1,
+        "textDocument/completion",
+        json!({
+            "textDocument": { "uri": a },
+            "position": { "line": 0, "character": 29 },
+        }),
+    );
+    assert_eq!(
+        ret["result"],
+        json!([
+            { "label": "index", "kind": 6 },
+            { "label": "item", "kind": 6 },
+        ])
+    );
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn semantic_tokens() {
+    let root = workspace("semantic-tokens", &[("a.wxml", "<view>{{ a }}</view>")]);
+    let mut server = Server::new();
+    let ret = request(
+        &mut server,
+        0,
+        "initialize",
+        initialize(&root)["params"].clone(),
+    );
+    let provider = &ret["result"]["capabilities"]["semanticTokensProvider"];
+    assert_eq!(provider["full"], true);
+    assert_eq!(provider["legend"]["tokenTypes"][0], "tagName");
+    assert_eq!(provider["legend"]["tokenTypes"][3], "dataField");
+    assert_eq!(provider["legend"]["tokenModifiers"], json!(["declaration"]));
+    let ret = request(
+        &mut server,
+        1,
+        "textDocument/semanticTokens/full",
+        json!({ "textDocument": { "uri": uri(&root, "a.wxml") } }),
+    );
+    assert_eq!(
+        ret["result"]["data"],
+        json!([0, 1, 4, 0, 0, 0, 8, 1, 3, 0, 0, 6, 4, 0, 0])
+    );
+    let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "serde")]
+
+use glass_easel_template_compiler::*;
+
+fn round_trip(group: &TmplGroup, path: &str) -> parse::Template {
+    let json = serde_json::to_string(group.get_tree(path).unwrap()).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn tree_round_trip() {
+    const SRC: &str = r#"
+        <import src="/b" />
+        <wxs module="m"> exports.f = function (x) { return x + 1 } </wxs>
+        <template name="t"><text>{{ m.f(a) }}</text></template>
+        <view wx:for="{{ list }}" wx:key="id" class="a {{ item.c }}" style="color: {{ c }}" bind:tap="h">
+            <input model:value="{{ v }}" />
+            <block wx:if="{{ index > 0 }}">{{ `${item.x?.y}` }}</block>
+            <template is="t" data="{{ a: item }}" />
+        </view>
+        <!-- comment -->
+        <slot name="s" />
+        <text title="{{ c }}">{{ v }}</text>
+    "#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC);
+    group.add_tmpl("b", "");
+    let mut cached_group = TmplGroup::new();
+    cached_group.add_tmpl_tree(round_trip(&group, "a"));
+    cached_group.add_tmpl_tree(round_trip(&group, "b"));
+    assert_eq!(cached_group.stringify_tmpl("a"), group.stringify_tmpl("a"));
+    assert_eq!(
+        cached_group.get_tmpl_gen_object("a").unwrap(),
+        group.get_tmpl_gen_object("a").unwrap(),
+    );
+    assert_eq!(
+        cached_group
+            .direct_dependencies("a")
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec!["b".to_string()],
+    );
+    assert!(cached_group.update_tmpl("a", &[]).is_err());
+}
+
+#[test]
+fn json_shape() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", "{{ a }}");
+    let json = serde_json::to_value(group.get_tree("a").unwrap()).unwrap();
+    let range = serde_json::json!({
+        "start": { "line": 0, "utf16_col": 3 },
+        "end": { "line": 0, "utf16_col": 4 },
+    });
+    assert_eq!(json["version"], parse::TEMPLATE_TREE_FORMAT_VERSION);
+    assert_eq!(json["path"], "a");
+    assert_eq!(
+        json["content"][0]["Text"]["Dynamic"]["expression"],
+        serde_json::json!({ "DataField": { "name": "a", "location": range } }),
+    );
+    assert!(json["content"][0]["Text"]["Dynamic"]
+        .get("binding_map_keys")
+        .is_none());
+}
+
+#[test]
+fn format_version_checked() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", "{{ a }}");
+    let mut json = serde_json::to_value(group.get_tree("a").unwrap()).unwrap();
+    json["version"] = (parse::TEMPLATE_TREE_FORMAT_VERSION + 1).into();
+    let err = serde_json::from_value::<parse::Template>(json).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("unsupported template tree format version"));
+}
+
+#[test]
+fn binding_map_regenerated() {
+    const SRC: &str = r#"<div title="{{ a }}">{{ a + 1 }}</div>"#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC);
+    let mut cached_group = TmplGroup::new();
+    cached_group.add_tmpl_tree(round_trip(&group, "a"));
+    assert_eq!(
+        cached_group.get_tmpl_gen_object("a").unwrap(),
+        group.get_tmpl_gen_object("a").unwrap(),
+    );
+}
@@ -0,0 +1,178 @@
+use glass_easel_template_compiler::*;
+use serde_json::json;
+
+fn render(src: &str, data: serde_json::Value) -> String {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", src);
+    group.render_html("a", &data).unwrap()
+}
+
+#[test]
+fn basic_elements() {
+    assert_eq!(
+        render(
+            r#"<view id="v-{{ id }}" hidden="{{ h }}" disabled>{{ a }} &amp; {{ b }}</view><input value="{{ s }}" /><text>{{ n / 2 }}</text>"#,
+            json!({ "id": 1, "h": false, "a": "<x>", "s": "\"q\"", "n": 3 }),
+        ),
+        r#"<view id="v-1" disabled>&lt;x> &amp; </view><input value="&quot;q&quot;"><text>1.5</text>"#,
+    );
+}
+
+#[test]
+fn class_and_style() {
+    assert_eq!(
+        render(
+            r#"<view class="  a {{ b }}  c " style="color: {{ c }}" data-my-value="{{ 1 }}" />"#,
+            json!({ "b": "b1 b2", "c": "red" }),
+        ),
+        r#"<view class="a b1 b2 c" style="color: red" data-my-value="1"></view>"#,
+    );
+    assert_eq!(
+        render(
+            r#"<view class:a class:b="{{ x }}" class:c="{{ !x }}" style:color="{{ c }}" style:width="1px" />"#,
+            json!({ "x": true, "c": "red" }),
+        ),
+        r#"<view class="a b" style="color:red;width:1px;"></view>"#,
+    );
+}
+
+#[test]
+fn if_and_for() {
+    const SRC: &str = r#"
+        <block wx:for="{{ list }}" wx:key="id">
+            <view wx:if="{{ item.v > 1 }}">{{ index }}:{{ item.v }}</view>
+            <view wx:elif="{{ item.v === 1 }}">one</view>
+            <view wx:else>none</view>
+        </block>
+        <text wx:for="{{ obj }}" wx:for-item="v" wx:for-index="k">{{ k }}={{ v }};</text>
+        <text wx:for="{{ 2 }}">{{ item }}</text>
+    "#;
+    assert_eq!(
+        render(
+            SRC,
+            json!({
+                "list": [{ "id": 1, "v": 0 }, { "id": 2, "v": 1 }, { "id": 3, "v": 2 }],
+                "obj": { "a": 1, "b": "x" },
+            }),
+        ),
+        "<view>none</view><view>one</view><view>2:2</view><text>a=1;</text><text>b=x;</text><text>0</text><text>1</text>",
+    );
+}
+
+#[test]
+fn template_and_include() {
+    let mut group = TmplGroup::new();
+    group.add_tmpl(
+        "lib",
+        r#"<template name="item"><li>{{ label }}</li></template><template name="other">lib</template>"#,
+    );
+    group.add_tmpl("header", r#"<h1>{{ title }}</h1>"#);
+    group.add_tmpl(
+        "page",
+        r#"
+            <import src="lib" />
+            <template name="other">local</template>
+            <include src="header" />
+            <ul><template wx:for="{{ list }}" is="item" data="{{ label: item }}" /></ul>
+            <template is="{{ t }}" />
+            <template is="missing" />
+        "#,
+    );
+    assert_eq!(
+        group
+            .render_html(
+                "page",
+                &json!({ "title": "T", "list": ["a", "b"], "t": "other" })
+            )
+            .unwrap(),
+        "<h1>T</h1><ul><li>a</li><li>b</li></ul>local",
+    );
+
+    group.add_tmpl("loop", r#"<include src="loop" />"#);
+    assert!(group.render_html("loop", &json!({})).is_err());
+}
+
+#[test]
+fn host_callbacks() {
+    struct Host;
+    impl ssr::SsrHost for Host {
+        fn call_script(
+            &mut self,
+            module: &str,
+            func: &str,
+            args: Vec<serde_json::Value>,
+        ) -> Option<serde_json::Value> {
+            Some(json!(format!("{}.{}({})", module, func, args[0])))
+        }
+
+        fn render_slot(
+            &mut self,
+            name: &str,
+            values: &serde_json::Map<String, serde_json::Value>,
+        ) -> Option<String> {
+            Some(format!(
+                "[{}:{}]",
+                name,
+                serde_json::Value::Object(values.clone())
+            ))
+        }
+    }
+
+    const SRC: &str = r#"
+        <wxs module="m">exports.f = function (x) { return x }</wxs>
+        <wxs module="u" src="/utils/u.wxs" />
+        <view>{{ m.f(a) }}|{{ u.g(a + 1) }}</view>
+        <slot /><slot name="s" value="{{ a }}" />
+    "#;
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", SRC);
+    assert_eq!(
+        group.render_html("a", &json!({ "a": 1 })).unwrap(),
+        "<view>|</view>",
+    );
+    assert_eq!(
+        group
+            .render_html_with_host("a", &json!({ "a": 1 }), &mut Host)
+            .unwrap(),
+        r#"<view>a#m.f(1)|utils/u.g(2)</view>[:{}][s:{"value":1}]"#,
+    );
+}
+
+#[test]
+fn for_object_key_order() {
+    assert_eq!(
+        render(
+            r#"<block wx:for="{{ o }}">{{ index }}={{ item }};</block>|<block wx:for="{{ { z: 1, ...o } }}">{{ index }};</block>"#,
+            json!({ "o": { "b": 1, "a": 2, "10": 3, "9": 4, "01": 5 } }),
+        ),
+        "9=4;10=3;b=1;a=2;01=5;|9;10;z;b;a;01;",
+    );
+}
+
+#[test]
+fn value_less_let_var() {
+    assert_eq!(render(r#"<block let:x>[{{ x }}]</block>"#, json!({})), "[]");
+}
+
+#[test]
+fn value_less_slot_value() {
+    struct Host;
+    impl ssr::SsrHost for Host {
+        fn render_slot(
+            &mut self,
+            _name: &str,
+            values: &serde_json::Map<String, serde_json::Value>,
+        ) -> Option<String> {
+            Some(serde_json::Value::Object(values.clone()).to_string())
+        }
+    }
+
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", r#"<slot v />"#);
+    assert_eq!(
+        group
+            .render_html_with_host("a", &json!({}), &mut Host)
+            .unwrap(),
+        r#"{"v":""}"#,
+    );
+}
@@ -0,0 +1,91 @@
+//! Type-check the converted expressions with `tsc` .
+//!
+//! It is skipped if `tsc` is not found.
+
+use std::collections::BTreeSet;
+use std::process::Command;
+
+use glass_easel_template_compiler::{parse::Position, TmplGroup};
+
+const TS_ENV: &str = r#"
+declare const data: {}
+declare const methods: {
+    good: (e: { detail: { value: string, cursor: number } }) => void
+    bad: (e: { detail: { value: number } }) => void
+    goodTap: (e: { detail: { x: number, y: number } }) => void
+    badTap: (e: { detail: { value: string } }) => void
+}
+declare const tags: { [other: string]: any }
+export default {}
+"#;
+
+/// Returns the (0-based) template lines with type errors.
+fn type_error_lines(src: &str) -> Option<BTreeSet<u32>> {
+    let mut group = TmplGroup::new();
+    group.add_tmpl("a", src);
+    let expr = group.get_tmpl_converted_expr("a", TS_ENV).unwrap();
+    let dir = std::env::temp_dir().join(format!("glass-easel-ts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("a.ts");
+    std::fs::write(&file, expr.code()).unwrap();
+    let output = Command::new("tsc")
+        .arg("--noEmit")
+        .arg("--strict")
+        .arg("--pretty")
+        .arg("false")
+        .arg(&file)
+        .output();
+    let _ = std::fs::remove_dir_all(&dir);
+    let output = output.ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = BTreeSet::new();
+    for msg in stdout.lines() {
+        // the format is `<file>(<line>,<col>): error TS<code>: <message>`
+        let Some((loc, _)) = msg.split_once("): error") else {
+            continue;
+        };
+        let Some((_, loc)) = loc.rsplit_once('(') else {
+            continue;
+        };
+        let (line, col) = loc.split_once(',').unwrap();
+        let pos = Position {
+            line: line.parse::<u32>().unwrap() - 1,
+            utf16_col: col.parse::<u32>().unwrap() - 1,
+        };
+        let src_loc = expr
+            .get_source_location(pos..pos)
+            .unwrap_or_else(|| panic!("no source location for {:?}", msg));
+        lines.insert(src_loc.start.line);
+    }
+    Some(lines)
+}
+
+#[test]
+fn mismatched_event_handlers() {
+    let src = r#"<input bind:input="good" />
+<input bind:input="bad" />
+<view bind:tap="goodTap" />
+<view bind:tap="badTap" />
+<view worklet:tap="goodTap" />
+<view worklet:tap="badTap" />
+"#;
+    let Some(lines) = type_error_lines(src) else {
+        eprintln!("`tsc` not found, skipped");
+        return;
+    };
+    assert_eq!(lines, vec![1, 3, 5].into_iter().collect::<BTreeSet<_>>());
+}
+
+#[test]
+fn mismatched_template_data() {
+    let src = r#"<template name="x">{{ c.d }}</template>
+<template is="x" data="{{ c: { d: 1 } }}" />
+<template is="x" data="{{ e: 1 }}" />
+<template is="x" data="{{ c: { d: 2 }, e: 1 }}" />
+"#;
+    let Some(lines) = type_error_lines(src) else {
+        eprintln!("`tsc` not found, skipped");
+        return;
+    };
+    assert_eq!(lines, vec![2].into_iter().collect::<BTreeSet<_>>());
+}
@@ -416,6 +416,32 @@ describe('binding expression', () => {
     expect(onAChanged).toBeCalledTimes(4)
   })
 
+  test('template literal strings', () => {
+    const def = glassEasel.registerElement({
+      template: tmpl(`
+        <div id="a" class="{{ \`item-\${ index }\${ active ? ' active' : '' }\` }}"></div>
+        <div id="b" data-a="{{ \`\${ a.b }/\${ c }\\\`\` }}"></div>
+      `),
+      data: {
+        index: 1,
+        active: false,
+        a: { b: 'x' } as { b: string } | null,
+        c: undefined as number | undefined,
+      },
+    })
+    const elem = glassEasel.Component.createWithContext('root', def.general(), domBackend)
+    const eA = elem.getShadowRoot()!.getElementById('a')!
+    const eB = elem.getShadowRoot()!.getElementById('b')!
+    expect(eA.class).toBe('item-1')
+    expect(eB.dataset.a).toBe('x/undefined`')
+    elem.setData({ active: true })
+    expect(eA.class).toBe('item-1 active')
+    elem.setData({ 'a.b': 'y', c: 2 })
+    expect(eB.dataset.a).toBe('y/2`')
+    elem.setData({ a: null })
+    expect(eB.dataset.a).toBe('undefined/2`')
+  })
+
   test('static member visit and update', () => {
     const def = glassEasel.registerElement({
       template: tmpl(`
@@ -536,4 +562,38 @@ describe('binding expression', () => {
     elem.setData({ a: 10 })
     expect(n1.dataset.a).toEqual(11)
   })
+
+  test('optional chaining', () => {
+    const def = glassEasel.registerElement({
+      template: tmpl(`
+        <div id="a" data-a="{{ a?.b }}"></div>
+        <div id="b" data-a="{{ a?.[c]?.d }}"></div>
+        <div id="c" data-a="{{ a?.f?.(1) }}"></div>
+      `),
+      data: {
+        a: null as { b: { d: number }; f: (x: number) => number } | null,
+        c: 'b',
+      },
+    })
+    const elem = glassEasel.Component.createWithContext('root', def.general(), domBackend)
+    const eA = elem.getShadowRoot()!.getElementById('a')!
+    const eB = elem.getShadowRoot()!.getElementById('b')!
+    const eC = elem.getShadowRoot()!.getElementById('c')!
+    expect(eA.dataset.a).toBeUndefined()
+    expect(eB.dataset.a).toBeUndefined()
+    expect(eC.dataset.a).toBeUndefined()
+    elem.setData({ a: { b: { d: 123 }, f: (x: number) => x + 1 } })
+    expect(eA.dataset.a).toEqual({ d: 123 })
+    expect(eB.dataset.a).toEqual(123)
+    expect(eC.dataset.a).toEqual(2)
+    elem.setData({ 'a.b.d': 456 })
+    expect(eA.dataset.a).toEqual({ d: 456 })
+    expect(eB.dataset.a).toEqual(456)
+    elem.setData({ c: 'f' })
+    expect(eB.dataset.a).toBeUndefined()
+    elem.setData({ a: null })
+    expect(eA.dataset.a).toBeUndefined()
+    expect(eB.dataset.a).toBeUndefined()
+    expect(eC.dataset.a).toBeUndefined()
+  })
 })